@@ -0,0 +1,77 @@
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::stats;
+use crate::table::{localize, print_table};
+use crate::TOTALS;
+
+/// Print the area under each cumulative curve, computed with the trapezoidal rule over the calls
+/// common to all inputs, and its ratio to the area of the first input.
+///
+/// The normalized area is the area divided by the area of a curve that reaches the same total
+/// at the last call, growing linearly. Curves that do most of their work early have a normalized
+/// area above 1, curves that do it late below 1.
+pub fn auc(inputs: &[Input]) {
+    if inputs.is_empty() {
+        return;
+    }
+
+    let mut rows: Vec<Vec<String>> = vec![];
+
+    for (metric, _, per_call_col_idx) in TOTALS.iter() {
+        let cumulatives: Vec<Vec<f64>> = inputs
+            .iter()
+            .map(|input| {
+                let values: Vec<f64> = augment::try_column_values(&input.path, *per_call_col_idx)
+                    .unwrap_or_else(|err| {
+                        errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+                    })
+                    .into_iter()
+                    .map(|value| value as f64)
+                    .collect();
+                stats::cumulative(&values)
+            })
+            .collect();
+
+        let num_calls = cumulatives.iter().map(Vec::len).min().unwrap();
+
+        let areas: Vec<f64> = cumulatives
+            .iter()
+            .map(|cumulative| stats::trapezoid_area(&cumulative[..num_calls]))
+            .collect();
+
+        for ((input, cumulative), area) in inputs.iter().zip(&cumulatives).zip(&areas) {
+            let linear_area = match num_calls {
+                0 => 0.0,
+                _ => cumulative[num_calls - 1] * (num_calls - 1) as f64 / 2.0,
+            };
+
+            rows.push(vec![
+                metric.to_string(),
+                input.label.clone(),
+                localize(&format!("{:.0}", area)),
+                ratio(*area, areas[0]),
+                ratio(*area, linear_area),
+            ]);
+        }
+    }
+
+    print_table(
+        &[
+            "metric",
+            "input",
+            "area",
+            &format!("ratio to {}", inputs[0].label),
+            "normalized",
+        ],
+        &rows,
+    );
+}
+
+fn ratio(a: f64, b: f64) -> String {
+    if b == 0.0 {
+        "-".to_owned()
+    } else {
+        localize(&format!("{:.4}", a / b))
+    }
+}
@@ -0,0 +1,451 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use indicatif::ProgressBar;
+use tempfile::NamedTempFile;
+
+use crate::cache;
+use crate::data::{parse_u64, Table, Totals};
+use crate::inputs::DRUN_HEADERS;
+use crate::TOTALS;
+
+/// Directory (relative to the working directory) that `--incremental` keeps augmented CSVs and
+/// their processing state in
+const INCREMENTAL_DIR: &str = ".generate_plots_incremental";
+
+// Given a canister perf CSV file path, write to a temporary path with a "total instructions",
+// "total accessed host pages", and "total dirtied host pages" columns, see
+// `Table::push_cumulative_columns`.
+pub fn add_cumulative_columns(csv_path: &Path) -> NamedTempFile {
+    add_cumulative_columns_with_progress(csv_path, &ProgressBar::hidden(), false)
+}
+
+/// Number of records between updates of the progress bar in `read_augmented`
+const PROGRESS_UPDATE_INTERVAL: usize = 1000;
+
+/// Like `add_cumulative_columns`, but advances `progress` by the number of bytes of the input
+/// read and shows the number of records processed as its message. With `mmap` the input is memory
+/// mapped and parsed from the mapped bytes, which is faster for very large inputs.
+pub fn add_cumulative_columns_with_progress(
+    csv_path: &Path,
+    progress: &ProgressBar,
+    mmap: bool,
+) -> NamedTempFile {
+    write_temp_csv(&read_augmented(csv_path, progress, mmap))
+}
+
+/// Write a table to a temporary CSV file.
+pub fn write_temp_csv(table: &Table) -> NamedTempFile {
+    let mut csv_writer = csv::Writer::from_writer(NamedTempFile::new().unwrap());
+    table.write_csv(&mut csv_writer, true);
+    csv_writer.into_inner().unwrap()
+}
+
+/// Parse a canister perf CSV and append the cumulative columns. See
+/// `add_cumulative_columns_with_progress` for the arguments.
+pub fn read_augmented(csv_path: &Path, progress: &ProgressBar, mmap: bool) -> Table {
+    let mut table = if mmap {
+        let file = std::fs::File::open(csv_path).unwrap();
+        // Safety: the input must not be modified while it's being processed, which is already
+        // needed for consistent results
+        let map = unsafe { memmap2::Mmap::map(&file) }.expect("Unable to memory map input");
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(&map[..]);
+        read_table(reader, csv_path, progress)
+    } else {
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .from_path(csv_path)
+            .unwrap();
+        read_table(reader, csv_path, progress)
+    };
+
+    table.push_cumulative_columns(&mut Totals::default());
+    table
+}
+
+fn read_table<R: Read>(
+    mut reader: csv::Reader<R>,
+    csv_path: &Path,
+    progress: &ProgressBar,
+) -> Table {
+    let headers: Vec<String> = reader
+        .headers()
+        .unwrap()
+        .iter()
+        .map(str::to_owned)
+        .collect();
+
+    let file_name = csv_path.display();
+    log::debug!("Augmenting {}", file_name);
+
+    let mut table = Table::new(headers);
+
+    // Inputs may be processed in parallel, so advance the bar by deltas rather than setting its
+    // position
+    let mut reported_bytes = 0;
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).unwrap() {
+        if table.num_rows.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            let bytes = record.position().unwrap().byte();
+            progress.inc(bytes - reported_bytes);
+            reported_bytes = bytes;
+            progress.set_message(format!("{}: {} rows", file_name, table.num_rows));
+        }
+        table.push_record(&record);
+    }
+
+    let input_len = std::fs::metadata(csv_path).unwrap().len();
+    progress.inc(input_len.saturating_sub(reported_bytes));
+    progress.set_message(format!("{}: {} rows", file_name, table.num_rows));
+
+    table
+}
+
+/// Values of the given (1-based) columns in the last record of a CSV. Zeros when the CSV has no
+/// records.
+pub fn last_values(csv_path: &Path, col_idxs: &[usize]) -> Vec<u64> {
+    let mut reader = csv::Reader::from_path(csv_path).unwrap();
+
+    let mut values = vec![0; col_idxs.len()];
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).unwrap() {
+        for (value, col_idx) in values.iter_mut().zip(col_idxs) {
+            *value = parse_u64(&record[col_idx - 1]).unwrap();
+        }
+    }
+
+    values
+}
+
+/// 1-based index of the column with the given header, ignoring case.
+pub fn find_column(csv_path: &Path, header: &str) -> Option<usize> {
+    let mut reader = csv::Reader::from_path(csv_path).unwrap();
+    let col_idx = reader
+        .headers()
+        .unwrap()
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(header))
+        .map(|idx| idx + 1);
+    log::debug!(
+        "{}: column '{}' is at index {:?}",
+        csv_path.display(),
+        header,
+        col_idx
+    );
+    col_idx
+}
+
+/// 1-based index in the augmented CSVs of a (1-based) column of an input. The cumulative columns
+/// are inserted after the columns of drun generated CSVs, see `Table::push_cumulative_columns`.
+pub fn augmented_col_idx(input_col_idx: usize) -> usize {
+    if input_col_idx <= DRUN_HEADERS.len() {
+        input_col_idx
+    } else {
+        input_col_idx + TOTALS.len()
+    }
+}
+
+/// 1-based index in an input of a (1-based) column of the augmented CSVs. `None` for the
+/// cumulative columns.
+pub fn input_col_idx(col_idx: usize) -> Option<usize> {
+    if col_idx <= DRUN_HEADERS.len() {
+        Some(col_idx)
+    } else if col_idx <= DRUN_HEADERS.len() + TOTALS.len() {
+        None
+    } else {
+        Some(col_idx - TOTALS.len())
+    }
+}
+
+/// Number of columns in the header of a CSV, or an error message when the CSV can't be read.
+pub fn num_columns(csv_path: &Path) -> Result<usize, String> {
+    let mut reader = csv::Reader::from_path(csv_path)
+        .map_err(|err| format!("Unable to read {}: {}", csv_path.display(), err))?;
+    let headers = reader
+        .headers()
+        .map_err(|err| format!("Unable to read {}: {}", csv_path.display(), err))?;
+    Ok(headers.len())
+}
+
+/// Values of a (1-based) column in every record of a CSV, or an error message when the CSV can't
+/// be read or a record has no number in the column.
+pub fn try_column_values(csv_path: &Path, col_idx: usize) -> Result<Vec<u64>, String> {
+    let mut reader = csv::Reader::from_path(csv_path)
+        .map_err(|err| format!("Unable to read {}: {}", csv_path.display(), err))?;
+    let mut values = vec![];
+    let mut record = csv::ByteRecord::new();
+    while reader
+        .read_byte_record(&mut record)
+        .map_err(|err| format!("Unable to read {}: {}", csv_path.display(), err))?
+    {
+        let value = record.get(col_idx - 1).and_then(parse_u64).ok_or_else(|| {
+            format!(
+                "{}: no number in column {} on line {}",
+                csv_path.display(),
+                col_idx,
+                record.position().map_or(0, |position| position.line())
+            )
+        })?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// How much of an input CSV `add_cumulative_columns_incremental` has processed
+struct IncrementalState {
+    /// Number of bytes of the input processed so far. Always at the end of a line.
+    offset: u64,
+    /// Hash of the first `offset` bytes of the input. Used to detect inputs that have been
+    /// modified, rather than appended to, since the previous run.
+    prefix_hash: String,
+    totals: Totals,
+}
+
+/// Like `add_cumulative_columns`, but writes to a persistent file in `INCREMENTAL_DIR` and
+/// returns its path. When the input has only grown since the previous call, just the appended
+/// records are processed, continuing from the stored totals. Otherwise the file is regenerated
+/// from scratch.
+///
+/// A trailing line without a newline is assumed to be still being written and is left for the
+/// next call.
+///
+/// The files are named after the canonical path of the input, so the input must be the file as
+/// it's given rather than a preprocessed temporary copy, which gets a new path on every run.
+pub fn add_cumulative_columns_incremental(csv_path: &Path) -> PathBuf {
+    add_cumulative_columns_incremental_in(csv_path, Path::new(INCREMENTAL_DIR))
+}
+
+/// `add_cumulative_columns_incremental` with the files in `dir`
+fn add_cumulative_columns_incremental_in(csv_path: &Path, dir: &Path) -> PathBuf {
+    std::fs::create_dir_all(dir).expect("Unable to create incremental state directory");
+
+    // Inputs with the same file name in different directories get different files
+    let canonical_path = std::fs::canonicalize(csv_path).unwrap_or_else(|_| csv_path.to_owned());
+    let path_hash = cache::hash_parts(&[canonical_path.to_string_lossy().as_bytes()]);
+    let file_name = format!(
+        "{}_{}.csv",
+        csv_path.file_stem().unwrap().to_string_lossy(),
+        &path_hash[..16]
+    );
+    let out_path = dir.join(&file_name);
+    let state_path = dir.join(format!("{}.state", file_name));
+
+    let mut input = std::fs::File::open(csv_path).unwrap();
+    let input_len = input.metadata().unwrap().len();
+
+    let state = if out_path.exists() {
+        load_state(&state_path)
+    } else {
+        None
+    }
+    .filter(|state| {
+        state.offset <= input_len && prefix_hash(&mut input, state.offset) == state.prefix_hash
+    });
+
+    let (mut totals, offset, mut writer) = match state {
+        Some(state) => {
+            let out = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&out_path)
+                .unwrap();
+            (state.totals, state.offset, csv::Writer::from_writer(out))
+        }
+        None => (
+            Totals::default(),
+            0,
+            csv::Writer::from_path(&out_path).unwrap(),
+        ),
+    };
+
+    input.seek(SeekFrom::Start(offset)).unwrap();
+    let mut tail = vec![];
+    input.read_to_end(&mut tail).unwrap();
+
+    let complete_len = tail
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |idx| idx + 1);
+    tail.truncate(complete_len);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(offset == 0)
+        .from_reader(&tail[..]);
+
+    let headers: Vec<String> = if offset == 0 {
+        reader
+            .headers()
+            .unwrap()
+            .iter()
+            .map(str::to_owned)
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut table = Table::new(headers);
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).unwrap() {
+        table.push_record(&record);
+    }
+    table.push_cumulative_columns(&mut totals);
+    table.write_csv(&mut writer, offset == 0);
+    writer.flush().unwrap();
+    let new_rows = table.num_rows;
+
+    log::info!(
+        "{}: {} new rows{}",
+        csv_path.display(),
+        new_rows,
+        if offset == 0 { " (from scratch)" } else { "" }
+    );
+
+    let new_offset = offset + complete_len as u64;
+    save_state(
+        &state_path,
+        &IncrementalState {
+            offset: new_offset,
+            prefix_hash: prefix_hash(&mut input, new_offset),
+            totals,
+        },
+    );
+
+    out_path
+}
+
+/// Hash of the first `len` bytes of a file, see `IncrementalState::prefix_hash`
+fn prefix_hash(file: &mut std::fs::File, len: u64) -> String {
+    file.seek(SeekFrom::Start(0)).unwrap();
+    cache::hash_reader(file.take(len)).unwrap()
+}
+
+fn load_state(state_path: &Path) -> Option<IncrementalState> {
+    let mut reader = csv::Reader::from_path(state_path).ok()?;
+    let record = reader.records().next()?.ok()?;
+    Some(IncrementalState {
+        offset: record[0].parse().ok()?,
+        prefix_hash: record[1].to_owned(),
+        totals: Totals {
+            instructions: record[2].parse().ok()?,
+            accessed_host_pages: record[3].parse().ok()?,
+            dirtied_host_pages: record[4].parse().ok()?,
+        },
+    })
+}
+
+fn save_state(state_path: &Path, state: &IncrementalState) {
+    let mut writer = csv::Writer::from_path(state_path).unwrap();
+    writer
+        .write_record([
+            "offset",
+            "prefix hash",
+            "total instructions",
+            "total accessed host pages",
+            "total dirtied host pages",
+        ])
+        .unwrap();
+    writer
+        .write_record([
+            state.offset.to_string(),
+            state.prefix_hash.clone(),
+            state.totals.instructions.to_string(),
+            state.totals.accessed_host_pages.to_string(),
+            state.totals.dirtied_host_pages.to_string(),
+        ])
+        .unwrap();
+    writer.flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "message,method,instructions,accessed host pages,dirtied host pages,\
+                          total Wasm pages in use\n";
+
+    fn row(message: usize) -> String {
+        format!(
+            "{},update foo,{},{},{},2\n",
+            message,
+            message * 100,
+            message,
+            message % 3
+        )
+    }
+
+    fn rows(messages: std::ops::Range<usize>) -> String {
+        messages.map(row).collect()
+    }
+
+    /// Augmented CSV of `contents` from scratch, to compare the incremental ones with
+    fn from_scratch(contents: &str) -> String {
+        let input = NamedTempFile::new().unwrap();
+        std::fs::write(input.path(), contents).unwrap();
+        std::fs::read_to_string(add_cumulative_columns(input.path())).unwrap()
+    }
+
+    fn incremental(csv_path: &Path, dir: &Path) -> String {
+        std::fs::read_to_string(add_cumulative_columns_incremental_in(csv_path, dir)).unwrap()
+    }
+
+    #[test]
+    fn incremental_appended_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("perf.csv");
+        let state_dir = dir.path().join("state");
+
+        std::fs::write(&csv_path, format!("{}{}", HEADER, rows(1..10))).unwrap();
+        assert_eq!(
+            incremental(&csv_path, &state_dir),
+            from_scratch(&format!("{}{}", HEADER, rows(1..10)))
+        );
+
+        let contents = format!("{}{}", HEADER, rows(1..25));
+        std::fs::write(&csv_path, &contents).unwrap();
+        assert_eq!(incremental(&csv_path, &state_dir), from_scratch(&contents));
+
+        // Only the appended rows were processed
+        let out_path = add_cumulative_columns_incremental_in(&csv_path, &state_dir);
+        let state_path = PathBuf::from(format!("{}.state", out_path.display()));
+        assert_eq!(
+            load_state(&state_path).unwrap().offset,
+            contents.len() as u64
+        );
+    }
+
+    #[test]
+    fn incremental_modified_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("perf.csv");
+        let state_dir = dir.path().join("state");
+
+        std::fs::write(&csv_path, format!("{}{}", HEADER, rows(1..10))).unwrap();
+        incremental(&csv_path, &state_dir);
+
+        // A different first call (of the same length) and two more calls: the part of the input
+        // that the state covers changed, so the stored totals are stale
+        let contents = format!("{}{}{}", HEADER, row(9), rows(2..12));
+        std::fs::write(&csv_path, &contents).unwrap();
+        assert_eq!(incremental(&csv_path, &state_dir), from_scratch(&contents));
+    }
+
+    #[test]
+    fn incremental_trailing_partial_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("perf.csv");
+        let state_dir = dir.path().join("state");
+
+        let partial = row(10);
+        let partial = &partial[..partial.len() / 2];
+        std::fs::write(&csv_path, format!("{}{}{}", HEADER, rows(1..10), partial)).unwrap();
+        assert_eq!(
+            incremental(&csv_path, &state_dir),
+            from_scratch(&format!("{}{}", HEADER, rows(1..10)))
+        );
+
+        let contents = format!("{}{}", HEADER, rows(1..12));
+        std::fs::write(&csv_path, &contents).unwrap();
+        assert_eq!(incremental(&csv_path, &state_dir), from_scratch(&contents));
+    }
+}
@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use crate::errors;
+use crate::html;
+use crate::metrics;
+use crate::plot;
+use crate::runs;
+use crate::stats;
+use crate::TOTALS;
+
+/// Directory that `badge` writes the badges to by default
+pub const DEFAULT_BADGE_DIR: &str = "badges";
+
+/// Colors of the right halves of badges: decreased totals are improvements
+const IMPROVED_COLOR: &str = "#4c1";
+const REGRESSED_COLOR: &str = "#e05d44";
+const UNCHANGED_COLOR: &str = "#9f9f9f";
+
+/// Changes of totals smaller than this (a fraction) are shown as unchanged
+const UNCHANGED_THRESHOLD: f64 = 0.0005;
+
+/// Write an SVG badge like "total instructions: -12.3%" to `dir` for each cumulative metric (an
+/// entry of `TOTALS`) of each input label of the archived run `tag`, comparing its final total
+/// with the one in `baseline`. Without `tag` the latest run is used, and without `baseline` the
+/// latest run before it that is marked as a baseline. Badges are named
+/// `<label>_<metric>.svg`. Inputs with the same label in a run are repetitions, and their totals
+/// are averaged.
+pub fn write_badges(tag: Option<&str>, baseline: Option<&str>, dir: &Path) {
+    let runs = runs::load_all();
+    let run_idx = match tag {
+        Some(tag) => runs.iter().position(|run| run.tag == tag),
+        None => runs.len().checked_sub(1),
+    };
+    let run_idx = run_idx.unwrap_or_else(|| {
+        errors::exit(
+            errors::Category::Runs,
+            None,
+            None,
+            &match tag {
+                Some(tag) => format!("Run '{}' not found in {}", tag, runs::RUNS_DIR),
+                None => format!("No archived runs in {}", runs::RUNS_DIR),
+            },
+        )
+    });
+    let run = &runs[run_idx];
+    let baseline = match baseline {
+        Some(baseline) => runs
+            .iter()
+            .find(|run| run.tag == baseline)
+            .unwrap_or_else(|| {
+                errors::exit(
+                    errors::Category::Runs,
+                    None,
+                    None,
+                    &format!("Run '{}' not found in {}", baseline, runs::RUNS_DIR),
+                )
+            }),
+        None => runs[..run_idx]
+            .iter()
+            .rev()
+            .find(|run| run.baseline)
+            .unwrap_or_else(|| {
+                errors::exit(
+                    errors::Category::Runs,
+                    None,
+                    None,
+                    &format!(
+                        "No baseline before run '{}', mark one with `runs baseline` or give \
+                         --baseline",
+                        run.tag
+                    ),
+                )
+            }),
+    };
+    log::info!("Comparing run '{}' with '{}'", run.tag, baseline.tag);
+
+    std::fs::create_dir_all(dir).unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Output,
+            Some(dir),
+            None,
+            &format!("Unable to create {}: {}", dir.display(), err),
+        )
+    });
+    // Badges are written again for every new run, replacing the ones of the previous run
+    plot::own_output_dir(dir);
+
+    let total_col_idxs: Vec<usize> = TOTALS.iter().map(|(_, col_idx, _)| *col_idx).collect();
+    for label in runs::labels([run]) {
+        let totals = runs::mean_totals(&runs::repetitions(run, label), &total_col_idxs);
+        let baseline_inputs = runs::repetitions(baseline, label);
+        let baseline_totals = if baseline_inputs.is_empty() {
+            None
+        } else {
+            Some(runs::mean_totals(&baseline_inputs, &total_col_idxs))
+        };
+        for (idx, (name, col_idx, _)) in TOTALS.iter().enumerate() {
+            let metric = metrics::display_name(*col_idx, name);
+            let (value, color) = match &baseline_totals {
+                None => ("no baseline".to_owned(), UNCHANGED_COLOR),
+                Some(baseline_totals) => {
+                    let delta =
+                        stats::signed_relative_difference(baseline_totals[idx], totals[idx]);
+                    if !delta.is_finite() {
+                        ("no baseline".to_owned(), UNCHANGED_COLOR)
+                    } else if delta.abs() < UNCHANGED_THRESHOLD {
+                        ("±0.0%".to_owned(), UNCHANGED_COLOR)
+                    } else if delta < 0.0 {
+                        (format!("−{:.1}%", -delta * 100.0), IMPROVED_COLOR)
+                    } else {
+                        (format!("+{:.1}%", delta * 100.0), REGRESSED_COLOR)
+                    }
+                }
+            };
+
+            let path = dir.join(format!(
+                "{}_{}.svg",
+                plot::file_name_slug(label),
+                plot::file_name_slug(name)
+            ));
+            plot::write_output(&path, badge(&metric, &value, color).as_bytes()).unwrap_or_else(
+                |err| {
+                    errors::exit(
+                        errors::Category::Output,
+                        Some(&path),
+                        None,
+                        &format!("Unable to write {}: {}", path.display(), err),
+                    )
+                },
+            );
+            log::info!("Wrote {} ({}: {})", path.display(), metric, value);
+        }
+    }
+}
+
+/// A badge in the style of shields.io, with `label` on grey and `value` on `color`
+fn badge(label: &str, value: &str, color: &str) -> String {
+    // Widths are estimated from the number of characters, as the text isn't measured
+    let text_width = |text: &str| text.chars().count() as u32 * 7;
+    let label_width = text_width(label) + 10;
+    let value_width = text_width(value) + 10;
+    let width = label_width + value_width;
+    let label = html::escape(label);
+    let value = html::escape(value);
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+<title>{label}: {value}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)">
+<rect width="{label_width}" height="20" fill="#555"/>
+<rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/>
+<rect width="{width}" height="20" fill="url(#s)"/>
+</g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text>
+<text x="{label_x}" y="14">{label}</text>
+<text x="{value_x}" y="15" fill="#010101" fill-opacity=".3">{value}</text>
+<text x="{value_x}" y="14">{value}</text>
+</g>
+</svg>
+"##,
+        width = width,
+        label_width = label_width,
+        value_width = value_width,
+        color = color,
+        label = label,
+        value = value,
+        label_x = label_width / 2,
+        value_x = label_width + value_width / 2,
+    )
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sha2::{Digest, Sha256};
+
+use crate::errors;
+use crate::table::print_table;
+
+/// File in the working directory recording the hash of the inputs each generated plot was
+/// rendered from
+const CACHE_FILE: &str = ".generate_plots_cache.csv";
+
+/// Maps generated files to the hash of everything they were generated from. A file is up to date
+/// when it exists and its recorded hash matches the hash of the current inputs.
+pub struct Cache {
+    entries: HashMap<PathBuf, String>,
+}
+
+impl Cache {
+    /// The cache of the previous runs. A cache file that can't be parsed (e.g. truncated by an
+    /// interrupted run) is ignored with a warning, so that everything is generated again.
+    pub fn load() -> Cache {
+        let entries = match csv::Reader::from_path(CACHE_FILE) {
+            Ok(reader) => read_entries(reader).unwrap_or_else(|err| {
+                log::warn!("{}: {}, regenerating everything", CACHE_FILE, err);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Cache { entries }
+    }
+
+    pub fn is_up_to_date(&self, output: &Path, hash: &str) -> bool {
+        output.exists() && self.entries.get(output).map(String::as_str) == Some(hash)
+    }
+
+    /// Check the generated outputs against the hashes of what they'd be generated from now, and
+    /// print a table of their states. The ones that aren't up to date are counted in
+    /// `num_stale`.
+    pub fn verify(&self, outputs: &[(PathBuf, String)]) {
+        let mut rows: Vec<Vec<String>> = vec![];
+        for (output, hash) in outputs {
+            let state = if !output.exists() {
+                "missing"
+            } else if self.is_up_to_date(output, hash) {
+                "up to date"
+            } else if self.entries.contains_key(output) {
+                "stale"
+            } else {
+                // Generated before the cache, or by other means
+                "unknown"
+            };
+            if state != "up to date" {
+                NUM_STALE.fetch_add(1, Ordering::Relaxed);
+            }
+            rows.push(vec![output.display().to_string(), state.to_owned()]);
+        }
+        print_table(&["output", "state"], &rows);
+    }
+
+    pub fn insert(&mut self, output: PathBuf, hash: String) {
+        self.entries.insert(output, hash);
+    }
+
+    pub fn save(&self) {
+        let mut entries: Vec<(&PathBuf, &String)> = self.entries.iter().collect();
+        entries.sort();
+
+        let mut writer = csv::Writer::from_path(CACHE_FILE).unwrap();
+        writer.write_record(["output", "hash"]).unwrap();
+        for (output, hash) in entries {
+            writer
+                .write_record([output.to_string_lossy().as_ref(), hash.as_str()])
+                .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+}
+
+/// Outputs found not to be up to date by `Cache::verify`
+static NUM_STALE: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of outputs that `Cache::verify` found to be stale, missing, or of unknown origin
+pub fn num_stale() -> usize {
+    NUM_STALE.load(Ordering::Relaxed)
+}
+
+/// The outputs and hashes of the records of a cache file
+fn read_entries(
+    mut reader: csv::Reader<std::fs::File>,
+) -> Result<HashMap<PathBuf, String>, String> {
+    let mut entries = HashMap::new();
+    for record in reader.records() {
+        let record = record.map_err(|err| err.to_string())?;
+        if record.len() != 2 {
+            return Err(format!("expected 2 fields, found {}", record.len()));
+        }
+        entries.insert(PathBuf::from(&record[0]), record[1].to_owned());
+    }
+    Ok(entries)
+}
+
+/// Hex-encoded SHA-256 of a file's contents, read in chunks. Exits with an error when the file
+/// can't be read.
+pub fn hash_file(path: &Path) -> String {
+    std::fs::File::open(path)
+        .and_then(hash_reader)
+        .unwrap_or_else(|err| {
+            errors::exit(
+                errors::Category::Input,
+                Some(path),
+                None,
+                &format!("Unable to read {}: {}", path.display(), err),
+            )
+        })
+}
+
+/// Hex-encoded SHA-256 of everything read from `reader`, without holding it in memory.
+pub fn hash_reader(mut reader: impl std::io::Read) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => hasher.update(&buf[..len]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Hex-encoded SHA-256 of a sequence of byte strings. Parts are length-prefixed so that moving
+/// bytes from one part to the next changes the hash.
+pub fn hash_parts(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,131 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::{Array, ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_ipc::reader::FileReader;
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+use indicatif::ProgressBar;
+use tempfile::NamedTempFile;
+
+use crate::augment;
+use crate::cache;
+use crate::data::{Column, Table};
+
+/// Directory (relative to the working directory) that `--columnar-cache` keeps the augmented
+/// inputs in, as Arrow IPC files named after the hashes of the inputs
+const COLUMNAR_CACHE_DIR: &str = ".generate_plots_columnar";
+
+/// Like `augment::add_cumulative_columns_with_progress`, but the augmented columns are stored in
+/// an Arrow IPC file keyed by the contents of the input. When the input hasn't changed since a
+/// previous call the CSV for gnuplot is written from the stored columns, without parsing the input
+/// or computing the totals again.
+pub fn add_cumulative_columns_cached(
+    csv_path: &Path,
+    progress: &ProgressBar,
+    mmap: bool,
+) -> NamedTempFile {
+    let input_len = std::fs::metadata(csv_path).unwrap().len();
+    let ipc_path =
+        Path::new(COLUMNAR_CACHE_DIR).join(format!("{}.arrow", cache::hash_file(csv_path)));
+
+    if let Some(table) = read_ipc(&ipc_path) {
+        log::debug!(
+            "{}: using columnar cache {}",
+            csv_path.display(),
+            ipc_path.display()
+        );
+        progress.inc(input_len);
+        return augment::write_temp_csv(&table);
+    }
+
+    let table = augment::read_augmented(csv_path, progress, mmap);
+    write_ipc(&table, &ipc_path);
+    log::debug!(
+        "{}: stored columnar cache {}",
+        csv_path.display(),
+        ipc_path.display()
+    );
+    augment::write_temp_csv(&table)
+}
+
+/// Store the columns of an augmented input in an Arrow IPC file.
+fn write_ipc(table: &Table, ipc_path: &Path) {
+    let mut fields: Vec<Field> = Vec::with_capacity(table.columns.len());
+    let mut arrays: Vec<ArrayRef> = Vec::with_capacity(table.columns.len());
+
+    for (header, column) in table.headers.iter().zip(table.columns.iter()) {
+        let (data_type, array): (DataType, ArrayRef) = match column {
+            Column::UInt(values) => (
+                DataType::UInt64,
+                Arc::new(UInt64Array::from(values.clone())),
+            ),
+            Column::Float(values) => (
+                DataType::Float64,
+                Arc::new(Float64Array::from(values.clone())),
+            ),
+            Column::Text(values) => (DataType::Utf8, Arc::new(StringArray::from(values.clone()))),
+        };
+        fields.push(Field::new(header, data_type, false));
+        arrays.push(array);
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays).unwrap();
+
+    std::fs::create_dir_all(COLUMNAR_CACHE_DIR).expect("Unable to create columnar cache directory");
+
+    // Write to a temporary file first so that an interrupted run doesn't leave a truncated file
+    let tmp = NamedTempFile::new_in(COLUMNAR_CACHE_DIR).unwrap();
+    let mut writer = FileWriter::try_new(tmp.as_file(), &schema).unwrap();
+    writer.write(&batch).unwrap();
+    writer.finish().unwrap();
+    drop(writer);
+    tmp.persist(ipc_path)
+        .expect("Unable to write columnar cache file");
+}
+
+/// Read the columns stored by `write_ipc`. `None` when the file doesn't exist or can't be read.
+fn read_ipc(ipc_path: &Path) -> Option<Table> {
+    let file = std::fs::File::open(ipc_path).ok()?;
+    let reader = FileReader::try_new(file, None).ok()?;
+    let schema = reader.schema();
+
+    let headers: Vec<String> = schema
+        .fields()
+        .iter()
+        .map(|field| field.name().to_owned())
+        .collect();
+    let mut columns: Vec<Column> = vec![];
+    let mut num_rows = 0;
+
+    for batch in reader {
+        let batch = batch.ok()?;
+        num_rows += batch.num_rows();
+        for (col_idx, array) in batch.columns().iter().enumerate() {
+            let any = array.as_any();
+            let column = if let Some(values) = any.downcast_ref::<UInt64Array>() {
+                Column::UInt(values.values().to_vec())
+            } else if let Some(values) = any.downcast_ref::<Float64Array>() {
+                Column::Float(values.values().to_vec())
+            } else if let Some(values) = any.downcast_ref::<StringArray>() {
+                Column::Text(values.iter().map(|v| v.unwrap_or("").to_owned()).collect())
+            } else {
+                return None;
+            };
+            match (columns.get_mut(col_idx), column) {
+                (None, column) => columns.push(column),
+                (Some(Column::UInt(values)), Column::UInt(more)) => values.extend(more),
+                (Some(Column::Float(values)), Column::Float(more)) => values.extend(more),
+                (Some(Column::Text(values)), Column::Text(more)) => values.extend(more),
+                _ => return None,
+            }
+        }
+    }
+
+    Some(Table {
+        headers,
+        columns,
+        num_rows,
+    })
+}
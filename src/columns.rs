@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use crate::augment;
+use crate::errors;
+use crate::inputs::DRUN_HEADERS;
+use crate::table::print_table;
+use crate::TOTALS;
+
+/// Print the columns of a CSV, with the cumulative columns added as in the augmented CSVs the
+/// plots are generated from, with their 1-based indices, types inferred from all values, and the
+/// first non-empty value.
+pub fn columns(csv_path: &Path) {
+    let rows: Vec<Vec<String>> = infer_columns(csv_path)
+        .into_iter()
+        .enumerate()
+        .map(|(col_idx, column)| {
+            let mut header = column.header;
+            if column.added {
+                header.push_str(" (added)");
+            }
+            vec![
+                (col_idx + 1).to_string(),
+                header,
+                column.ty.name().to_owned(),
+                column.example.unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    print_table(&["index", "header", "type", "example"], &rows);
+}
+
+/// A column of an augmented CSV
+pub struct Column {
+    pub header: String,
+    pub ty: ColumnType,
+    /// First non-empty value
+    pub example: Option<String>,
+    /// Whether the column is one of the cumulative columns added by `add_cumulative_columns`
+    pub added: bool,
+}
+
+/// Columns of a CSV with the cumulative columns added, in order. Exits with an error message
+/// when the CSV can't be read.
+pub fn infer_columns(csv_path: &Path) -> Vec<Column> {
+    let num_input_columns = csv::Reader::from_path(csv_path)
+        .unwrap_or_else(|err| {
+            errors::exit(
+                errors::Category::Input,
+                Some(csv_path),
+                None,
+                &format!("Unable to read {}: {}", csv_path.display(), err),
+            );
+        })
+        .headers()
+        .unwrap()
+        .len();
+
+    let augmented = augment::add_cumulative_columns(csv_path);
+    let mut reader = csv::Reader::from_path(augmented.path()).unwrap();
+    let headers = reader.headers().unwrap().to_owned();
+
+    // See `Table::push_cumulative_columns`
+    let added_at = num_input_columns.min(DRUN_HEADERS.len());
+    let mut columns: Vec<Column> = headers
+        .iter()
+        .enumerate()
+        .map(|(col_idx, header)| Column {
+            header: header.to_owned(),
+            ty: ColumnType::Empty,
+            example: None,
+            added: (added_at..added_at + TOTALS.len()).contains(&col_idx),
+        })
+        .collect();
+
+    for record in reader.records() {
+        let record = record.unwrap();
+        for (field, column) in record.iter().zip(columns.iter_mut()) {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+            column.ty = column.ty.join(ColumnType::of(field));
+            if column.example.is_none() {
+                column.example = Some(field.to_owned());
+            }
+        }
+    }
+
+    columns
+}
+
+/// Most specific type that all non-empty values of a column have
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    /// No non-empty values
+    Empty,
+    Integer,
+    Float,
+    Text,
+}
+
+impl ColumnType {
+    fn of(field: &str) -> ColumnType {
+        if field.parse::<i64>().is_ok() || field.parse::<u64>().is_ok() {
+            ColumnType::Integer
+        } else if field.parse::<f64>().is_ok() {
+            ColumnType::Float
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    fn join(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (ColumnType::Empty, ty) | (ty, ColumnType::Empty) => ty,
+            (ColumnType::Text, _) | (_, ColumnType::Text) => ColumnType::Text,
+            (ColumnType::Float, _) | (_, ColumnType::Float) => ColumnType::Float,
+            (ColumnType::Integer, ColumnType::Integer) => ColumnType::Integer,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Text => "text",
+        }
+    }
+}
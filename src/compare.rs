@@ -0,0 +1,169 @@
+use crate::augment;
+use crate::errors;
+use crate::plot::{self, Series, SeriesStyle};
+use crate::progress;
+use crate::runs;
+use crate::stats;
+use crate::table::{localize, print_table};
+use crate::TOTALS;
+
+/// Plot the archived data of two runs on top of each other and print a table of the differences
+/// in their final totals, and the geometric mean of the `tag_b`/`tag_a` ratios of the totals for
+/// each input. Inputs of the two runs are paired by label. Inputs with the same label in a run
+/// are repetitions of the same configuration: their totals are averaged, and their per-call
+/// values are tested for a significant difference. Shows a progress bar unless `quiet`.
+pub fn compare(tag_a: &str, tag_b: &str, quiet: bool) {
+    let run_a = runs::load(tag_a);
+    let run_b = runs::load(tag_b);
+
+    let runs = [&run_a, &run_b];
+
+    let labels: Vec<String> = runs
+        .iter()
+        .flat_map(|run| {
+            run.inputs
+                .iter()
+                .map(move |input| format!("{} ({})", input.label, run.tag))
+        })
+        .collect();
+
+    let style = SeriesStyle::default();
+    let series: Vec<Series> = runs
+        .iter()
+        .flat_map(|run| run.inputs.iter())
+        .zip(labels.iter())
+        .map(|(input, label)| Series {
+            data: &input.data,
+            label,
+            style: &style,
+            offset: 0,
+            markers: &[],
+        })
+        .collect();
+
+    let progress = progress::plots_bar(crate::PLOTS.len() as u64, quiet);
+    plot::render_plots(
+        &series,
+        &crate::PLOTS,
+        &format!("compare_{}_{}_", tag_a, tag_b),
+        &progress,
+    );
+    progress.finish_and_clear();
+
+    let total_col_idxs: Vec<usize> = TOTALS.iter().map(|(_, col_idx, _)| *col_idx).collect();
+
+    let mut rows: Vec<Vec<String>> = vec![];
+    let mut summary_rows: Vec<Vec<String>> = vec![];
+    for label in runs::labels([&run_a]) {
+        // Inputs with the same label are repetitions of the same configuration
+        let inputs_a: Vec<&runs::RunInput> = runs::repetitions(&run_a, label);
+        let inputs_b: Vec<&runs::RunInput> = runs::repetitions(&run_b, label);
+
+        if inputs_b.is_empty() {
+            println!(
+                "'{}' is not in run '{}', skipping in delta table",
+                label, tag_b
+            );
+            continue;
+        }
+
+        let totals_a = runs::mean_totals(&inputs_a, &total_col_idxs);
+        let totals_b = runs::mean_totals(&inputs_b, &total_col_idxs);
+
+        let mut ratios: Vec<f64> = vec![];
+
+        for (((metric, _, per_call_col_idx), a), b) in TOTALS.iter().zip(totals_a).zip(totals_b) {
+            // Ratios with a zero are not meaningful in a geometric mean
+            if a != 0.0 && b != 0.0 {
+                ratios.push(b / a);
+            }
+
+            let samples_a = per_call_samples(&inputs_a, *per_call_col_idx);
+            let samples_b = per_call_samples(&inputs_b, *per_call_col_idx);
+            let p_value = stats::mann_whitney_u(&samples_a, &samples_b);
+
+            rows.push(vec![
+                label.to_owned(),
+                metric.to_string(),
+                localize(&format!("{:.0}", a)),
+                localize(&format!("{:.0}", b)),
+                localize(&format!("{:+.0}", b - a)),
+                format_relative_delta(a, b),
+                match p_value {
+                    Some(p_value) => format!(
+                        "{}{}",
+                        localize(&format!("{:.4}", p_value)),
+                        significance_marker(p_value)
+                    ),
+                    None => "-".to_owned(),
+                },
+                format!("{}/{}", inputs_a.len(), inputs_b.len()),
+            ]);
+        }
+
+        if !ratios.is_empty() {
+            let geomean = stats::geometric_mean(&ratios);
+            summary_rows.push(vec![
+                label.to_owned(),
+                localize(&format!("{:.4}", geomean)),
+                localize(&format!("{:+.2}%", (geomean - 1.0) * 100.0)),
+                ratios.len().to_string(),
+            ]);
+        }
+    }
+
+    print_table(
+        &[
+            "input",
+            "metric",
+            tag_a,
+            tag_b,
+            "delta",
+            "delta %",
+            "p-value",
+            "repetitions",
+        ],
+        &rows,
+    );
+    println!(
+        "p-values are from a two-sided Mann-Whitney U test on per-call values of all repetitions \
+         (* p < 0.05, ** p < 0.01)"
+    );
+
+    println!();
+    println!("Geometric mean of {}/{} ratios:", tag_b, tag_a);
+    print_table(
+        &["input", "geomean ratio", "change", "metrics"],
+        &summary_rows,
+    );
+}
+
+/// Values of a column in every record of every repetition
+fn per_call_samples(inputs: &[&runs::RunInput], col_idx: usize) -> Vec<f64> {
+    inputs
+        .iter()
+        .flat_map(|input| {
+            augment::try_column_values(&input.data, col_idx).unwrap_or_else(|err| {
+                errors::exit(errors::Category::Input, Some(&input.data), None, &err)
+            })
+        })
+        .map(|value| value as f64)
+        .collect()
+}
+
+fn significance_marker(p_value: f64) -> &'static str {
+    if p_value < 0.01 {
+        " **"
+    } else if p_value < 0.05 {
+        " *"
+    } else {
+        ""
+    }
+}
+
+fn format_relative_delta(a: f64, b: f64) -> String {
+    if a == 0.0 {
+        return "-".to_owned();
+    }
+    localize(&format!("{:+.2}%", (b - a) / a * 100.0))
+}
@@ -0,0 +1,933 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::columns::{self, ColumnType};
+use crate::errors;
+use crate::export;
+use crate::inputs;
+use crate::plot;
+use crate::ratios;
+use crate::stacks;
+use crate::{PLOTS, TOTALS};
+
+/// Configuration file read from the working directory when no `--config` is given
+pub const CONFIG_FILE: &str = "generate_plots.toml";
+
+/// Contents of a configuration file. Everything is optional, missing parts fall back to the
+/// command line arguments and the built-in defaults.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Inputs used when none are given on the command line
+    #[serde(default, rename = "input")]
+    pub inputs: Vec<InputConfig>,
+
+    /// Plots to generate instead of `PLOTS`
+    #[serde(default, rename = "plot")]
+    pub plots: Vec<PlotConfig>,
+
+    /// Units, scaling, and names of columns
+    #[serde(default, rename = "metric")]
+    pub metrics: Vec<MetricConfig>,
+
+    /// Headers of the input columns with the roles of drun's columns, for CSVs with other layouts
+    #[serde(default)]
+    pub columns: ColumnMapping,
+
+    /// Which columns get plots when they're discovered automatically
+    #[serde(default)]
+    pub discover: Discover,
+
+    /// `[profile.<name>]` tables, selected with `--profile`
+    #[serde(default, rename = "profile")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// `[suite.<name>]` tables, run with `--suite` or `--all`
+    #[serde(default, rename = "suite")]
+    pub suites: BTreeMap<String, Suite>,
+
+    /// Per-call ratios of two plotted columns, by name, e.g. `"dirty fraction" =
+    /// "dirtied_host_pages / accessed_host_pages"`
+    #[serde(default, rename = "ratio")]
+    pub ratios: BTreeMap<String, String>,
+
+    /// Plotted columns that are components of a cost, by name, stacked in a plot per input, e.g.
+    /// `"instructions by component" = ["mutator_instructions", "gc_instructions"]`
+    #[serde(default, rename = "stack")]
+    pub stacks: BTreeMap<String, Vec<String>>,
+}
+
+/// A `[suite.<name>]` table: a benchmark suite, with its own inputs and plots directory
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    #[serde(default, rename = "input")]
+    pub inputs: Vec<InputConfig>,
+    /// Directory of the plots, relative to `--output-dir`. The name of the suite by default.
+    pub output_dir: Option<PathBuf>,
+}
+
+/// A `[profile.<name>]` table. The parts it has replace the ones of the profile it inherits from,
+/// or of the top level of the file when it doesn't inherit from another profile.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Name of another profile to start from
+    pub inherits: Option<String>,
+    #[serde(rename = "input")]
+    pub inputs: Option<Vec<InputConfig>>,
+    #[serde(rename = "plot")]
+    pub plots: Option<Vec<PlotConfig>>,
+    #[serde(rename = "metric")]
+    pub metrics: Option<Vec<MetricConfig>>,
+    pub columns: Option<ColumnMapping>,
+    pub discover: Option<Discover>,
+}
+
+impl Config {
+    /// The configuration with the parts of profile `name` (and the profiles it inherits from)
+    /// applied. Fails when a profile doesn't exist or inherits from itself.
+    fn with_profile(mut self, name: &str, path: &Path) -> Result<Config, String> {
+        // From `name` to the profile that doesn't inherit from another one
+        let mut chain: Vec<String> = vec![];
+        let mut next = Some(name.to_owned());
+        while let Some(name) = next {
+            if chain.contains(&name) {
+                return Err(format!(
+                    "Profile '{}' in {} inherits from itself: {} -> {}",
+                    name,
+                    path.display(),
+                    chain.join(" -> "),
+                    name
+                ));
+            }
+            let profile = match self.profiles.get(&name) {
+                Some(profile) => profile,
+                None => {
+                    let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                    return Err(format!(
+                        "No profile '{}' in {}, profiles are: {}",
+                        name,
+                        path.display(),
+                        if names.is_empty() {
+                            "(none)".to_owned()
+                        } else {
+                            names.join(", ")
+                        }
+                    ));
+                }
+            };
+            next = profile.inherits.clone();
+            chain.push(name);
+        }
+
+        log::debug!("Using profile {}", chain.join(" <- "));
+
+        for name in chain.iter().rev() {
+            let profile = self.profiles.remove(name).unwrap();
+            if let Some(inputs) = profile.inputs {
+                self.inputs = inputs;
+            }
+            if let Some(plots) = profile.plots {
+                self.plots = plots;
+            }
+            if let Some(metrics) = profile.metrics {
+                self.metrics = metrics;
+            }
+            if let Some(columns) = profile.columns {
+                self.columns = columns;
+            }
+            if let Some(discover) = profile.discover {
+                self.discover = discover;
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+/// The `[discover]` table: regular expressions selecting the numeric columns that get plots when
+/// plots are discovered from the columns of the inputs. Patterns match either the header (e.g.
+/// "total instructions") or the plot name (e.g. "total_instructions").
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Discover {
+    /// Only columns matching one of these, all columns when empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Columns matching one of these are left out, even when included
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Discover {
+    /// Whether a column with the given header and plot name should get a plot. Exits with an error
+    /// when a pattern is not a valid regular expression.
+    pub fn selects(&self, header: &str, plot_name: &str) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns.iter().any(|pattern| {
+                let regex = Regex::new(pattern).unwrap_or_else(|err| {
+                    errors::exit(
+                        errors::Category::Config,
+                        None,
+                        None,
+                        &format!("Invalid pattern '{}' in [discover]: {}", pattern, err),
+                    );
+                });
+                regex.is_match(header) || regex.is_match(plot_name)
+            })
+        };
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+}
+
+/// The `[columns]` table: headers (ignoring case) of the input columns to use as the columns of
+/// drun generated CSVs. See `inputs::map_columns`.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnMapping {
+    pub message: Option<String>,
+    pub method: Option<String>,
+    pub instructions: Option<String>,
+    pub accessed_pages: Option<String>,
+    pub dirtied_pages: Option<String>,
+    /// Used as "total Wasm pages in use"
+    pub heap_size: Option<String>,
+    /// Number of rounds of the execution of the message, see `inputs::ROUNDS_HEADER`
+    pub rounds: Option<String>,
+}
+
+impl ColumnMapping {
+    /// Whether no columns are mapped, i.e. the inputs are drun generated CSVs
+    pub fn is_empty(&self) -> bool {
+        self.message.is_none()
+            && self.method.is_none()
+            && self.instructions.is_none()
+            && self.accessed_pages.is_none()
+            && self.dirtied_pages.is_none()
+            && self.heap_size.is_none()
+            && self.rounds.is_none()
+    }
+
+    /// The mapping, with the roles it doesn't map mapped as in `defaults`
+    pub fn or(&self, defaults: ColumnMapping) -> ColumnMapping {
+        ColumnMapping {
+            message: self.message.clone().or(defaults.message),
+            method: self.method.clone().or(defaults.method),
+            instructions: self.instructions.clone().or(defaults.instructions),
+            accessed_pages: self.accessed_pages.clone().or(defaults.accessed_pages),
+            dirtied_pages: self.dirtied_pages.clone().or(defaults.dirtied_pages),
+            heap_size: self.heap_size.clone().or(defaults.heap_size),
+            rounds: self.rounds.clone().or(defaults.rounds),
+        }
+    }
+}
+
+/// An `[[input]]` table, with the same options as `--input`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputConfig {
+    pub path: PathBuf,
+    pub label: Option<String>,
+    pub color: Option<String>,
+    pub dash: Option<u32>,
+    /// Number of leading records (e.g. warm-up calls) to leave out
+    #[serde(default)]
+    pub skip_rows: usize,
+    /// Number of leading calls plotted before the first call of the other inputs
+    #[serde(default)]
+    pub offset: usize,
+    /// Other runs of the same build, aggregated with the one at `path` call by call
+    #[serde(default)]
+    pub repetitions: Vec<PathBuf>,
+    /// `mean` or `median` of the repetitions
+    #[serde(default)]
+    pub aggregate: inputs::Aggregate,
+    /// Calls of the install/upgrade phase that aren't detected by their methods, as 1-based calls
+    /// and ranges of calls, e.g. `"1-3,50"`. See `--setup-phase`.
+    pub setup_calls: Option<String>,
+    /// How the input was produced, e.g. the flags of drun, shown in reports
+    pub description: Option<String>,
+}
+
+/// A `[[plot]]` table
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlotConfig {
+    /// Name of the plot, used as the file name and (with `_`s replaced by spaces) the y label
+    pub name: String,
+    /// 1-based index of the column in the augmented CSVs
+    pub column: usize,
+    /// Title of the plot. No title by default.
+    pub title: Option<String>,
+    /// Label of the x axis, "call" by default
+    pub xlabel: Option<String>,
+    /// Label of the y axis, e.g. with the unit. The name with `_`s replaced by spaces by default.
+    pub ylabel: Option<String>,
+}
+
+/// A `[[metric]]` table, configuring how the values of a column are shown in plots, reports, and
+/// command output
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricConfig {
+    /// 1-based index of the column in the augmented CSVs
+    pub column: usize,
+    /// Name shown instead of the plot or column name, e.g. "instructions"
+    pub name: Option<String>,
+    /// Unit of the scaled values, e.g. "millions" or "pages"
+    pub unit: Option<String>,
+    /// Factor the values are multiplied with, e.g. 0.000001 for millions
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    /// Also plot the running total of the values
+    #[serde(default)]
+    pub cumulative: bool,
+    /// Also plot running totals of the values that restart every this many calls, e.g. to show
+    /// the work of each window of 100 calls
+    pub cumulative_window: Option<usize>,
+    /// Also plot each value as a percentage of the total of the input
+    #[serde(default)]
+    pub percent_of_total: bool,
+    /// Plot log10 of the values instead of the values, for metrics spanning many orders of
+    /// magnitude
+    #[serde(default)]
+    pub log10: bool,
+    /// What empty and `NA` values of the column are replaced with: `zero`, `previous`,
+    /// `interpolate`, or `drop` to leave out their calls
+    pub missing: Option<inputs::Missing>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// Load the configuration file at `path`, with `profile` applied. A missing file is only an
+/// error when `required`, i.e. the path was given explicitly, or a profile is selected. Exits
+/// with an error when the configuration is invalid.
+pub fn load(path: &Path, required: bool, profile: Option<&str>) -> Config {
+    try_load(path, required, profile).unwrap_or_else(|err| {
+        errors::exit(errors::Category::Config, Some(path), None, &err);
+    })
+}
+
+/// Like `load`, but returns the error message instead of exiting.
+pub fn try_load(path: &Path, required: bool, profile: Option<&str>) -> Result<Config, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err)
+            if err.kind() == std::io::ErrorKind::NotFound && !required && profile.is_none() =>
+        {
+            return Ok(Config::default())
+        }
+        Err(err) => return Err(format!("Unable to read {}: {}", path.display(), err)),
+    };
+
+    log::debug!("Loading configuration from {}", path.display());
+
+    let config: Config = match toml::from_str(&contents) {
+        Ok(config) => config,
+        Err(err) => {
+            let suggestion = unknown_field_suggestion(err.message())
+                .map(|field| format!("\ndid you mean `{}`?", field))
+                .unwrap_or_default();
+            return Err(format!(
+                "Invalid configuration file {}: {}{}",
+                path.display(),
+                err.to_string().trim_end(),
+                suggestion
+            ));
+        }
+    };
+
+    let problems = validate(&config);
+    if !problems.is_empty() {
+        // The file parsed, so this can't fail
+        let document = toml::de::DeTable::parse(&contents).unwrap();
+        let mut message = format!("Invalid configuration file {}:", path.display());
+        for problem in &problems {
+            let line = match key_offset(document.get_ref(), &problem.key) {
+                Some(offset) => format!("line {}, ", contents[..offset].lines().count().max(1)),
+                None => String::new(),
+            };
+            message.push_str(&format!(
+                "\n  {}{}: {}",
+                line,
+                display_key(&problem.key),
+                problem.message
+            ));
+        }
+        return Err(message);
+    }
+
+    match profile {
+        Some(profile) => config.with_profile(profile, path),
+        None => Ok(config),
+    }
+}
+
+/// A problem `validate` found with a configuration
+struct Problem {
+    /// Key of the value with the problem, with array indices, e.g. `["input", "1", "path"]`
+    key: Vec<String>,
+    message: String,
+}
+
+impl Problem {
+    fn new(prefix: &[&str], key: &[&str], message: String) -> Problem {
+        Problem {
+            key: prefix
+                .iter()
+                .chain(key)
+                .map(|part| part.to_string())
+                .collect(),
+            message,
+        }
+    }
+}
+
+/// Check the parts of a configuration that parse but can't work: missing input files, columns
+/// that don't exist, plots written to the same file, invalid patterns, ratios and stacks of plots
+/// that don't exist, and profiles inheriting from profiles that don't exist.
+fn validate(config: &Config) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    let operands = ratios::operands(config);
+    for (name, expr) in &config.ratios {
+        if let Err(err) = ratios::parse_ratio(name, expr, &operands) {
+            let names = operands.iter().map(|(plot_name, _)| *plot_name);
+            let suggestion = match err.strip_prefix("there's no plot `") {
+                Some(rest) => did_you_mean(rest.trim_end_matches('`'), names),
+                None => String::new(),
+            };
+            problems.push(Problem::new(
+                &[],
+                &["ratio", name],
+                format!("{}{}", err, suggestion),
+            ));
+        }
+    }
+    for (name, components) in &config.stacks {
+        if let Err(err) = stacks::parse_stack(name, components, &operands) {
+            let names = operands.iter().map(|(plot_name, _)| *plot_name);
+            let suggestion = match err.strip_prefix("there's no plot `") {
+                Some(rest) => did_you_mean(rest.trim_end_matches('`'), names),
+                None => String::new(),
+            };
+            problems.push(Problem::new(
+                &[],
+                &["stack", name],
+                format!("{}{}", err, suggestion),
+            ));
+        }
+    }
+
+    validate_section(
+        &mut problems,
+        &[],
+        &config.inputs,
+        &config.plots,
+        &config.metrics,
+        &config.columns,
+        &config.discover,
+    );
+
+    for (name, profile) in &config.profiles {
+        let prefix = ["profile", name.as_str()];
+        if let Some(inherits) = &profile.inherits {
+            if !config.profiles.contains_key(inherits) {
+                let names = config.profiles.keys().map(String::as_str);
+                problems.push(Problem::new(
+                    &prefix,
+                    &["inherits"],
+                    format!(
+                        "there's no profile `{}`{}",
+                        inherits,
+                        did_you_mean(inherits, names)
+                    ),
+                ));
+            }
+        }
+        // Parts a profile doesn't have are checked at the top level
+        validate_section(
+            &mut problems,
+            &prefix,
+            profile.inputs.as_deref().unwrap_or(&[]),
+            profile.plots.as_deref().unwrap_or(&[]),
+            profile.metrics.as_deref().unwrap_or(&[]),
+            profile.columns.as_ref().unwrap_or(&config.columns),
+            profile.discover.as_ref().unwrap_or(&Discover::default()),
+        );
+    }
+
+    for (name, suite) in &config.suites {
+        if suite.inputs.is_empty() {
+            problems.push(Problem::new(
+                &["suite", name],
+                &[],
+                "suites need at least one [[input]]".to_owned(),
+            ));
+        }
+        validate_section(
+            &mut problems,
+            &["suite", name],
+            &suite.inputs,
+            &[],
+            &[],
+            &config.columns,
+            &Discover::default(),
+        );
+    }
+
+    problems
+}
+
+/// `validate` the top level of a configuration or a profile, with the keys prefixed by `prefix`
+fn validate_section(
+    problems: &mut Vec<Problem>,
+    prefix: &[&str],
+    inputs: &[InputConfig],
+    plots: &[PlotConfig],
+    metrics: &[MetricConfig],
+    columns: &ColumnMapping,
+    discover: &Discover,
+) {
+    for (idx, input) in inputs.iter().enumerate() {
+        let idx_str = idx.to_string();
+        if let Some(Err(err)) = input.color.as_deref().map(plot::check_color) {
+            problems.push(Problem::new(prefix, &["input", &idx_str, "color"], err));
+        }
+        for (repetition_idx, repetition) in input.repetitions.iter().enumerate() {
+            if !repetition.is_file() {
+                problems.push(Problem::new(
+                    prefix,
+                    &[
+                        "input",
+                        &idx_str,
+                        "repetitions",
+                        &repetition_idx.to_string(),
+                    ],
+                    format!("`{}` doesn't exist", repetition.display()),
+                ));
+            }
+        }
+        if !input.path.is_file() {
+            let dir = match input.path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let file_names: Vec<String> = std::fs::read_dir(dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| dir.join(entry.file_name()))
+                        .map(|path| {
+                            let path = path.strip_prefix(".").map(Path::to_owned).unwrap_or(path);
+                            path.to_string_lossy().into_owned()
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            let path = input.path.to_string_lossy();
+            problems.push(Problem::new(
+                prefix,
+                &["input", &idx_str, "path"],
+                format!(
+                    "`{}` doesn't exist{}",
+                    path,
+                    did_you_mean(&path, file_names.iter().map(String::as_str))
+                ),
+            ));
+        }
+    }
+
+    // Headers of the first input, to check the mapped headers and plotted columns
+    let headers: Option<Vec<String>> = inputs
+        .iter()
+        .find_map(|input| csv::Reader::from_path(&input.path).ok())
+        .and_then(|mut reader| {
+            Some(
+                reader
+                    .headers()
+                    .ok()?
+                    .iter()
+                    .map(|header| header.trim().to_owned())
+                    .collect(),
+            )
+        });
+
+    let mapped_headers = [
+        ("message", &columns.message),
+        ("method", &columns.method),
+        ("instructions", &columns.instructions),
+        ("accessed_pages", &columns.accessed_pages),
+        ("dirtied_pages", &columns.dirtied_pages),
+        ("heap_size", &columns.heap_size),
+    ];
+    if let Some(headers) = &headers {
+        for (role, header) in mapped_headers {
+            let header = match header {
+                Some(header) => header,
+                None => continue,
+            };
+            if !headers
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(header.trim()))
+            {
+                problems.push(Problem::new(
+                    prefix,
+                    &["columns", role],
+                    format!(
+                        "the inputs have no column `{}`{}",
+                        header,
+                        did_you_mean(header, headers.iter().map(String::as_str))
+                    ),
+                ));
+            }
+        }
+    }
+
+    // With mapped columns the inputs are rewritten in the drun layout, see `inputs::map_columns`
+    let num_columns = if columns.is_empty() {
+        headers.as_ref().map(|headers| headers.len() + TOTALS.len())
+    } else {
+        Some(inputs::DRUN_HEADERS.len() + TOTALS.len())
+    };
+    // Why a 1-based column index doesn't work
+    let column_problem = |column: usize| match num_columns {
+        _ if column == 0 => Some("columns are 1-based, the first column is 1".to_owned()),
+        Some(num_columns) if column > num_columns => Some(format!(
+            "there's no column {}, the inputs have {} columns with the cumulative columns",
+            column, num_columns
+        )),
+        _ => None,
+    };
+
+    for (idx, plot) in plots.iter().enumerate() {
+        let idx_str = idx.to_string();
+        if let Some(message) = column_problem(plot.column) {
+            problems.push(Problem::new(prefix, &["plot", &idx_str, "column"], message));
+        }
+        if plots[..idx].iter().any(|other| other.name == plot.name) {
+            problems.push(Problem::new(
+                prefix,
+                &["plot", &idx_str, "name"],
+                format!(
+                    "another plot is also named `{}`, they would be written to the same file",
+                    plot.name
+                ),
+            ));
+        }
+    }
+
+    for (idx, metric) in metrics.iter().enumerate() {
+        let idx_str = idx.to_string();
+        if let Some(message) = column_problem(metric.column) {
+            problems.push(Problem::new(
+                prefix,
+                &["metric", &idx_str, "column"],
+                message,
+            ));
+        }
+        if !metric.scale.is_finite() || metric.scale == 0.0 {
+            problems.push(Problem::new(
+                prefix,
+                &["metric", &idx_str, "scale"],
+                format!("scale {} would make every value 0 or invalid", metric.scale),
+            ));
+        }
+        if metrics[..idx]
+            .iter()
+            .any(|other| other.column == metric.column)
+        {
+            problems.push(Problem::new(
+                prefix,
+                &["metric", &idx_str, "column"],
+                format!("column {} already has a [[metric]] table", metric.column),
+            ));
+        }
+    }
+
+    for (part, patterns) in [
+        ("include", &discover.include),
+        ("exclude", &discover.exclude),
+    ] {
+        for (idx, pattern) in patterns.iter().enumerate() {
+            if let Err(err) = Regex::new(pattern) {
+                problems.push(Problem::new(
+                    prefix,
+                    &["discover", part, &idx.to_string()],
+                    format!("invalid pattern: {}", err),
+                ));
+            }
+        }
+    }
+}
+
+/// Byte offset of the value of `key` in the file, or of the innermost part of it that exists
+fn key_offset(document: &toml::de::DeTable, key: &[String]) -> Option<usize> {
+    let mut value = document.get(key.first()?.as_str())?;
+    let mut offset = value.span().start;
+    for part in &key[1..] {
+        let next = match part.parse::<usize>() {
+            Ok(idx) if value.get_ref().is_array() => value.get_ref().get(idx),
+            _ => value.get_ref().get(part.as_str()),
+        };
+        match next {
+            Some(next) => {
+                value = next;
+                offset = value.span().start;
+            }
+            None => break,
+        }
+    }
+    Some(offset)
+}
+
+/// A key as written in TOML, with array indices in brackets, e.g. `input[1].path`
+fn display_key(key: &[String]) -> String {
+    let mut display = String::new();
+    for part in key {
+        if part.parse::<usize>().is_ok() {
+            display.push_str(&format!("[{}]", part));
+        } else {
+            if !display.is_empty() {
+                display.push('.');
+            }
+            // Keys that aren't bare TOML keys are quoted, as in the file
+            if part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                display.push_str(part);
+            } else {
+                display.push_str(&format!("\"{}\"", part));
+            }
+        }
+    }
+    display
+}
+
+/// For serde's "unknown field `x`, expected one of `a`, `b`" errors, the expected field closest to
+/// the unknown one
+fn unknown_field_suggestion(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("unknown field `")?;
+    let (field, expected) = rest.split_once('`')?;
+    // Every other part between backquotes is a field name
+    let fields = expected.split('`').skip(1).step_by(2);
+    closest(field, fields)
+}
+
+/// ", did you mean `x`?" with the candidate closest to `name`, when one is close enough
+fn did_you_mean<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> String {
+    closest(name, candidates)
+        .map(|candidate| format!(", did you mean `{}`?", candidate))
+        .unwrap_or_default()
+}
+
+/// The candidate with the smallest edit distance to `name`, ignoring case, when it's at most a
+/// third of the length of `name` (and at least 2)
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance of two strings, ignoring case
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Write a starter configuration to `path` with the CSVs in the working directory (other than
+/// exported ones) as inputs and their numeric columns as plots. Optional settings are included as
+/// comments. When overwriting a configuration, its `[discover]` patterns select the plots and are
+/// kept.
+pub fn init(path: &Path, force: bool) {
+    if path.exists() && !force {
+        errors::exit(
+            errors::Category::Usage,
+            Some(path),
+            None,
+            &format!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            ),
+        );
+    }
+
+    // A configuration that doesn't parse anymore is overwritten without its patterns
+    let discover = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| toml::from_str::<Config>(&contents).ok())
+        .map(|config| config.discover)
+        .unwrap_or_default();
+
+    let mut csv_paths: Vec<PathBuf> = std::fs::read_dir(".")
+        .expect("Unable to read working directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+                && !path.file_name().is_some_and(|name| {
+                    name.to_string_lossy()
+                        .starts_with(export::EXPORT_FILE_PREFIX)
+                })
+        })
+        .map(|path| path.strip_prefix(".").map(Path::to_owned).unwrap_or(path))
+        .collect();
+    csv_paths.sort();
+
+    let args: Vec<String> = csv_paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    let labels: Vec<String> = if csv_paths.is_empty() {
+        vec![]
+    } else {
+        inputs::parse_inputs(&args, &[], &[])
+            .into_iter()
+            .map(|input| input.label)
+            .collect()
+    };
+
+    let mut config = String::new();
+    config.push_str("# Configuration of generate_plots, generated by `generate_plots init`\n\n");
+
+    config
+        .push_str("# Inputs, used when none are given on the command line. `color` is a gnuplot\n");
+    config.push_str(
+        "# color (e.g. \"#00A000\") and `dash` a gnuplot dash type. `repetitions` lists\n",
+    );
+    config
+        .push_str("# other runs of the same build, e.g. `repetitions = [\"run2.csv\"]`, to plot\n");
+    config.push_str("# the mean of the runs, or their median with `aggregate = \"median\"`.\n");
+    if csv_paths.is_empty() {
+        config.push_str("#\n# No CSVs found in the working directory\n");
+        config.push_str(
+            "#\n# [[input]]\n# path = \"canister_perf.csv\"\n# label = \"My canister\"\n",
+        );
+    }
+    for (path, label) in csv_paths.iter().zip(labels.iter()) {
+        config.push_str(&format!(
+            "\n[[input]]\npath = {}\nlabel = {}\n# color = \"#A00000\"\n# dash = 2\n",
+            toml_string(&path.to_string_lossy()),
+            toml_string(label),
+        ));
+    }
+
+    config.push_str("\n# Plots: file name (without `.png`) and 1-based column index in the CSVs\n");
+    config.push_str("# with the cumulative columns appended. Remove the ones you don't need.\n");
+    for (name, column) in plottable_columns(csv_paths.first(), &discover) {
+        config.push_str(&format!(
+            "\n[[plot]]\nname = {}\ncolumn = {}\n# title = \"...\"\n# ylabel = \"...\"\n",
+            toml_string(&name),
+            column
+        ));
+    }
+
+    config.push_str(
+        "\n# Regular expressions selecting the columns that `init` generates plots for,\n",
+    );
+    config.push_str(
+        "# matching headers (\"total instructions\") or plot names (\"total_instructions\").\n",
+    );
+    if discover.include.is_empty() && discover.exclude.is_empty() {
+        config.push_str(
+            "#\n# [discover]\n# include = [\"^total_\"]\n# exclude = [\".*host pages$\"]\n",
+        );
+    } else {
+        let patterns = |patterns: &[String]| -> String {
+            let patterns: Vec<String> = patterns.iter().map(|p| toml_string(p)).collect();
+            patterns.join(", ")
+        };
+        config.push_str(&format!(
+            "\n[discover]\ninclude = [{}]\nexclude = [{}]\n",
+            patterns(&discover.include),
+            patterns(&discover.exclude)
+        ));
+    }
+
+    config.push_str(
+        "\n# Profiles, selected with `--profile`. The parts a profile has replace the ones\n",
+    );
+    config.push_str("# above, or the ones of the profile it inherits from.\n");
+    config.push_str("#\n# [profile.quick]\n# [[profile.quick.plot]]\n");
+    config.push_str("# name = \"total_instructions\"\n# column = 7\n");
+    config.push_str("#\n# [profile.paper]\n# inherits = \"quick\"\n# [[profile.paper.metric]]\n");
+    config.push_str("# column = 7\n# unit = \"millions\"\n# scale = 0.000001\n");
+
+    config.push_str(
+        "\n# Benchmark suites, each with its inputs and plots directory, generated with\n",
+    );
+    config.push_str("# `--suite <name>`, or all of them and an index with `--all`.\n");
+    config.push_str("#\n# [suite.gc]\n# output_dir = \"gc\"\n# [[suite.gc.input]]\n");
+    config.push_str("# path = \"canister_perf_copying_gc.csv\"\n");
+
+    config.push_str("\n# Per-call ratios of two of the plots above or the built-in plots, each\n");
+    config.push_str("# plotted as `<name>.png`.\n");
+    config.push_str(
+        "#\n# [ratio]\n# \"dirty fraction\" = \"dirtied_host_pages / accessed_host_pages\"\n",
+    );
+
+    config.push_str("\n# Plots that are components of a cost, stacked in a plot per input, each\n");
+    config.push_str("# plotted as `<name>_<label>.png`.\n");
+    config.push_str(
+        "#\n# [stack]\n# \"host pages\" = [\"dirtied_host_pages\", \"accessed_host_pages\"]\n",
+    );
+
+    std::fs::write(path, config).expect("Unable to write configuration file");
+    log::info!("Wrote {} with {} inputs", path.display(), csv_paths.len());
+}
+
+/// Plot names and 1-based column indices of the numeric columns of a CSV selected by `discover`,
+/// including the columns added by `add_cumulative_columns`. The built-in `PLOTS` when there's no
+/// CSV.
+pub fn plottable_columns(csv_path: Option<&PathBuf>, discover: &Discover) -> Vec<(String, usize)> {
+    let csv_path = match csv_path {
+        Some(csv_path) => csv_path,
+        None => {
+            return PLOTS
+                .iter()
+                .map(|(name, column)| (name.to_string(), *column))
+                .collect()
+        }
+    };
+
+    columns::infer_columns(csv_path)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, column)| {
+            // "message" is the index of the call
+            matches!(column.ty, ColumnType::Integer | ColumnType::Float)
+                && !column.header.eq_ignore_ascii_case("message")
+        })
+        .map(|(col_idx, column)| (column.header.replace(' ', "_"), col_idx + 1, column.header))
+        .filter(|(name, _, header)| discover.selects(header, name))
+        .map(|(name, col_idx, _)| (name, col_idx))
+        .collect()
+}
+
+/// A TOML basic string
+fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_owned()).to_string()
+}
@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::plot;
+use crate::stats;
+use crate::table::{localize, print_table};
+use crate::{
+    ACCESSED_HOST_PAGES_COL_IDX, DIRTIED_HOST_PAGES_COL_IDX, INSTRUCTIONS_COL_IDX,
+    WASM_PAGES_COL_IDX,
+};
+
+/// Per-call metrics that are correlated with each other, with their 1-based column indices
+const METRICS: [(&str, usize); 4] = [
+    ("instructions", INSTRUCTIONS_COL_IDX),
+    ("accessed host pages", ACCESSED_HOST_PAGES_COL_IDX),
+    ("dirtied host pages", DIRTIED_HOST_PAGES_COL_IDX),
+    ("Wasm pages in use", WASM_PAGES_COL_IDX),
+];
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Method {
+    /// Linear correlation of the values
+    Pearson,
+    /// Linear correlation of the ranks of the values
+    Spearman,
+}
+
+/// Print a matrix of correlation coefficients between `METRICS` for each input, optionally also
+/// rendering it as a heatmap.
+pub fn correlation(inputs: &[Input], method: Method, heatmap: bool) {
+    for input in inputs {
+        let columns: Vec<Vec<f64>> = METRICS
+            .iter()
+            .map(|(_, col_idx)| {
+                let values: Vec<f64> = augment::try_column_values(&input.path, *col_idx)
+                    .unwrap_or_else(|err| {
+                        errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+                    })
+                    .into_iter()
+                    .map(|value| value as f64)
+                    .collect();
+                match method {
+                    Method::Pearson => values,
+                    Method::Spearman => stats::ranks(&values),
+                }
+            })
+            .collect();
+
+        let matrix: Vec<Vec<Option<f64>>> = columns
+            .iter()
+            .map(|xs| {
+                columns
+                    .iter()
+                    .map(|ys| stats::pearson_correlation(xs, ys))
+                    .collect()
+            })
+            .collect();
+
+        println!("{}:", input.label);
+
+        let mut headers = vec![""];
+        headers.extend(METRICS.iter().map(|(name, _)| *name));
+
+        let rows: Vec<Vec<String>> = METRICS
+            .iter()
+            .zip(matrix.iter())
+            .map(|((name, _), row)| {
+                let mut cells = vec![name.to_string()];
+                cells.extend(row.iter().map(|r| match r {
+                    Some(r) => localize(&format!("{:+.3}", r)),
+                    None => "-".to_owned(),
+                }));
+                cells
+            })
+            .collect();
+
+        print_table(&headers, &rows);
+        println!();
+
+        if heatmap {
+            let path = PathBuf::from(format!(
+                "correlation_{}.png",
+                plot::file_name_slug(&input.label)
+            ));
+            plot::run_gnuplot(&heatmap_program(&input.label, &matrix), &path);
+            log::info!("Wrote {}", path.display());
+        }
+    }
+}
+
+/// Variables of `HEATMAP_TEMPLATE`
+#[derive(Serialize)]
+struct Heatmap<'a> {
+    title: &'a str,
+    metrics: Vec<&'a str>,
+    rows: Vec<Vec<String>>,
+}
+
+fn heatmap_program(label: &str, matrix: &[Vec<Option<f64>>]) -> plot::GnuplotProgram {
+    // Undefined coefficients (constant columns) are plotted as 0
+    let rows = matrix
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|r| format!("{:.4}", r.unwrap_or(0.0)))
+                .collect()
+        })
+        .collect();
+
+    plot::GnuplotProgram::new(
+        HEATMAP_TEMPLATE,
+        &Heatmap {
+            title: label,
+            metrics: METRICS.iter().map(|(name, _)| *name).collect(),
+            rows,
+        },
+    )
+}
+
+const HEATMAP_TEMPLATE: &str = r###"
+set terminal png notransparent rounded giant \
+  font "{{ settings.font | gnuplot_escape }},20" size 1200,1080 {{ settings.background }}
+
+$DATA << EOD
+{% for row in rows %}{{ row | join(sep=" ") }}
+{% endfor %}EOD
+
+{{ settings.theme_commands }}
+set title "{{ title | gnuplot_escape }}"
+
+{% set tics %}{% for metric in metrics %}"{{ metric }}" {{ loop.index0 }}{% if not loop.last %}, {% endif %}{% endfor %}{% endset %}
+set xtics ({{ tics }}) rotate by -30 nomirror
+set ytics ({{ tics }}) nomirror
+set yrange [] reverse
+
+set cbrange [-1:1]
+set palette defined (-1 "#5060D0", 0 "#FFFFFF", 1 "#A00000")
+
+unset key
+
+plot $DATA matrix with image, \
+  $DATA matrix using 1:2:(sprintf("%.2f", $3)) with labels
+"###;
@@ -0,0 +1,245 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::inputs::DRUN_HEADERS;
+use crate::{ACCESSED_HOST_PAGES_COL_IDX, DIRTIED_HOST_PAGES_COL_IDX, INSTRUCTIONS_COL_IDX};
+
+/// Parsed columns of a CSV
+pub struct Table {
+    pub headers: Vec<String>,
+    pub columns: Vec<Column>,
+    pub num_rows: usize,
+}
+
+/// Values of a column, with the most specific type all of them parse as
+pub enum Column {
+    UInt(Vec<u64>),
+    Float(Vec<f64>),
+    Text(Vec<String>),
+}
+
+/// Running totals of the cumulative columns
+#[derive(Default)]
+pub struct Totals {
+    pub instructions: u64,
+    pub accessed_host_pages: u64,
+    pub dirtied_host_pages: u64,
+}
+
+impl Table {
+    /// An empty table. Without headers the number of columns is taken from the first record.
+    pub fn new(headers: Vec<String>) -> Table {
+        Table {
+            columns: headers.iter().map(|_| Column::UInt(vec![])).collect(),
+            headers,
+            num_rows: 0,
+        }
+    }
+
+    /// Parse a record into the columns. The record is only borrowed, so that callers can read
+    /// every record into the same `ByteRecord`. Fields are only validated as UTF-8 when they're
+    /// not unsigned integers.
+    pub fn push_record(&mut self, record: &csv::ByteRecord) {
+        if self.num_rows == 0 && self.columns.is_empty() {
+            self.columns = record.iter().map(|_| Column::UInt(vec![])).collect();
+        }
+        for (column, field) in self.columns.iter_mut().zip(record.iter()) {
+            column.push(field);
+        }
+        self.num_rows += 1;
+    }
+
+    /// Add "total instructions", "total accessed host pages", and "total dirtied host pages"
+    /// columns, continuing from `totals`, which are updated to the totals of the last row. They're
+    /// inserted after the columns of drun generated CSVs, so that they're at the same indices when
+    /// the CSV has more columns, e.g. the rounds of newer drun versions.
+    pub fn push_cumulative_columns(&mut self, totals: &mut Totals) {
+        let instructions = cumulative(
+            self.source_column(INSTRUCTIONS_COL_IDX),
+            &mut totals.instructions,
+        );
+        let accessed_host_pages = cumulative(
+            self.source_column(ACCESSED_HOST_PAGES_COL_IDX),
+            &mut totals.accessed_host_pages,
+        );
+        let dirtied_host_pages = cumulative(
+            self.source_column(DIRTIED_HOST_PAGES_COL_IDX),
+            &mut totals.dirtied_host_pages,
+        );
+
+        // Without headers (see `new`) only the columns are inserted
+        let headers_at = self.headers.len().min(DRUN_HEADERS.len());
+        self.headers.splice(
+            headers_at..headers_at,
+            [
+                "total instructions".to_owned(),
+                "total accessed host pages".to_owned(),
+                "total dirtied host pages".to_owned(),
+            ],
+        );
+        let columns_at = self.columns.len().min(DRUN_HEADERS.len());
+        self.columns.splice(
+            columns_at..columns_at,
+            [
+                Column::UInt(instructions),
+                Column::UInt(accessed_host_pages),
+                Column::UInt(dirtied_host_pages),
+            ],
+        );
+    }
+
+    /// Values of a (1-based) column that the cumulative columns are computed from. Panics when
+    /// the column doesn't exist or has values other than unsigned integers.
+    fn source_column(&self, col_idx: usize) -> &[u64] {
+        if self.num_rows == 0 {
+            return &[];
+        }
+        match self.columns.get(col_idx - 1) {
+            Some(Column::UInt(values)) => values,
+            _ => panic!(
+                "Column {} must exist and contain unsigned integers",
+                col_idx
+            ),
+        }
+    }
+
+    /// Keep only the rows whose `keep` value is true. `keep` has a value for each row.
+    pub fn retain_rows(&mut self, keep: &[bool]) {
+        for column in &mut self.columns {
+            match column {
+                Column::UInt(values) => retain(values, keep),
+                Column::Float(values) => retain(values, keep),
+                Column::Text(values) => retain(values, keep),
+            }
+        }
+        self.num_rows = keep.iter().filter(|keep| **keep).count();
+    }
+
+    /// Write the table as CSV, with the header record when `headers`.
+    pub fn write_csv<W: Write>(&self, writer: &mut csv::Writer<W>, headers: bool) {
+        if headers {
+            writer.write_record(&self.headers).unwrap();
+        }
+
+        let mut int_buffer = itoa::Buffer::new();
+        let mut float_buffer = String::new();
+
+        for row_idx in 0..self.num_rows {
+            for column in &self.columns {
+                match column {
+                    Column::UInt(values) => writer
+                        .write_field(int_buffer.format(values[row_idx]))
+                        .unwrap(),
+                    Column::Float(values) => {
+                        float_buffer.clear();
+                        write!(float_buffer, "{}", values[row_idx]).unwrap();
+                        writer.write_field(&float_buffer).unwrap();
+                    }
+                    Column::Text(values) => writer.write_field(&values[row_idx]).unwrap(),
+                }
+            }
+            writer.write_record(None::<&[u8]>).unwrap();
+        }
+    }
+}
+
+impl Column {
+    /// Add a field, switching to a less specific type when it doesn't parse as the current one.
+    fn push(&mut self, field: &[u8]) {
+        match self {
+            Column::UInt(values) => {
+                if let Some(value) = parse_u64(field) {
+                    values.push(value);
+                } else if let Some(value) = parse_f64(field) {
+                    let mut floats: Vec<f64> = values.iter().map(|value| *value as f64).collect();
+                    floats.push(value);
+                    *self = Column::Float(floats);
+                } else {
+                    let buffer = &mut itoa::Buffer::new();
+                    let mut texts: Vec<String> = values
+                        .iter()
+                        .map(|value| buffer.format(*value).to_owned())
+                        .collect();
+                    texts.push(String::from_utf8_lossy(field).into_owned());
+                    *self = Column::Text(texts);
+                }
+            }
+            Column::Float(values) => {
+                if let Some(value) = parse_f64(field) {
+                    values.push(value);
+                } else {
+                    let mut texts: Vec<String> =
+                        values.iter().map(|value| value.to_string()).collect();
+                    texts.push(String::from_utf8_lossy(field).into_owned());
+                    *self = Column::Text(texts);
+                }
+            }
+            Column::Text(values) => values.push(String::from_utf8_lossy(field).into_owned()),
+        }
+    }
+}
+
+fn retain<T>(values: &mut Vec<T>, keep: &[bool]) {
+    let mut keep = keep.iter();
+    values.retain(|_| *keep.next().unwrap());
+}
+
+/// Running sums of `values`, starting from `total`, which is updated to the final sum.
+fn cumulative(values: &[u64], total: &mut u64) -> Vec<u64> {
+    values
+        .iter()
+        .map(|value| {
+            *total += value;
+            *total
+        })
+        .collect()
+}
+
+/// `str::parse::<u64>().ok()` on a field, several times faster for plain decimal numbers, which
+/// is what drun generates. Anything else is left to `str::parse`.
+pub fn parse_u64(bytes: &[u8]) -> Option<u64> {
+    // 19 digits always fit in a u64
+    if bytes.is_empty() || bytes.len() > 19 {
+        return std::str::from_utf8(bytes).ok()?.parse().ok();
+    }
+
+    let mut value: u64 = 0;
+    for byte in bytes {
+        let digit = byte.wrapping_sub(b'0');
+        if digit > 9 {
+            return std::str::from_utf8(bytes).ok()?.parse().ok();
+        }
+        value = value * 10 + u64::from(digit);
+    }
+    Some(value)
+}
+
+/// `str::parse::<f64>().ok()` on a field
+fn parse_f64(bytes: &[u8]) -> Option<f64> {
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_u64_like_str_parse() {
+        for s in [
+            "0",
+            "123",
+            "007",
+            "",
+            "12a",
+            "-1",
+            "+5",
+            " 1",
+            "1.5",
+            "9999999999999999999",
+            "18446744073709551615",
+            "18446744073709551616",
+        ] {
+            assert_eq!(parse_u64(s.as_bytes()), s.parse().ok(), "{:?}", s);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use crate::augment;
+use crate::errors;
+use crate::metrics;
+use crate::stats;
+use crate::table::{localize, print_table};
+use crate::PLOTS;
+
+/// Print the calls at which `metric` (a name of a configured metric or from `PLOTS`) differs
+/// between the two CSVs by more than `threshold` (a fraction, e.g. 0.1), relative to the value in
+/// `a`.
+pub fn diff(a: &Path, b: &Path, metric: &str, threshold: f64) {
+    let configured = metrics::find_metric(metric);
+    let metric = metric.replace(' ', "_");
+    let col_idx = match configured.or_else(|| {
+        PLOTS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&metric))
+            .map(|(_, col_idx)| *col_idx)
+    }) {
+        Some(col_idx) => col_idx,
+        None => {
+            let names: Vec<&str> = PLOTS.iter().map(|(name, _)| *name).collect();
+            errors::exit(
+                errors::Category::Usage,
+                None,
+                None,
+                &format!(
+                    "Unknown metric '{}', expected one of: {}",
+                    metric,
+                    names.join(", ")
+                ),
+            );
+        }
+    };
+
+    let augmented_a = augment::add_cumulative_columns(a);
+    let augmented_b = augment::add_cumulative_columns(b);
+    let values_a = augment::try_column_values(augmented_a.path(), col_idx)
+        .unwrap_or_else(|err| errors::exit(errors::Category::Input, Some(a), None, &err));
+    let values_b = augment::try_column_values(augmented_b.path(), col_idx)
+        .unwrap_or_else(|err| errors::exit(errors::Category::Input, Some(b), None, &err));
+
+    if values_a.len() != values_b.len() {
+        println!(
+            "Note: {} has {} calls, {} has {}; only the first {} are compared",
+            a.display(),
+            values_a.len(),
+            b.display(),
+            values_b.len(),
+            values_a.len().min(values_b.len())
+        );
+    }
+
+    let rows: Vec<Vec<String>> = values_a
+        .iter()
+        .zip(values_b.iter())
+        .enumerate()
+        .filter_map(|(call_idx, (value_a, value_b))| {
+            let relative = stats::signed_relative_difference(*value_a as f64, *value_b as f64);
+            if relative.abs() <= threshold {
+                return None;
+            }
+            let scale = metrics::scale(col_idx);
+            let delta = if scale == 1.0 {
+                localize(&format!("{:+}", *value_b as i128 - *value_a as i128))
+            } else {
+                localize(&format!(
+                    "{:+.2}",
+                    (*value_b as f64 - *value_a as f64) * scale
+                ))
+            };
+            Some(vec![
+                (call_idx + 1).to_string(),
+                metrics::format_value(col_idx, *value_a),
+                metrics::format_value(col_idx, *value_b),
+                delta,
+                localize(&format!("{:+.2}%", relative * 100.0)),
+            ])
+        })
+        .collect();
+
+    println!(
+        "{} calls where {} differs by more than {}%",
+        rows.len(),
+        metrics::display_name(col_idx, &metric.replace('_', " ")),
+        threshold * 100.0
+    );
+
+    if !rows.is_empty() {
+        let a_name = a.to_string_lossy();
+        let b_name = b.to_string_lossy();
+        print_table(&["call", &a_name, &b_name, "delta", "delta %"], &rows);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::stats;
+use crate::table::{localize, print_table};
+use crate::TOTALS;
+
+/// For each cumulative metric and input, print the first (1-based) call at which the metric's
+/// relative difference to the first input's exceeds `threshold` (a fraction, e.g. 0.05).
+pub fn divergence(inputs: &[Input], threshold: f64) {
+    if inputs.len() < 2 {
+        errors::exit(
+            errors::Category::Usage,
+            None,
+            None,
+            "divergence: at least two inputs are needed",
+        );
+    }
+
+    let mut rows: Vec<Vec<String>> = vec![];
+
+    for (metric, _, per_call_col_idx) in TOTALS.iter() {
+        let cumulatives: Vec<Vec<f64>> = inputs
+            .iter()
+            .map(|input| {
+                let values: Vec<f64> = augment::try_column_values(&input.path, *per_call_col_idx)
+                    .unwrap_or_else(|err| {
+                        errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+                    })
+                    .into_iter()
+                    .map(|value| value as f64)
+                    .collect();
+                stats::cumulative(&values)
+            })
+            .collect();
+
+        let baseline = &cumulatives[0];
+
+        for (input, cumulative) in inputs.iter().zip(&cumulatives).skip(1) {
+            let divergence = baseline
+                .iter()
+                .zip(cumulative.iter())
+                .position(|(a, b)| stats::signed_relative_difference(*a, *b).abs() > threshold);
+
+            let cells = match divergence {
+                Some(call_idx) => vec![
+                    (call_idx + 1).to_string(),
+                    localize(&format!("{:.0}", baseline[call_idx])),
+                    localize(&format!("{:.0}", cumulative[call_idx])),
+                    localize(&format!(
+                        "{:+.2}%",
+                        stats::signed_relative_difference(baseline[call_idx], cumulative[call_idx])
+                            * 100.0
+                    )),
+                ],
+                None => vec![
+                    "never".to_owned(),
+                    "-".to_owned(),
+                    "-".to_owned(),
+                    "-".to_owned(),
+                ],
+            };
+
+            let mut row = vec![metric.to_string(), input.label.clone()];
+            row.extend(cells);
+            rows.push(row);
+        }
+    }
+
+    println!(
+        "First call where cumulative metrics differ from {} by more than {}%:",
+        inputs[0].label,
+        threshold * 100.0
+    );
+    print_table(
+        &[
+            "metric",
+            "input",
+            "call",
+            &inputs[0].label,
+            "input value",
+            "difference",
+        ],
+        &rows,
+    );
+}
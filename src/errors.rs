@@ -0,0 +1,105 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde_json::json;
+
+/// How the errors that the tool exits with are printed to standard error
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// A message
+    Text,
+    /// A JSON object per line with the `category`, `file`, `row`, and `message` of the error.
+    /// `file` and `row` are null when the error isn't about a file or row.
+    Json,
+}
+
+/// Set with `set_error_format`
+static ERROR_FORMAT: Mutex<ErrorFormat> = Mutex::new(ErrorFormat::Text);
+
+/// Print the errors that the tool exits with, including panics, in the given format.
+pub fn set_error_format(format: ErrorFormat) {
+    *ERROR_FORMAT.lock().unwrap() = format;
+    if format == ErrorFormat::Json {
+        std::panic::set_hook(Box::new(|info| {
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            print(
+                Category::Internal,
+                None,
+                None,
+                &match info.location() {
+                    Some(location) => format!("{} (at {})", message, location),
+                    None => message,
+                },
+            );
+        }));
+    }
+}
+
+/// Whether `--error-format json` (or `DRUN_BENCH_ERROR_FORMAT=json`) is given, for errors that
+/// happen before the arguments are parsed: the ones of parsing them
+pub fn json_requested() -> bool {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().any(|arg| arg == "--error-format=json")
+        || args
+            .windows(2)
+            .any(|args| args[0] == "--error-format" && args[1] == "json")
+        || std::env::var("DRUN_BENCH_ERROR_FORMAT").is_ok_and(|format| format == "json")
+}
+
+/// What an error is about
+#[derive(Clone, Copy)]
+pub enum Category {
+    /// Invalid command line arguments
+    Usage,
+    /// Invalid configuration file
+    Config,
+    /// Input CSVs that can't be read or used
+    Input,
+    /// Outputs that can't be written
+    Output,
+    /// Archived runs that don't exist or already exist
+    Runs,
+    /// A bug, or failures of the system (panics)
+    Internal,
+}
+
+impl Category {
+    fn name(self) -> &'static str {
+        match self {
+            Category::Usage => "usage",
+            Category::Config => "config",
+            Category::Input => "input",
+            Category::Output => "output",
+            Category::Runs => "runs",
+            Category::Internal => "internal",
+        }
+    }
+}
+
+/// Print an error about `file` (and its 1-based line `row`) when given, and exit with status 1.
+pub fn exit(category: Category, file: Option<&Path>, row: Option<usize>, message: &str) -> ! {
+    print(category, file, row, message);
+    std::process::exit(1);
+}
+
+fn print(category: Category, file: Option<&Path>, row: Option<usize>, message: &str) {
+    match *ERROR_FORMAT.lock().unwrap() {
+        ErrorFormat::Text => eprintln!("{}", message),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            json!({
+                "category": category.name(),
+                "file": file.map(|file| file.to_string_lossy()),
+                "row": row,
+                "message": message,
+            })
+        ),
+    }
+}
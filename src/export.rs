@@ -0,0 +1,512 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::data::parse_u64;
+use crate::metrics;
+use crate::plot::{self, Series};
+
+/// Formats that plots can be exported to, in addition to the PNGs
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// A Vega-Lite specification per plot, as `<plot name>.vl.json`
+    Vega,
+    /// A Jupyter notebook with the data and a matplotlib cell per plot, as `plots.ipynb`
+    Notebook,
+    /// The augmented inputs joined on the call index into one CSV, as `export_wide.csv`
+    WideCsv,
+}
+
+/// File that `Format::Notebook` writes
+const NOTEBOOK_FILE: &str = "plots.ipynb";
+
+/// File that `Format::WideCsv` writes
+const WIDE_CSV_FILE: &str = "export_wide.csv";
+
+/// Prefix of the CSVs written by exports. `init` skips these when looking for inputs.
+pub const EXPORT_FILE_PREFIX: &str = "export_";
+
+/// How exported plots get their data
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Data {
+    /// Embed the values in each exported file
+    Inline,
+    /// Write the augmented inputs as `export_data_<label>.csv` and refer to them by path
+    Reference,
+}
+
+/// Export the given plots (names and column indices, as in `PLOTS`) of `series`, which must have
+/// augmented CSVs as data. Returns the paths of the exported files.
+pub fn export(
+    format: Format,
+    data: Data,
+    series: &[Series],
+    plots: &[(&str, usize)],
+) -> Vec<PathBuf> {
+    let data_urls: Vec<Option<String>> = series
+        .iter()
+        .map(|series| match data {
+            Data::Inline => None,
+            Data::Reference => Some(write_data_file(series)),
+        })
+        .collect();
+
+    let mut paths = vec![];
+    match format {
+        Format::Vega => {
+            for (plot_name, column_idx) in plots {
+                let path = PathBuf::from(format!("{}.vl.json", plot_name));
+                write_json(
+                    &path,
+                    &vega_lite_spec(plot_name, series, &data_urls, *column_idx),
+                );
+                paths.push(path);
+            }
+        }
+        Format::Notebook => {
+            let path = PathBuf::from(NOTEBOOK_FILE);
+            write_json(&path, &notebook(series, &data_urls, plots));
+            paths.push(path);
+        }
+        Format::WideCsv => {
+            let path = PathBuf::from(WIDE_CSV_FILE);
+            write_wide_csv(&path, series);
+            paths.push(path);
+        }
+    }
+    for path in &paths {
+        log::info!("Wrote {}", path.display());
+    }
+    paths
+}
+
+/// Copy the augmented CSV of a series to the working directory, for exported files to refer to.
+/// Returns the path of the copy.
+fn write_data_file(series: &Series) -> String {
+    let path = format!(
+        "{}data_{}.csv",
+        EXPORT_FILE_PREFIX,
+        plot::file_name_slug(series.label)
+    );
+    let data = std::fs::read(series.data).expect("Unable to read data");
+    plot::write_output(Path::new(&path), &data).expect("Unable to write export data file");
+    log::info!("Wrote {}", path);
+    path
+}
+
+/// Write the CSVs of the series side by side, as a `call` column followed by a
+/// `<header>_<label>` column per column and series. Columns are in the order they first appear in
+/// the series, and series that are shorter than others or lack a column get empty cells.
+fn write_wide_csv(path: &Path, series: &[Series]) {
+    let tables: Vec<(csv::StringRecord, Vec<csv::StringRecord>)> = series
+        .iter()
+        .map(|series| {
+            let mut reader = csv::Reader::from_path(series.data).unwrap();
+            let headers = reader.headers().unwrap().clone();
+            let records = reader.records().map(Result::unwrap).collect();
+            (headers, records)
+        })
+        .collect();
+
+    let mut headers: Vec<&str> = vec![];
+    for (series_headers, _) in &tables {
+        for header in series_headers {
+            if !headers.contains(&header) {
+                headers.push(header);
+            }
+        }
+    }
+
+    // Index of each of the wide CSV's columns in each series, `None` when the series lacks it
+    let mut columns: Vec<(String, usize, Option<usize>)> = vec![];
+    for header in &headers {
+        for (series_idx, (series_headers, _)) in tables.iter().enumerate() {
+            let col_idx = series_headers.iter().position(|h| h == *header);
+            if col_idx.is_some() {
+                columns.push((
+                    format!(
+                        "{}_{}",
+                        header.replace(' ', "_"),
+                        plot::file_name_slug(series[series_idx].label)
+                    ),
+                    series_idx,
+                    col_idx,
+                ));
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .write_record(
+            std::iter::once("call").chain(columns.iter().map(|(name, _, _)| name.as_str())),
+        )
+        .unwrap();
+
+    let num_rows = tables
+        .iter()
+        .map(|(_, records)| records.len())
+        .max()
+        .unwrap_or(0);
+    for row_idx in 0..num_rows {
+        writer.write_field((row_idx + 1).to_string()).unwrap();
+        for (_, series_idx, col_idx) in &columns {
+            let field = tables[*series_idx]
+                .1
+                .get(row_idx)
+                .and_then(|record| record.get(col_idx.unwrap()))
+                .unwrap_or("");
+            writer.write_field(field).unwrap();
+        }
+        writer.write_record(None::<&[u8]>).unwrap();
+    }
+    plot::write_output(path, &writer.into_inner().unwrap()).expect("Unable to write export file");
+}
+
+fn write_json(path: &Path, value: &Value) {
+    let json = serde_json::to_string_pretty(value).unwrap();
+    plot::write_output(path, (json + "\n").as_bytes()).expect("Unable to write export file");
+}
+
+/// A layered Vega-Lite specification with a line per series, styled like the gnuplot plot. The
+/// data of a series is referenced by `data_urls` when given, inlined otherwise.
+fn vega_lite_spec(
+    plot_name: &str,
+    series: &[Series],
+    data_urls: &[Option<String>],
+    column_idx: usize,
+) -> Value {
+    let labels: Vec<&str> = series.iter().map(|series| series.label).collect();
+    let colors: Vec<String> = series_colors(series);
+    let plot_labels = plot::plot_labels(plot_name, column_idx);
+    let title = plot_labels.title.as_deref().unwrap_or(&plot_labels.ylabel);
+    let (x_title, y_title) = (&plot_labels.xlabel, &plot_labels.ylabel);
+    let scale = metrics::scale(column_idx);
+    let cumulative = metrics::is_cumulative_plot(plot_name);
+    let window = metrics::cumulative_window(plot_name);
+    let percent_of_total = metrics::is_percent_of_total_plot(plot_name);
+    let log10 = metrics::log10(column_idx);
+
+    let layers: Vec<Value> = series
+        .iter()
+        .zip(data_urls)
+        .map(|(series, data_url)| {
+            let (data, field) = match data_url {
+                Some(url) => (
+                    json!({ "url": url, "format": { "type": "csv" } }),
+                    column_header(series.data, column_idx),
+                ),
+                None => (
+                    json!({ "values": column_json_values(series.data, column_idx) }),
+                    "value".to_owned(),
+                ),
+            };
+
+            let mut transforms = vec![json!({ "window": [{ "op": "row_number", "as": "call" }] })];
+            let mut field = field;
+            if scale != 1.0 {
+                transforms.push(json!({
+                    "calculate": format!("datum[{}] * {}", serde_json::to_string(&field).unwrap(), scale),
+                    "as": "scaled",
+                }));
+                field = "scaled".to_owned();
+            }
+            if cumulative {
+                transforms.push(json!({
+                    "window": [{ "op": "sum", "field": vega_field(&field), "as": "total" }],
+                    "frame": [null, 0],
+                }));
+                field = "total".to_owned();
+            }
+            if let Some(window) = window {
+                transforms.push(json!({
+                    "calculate": format!("floor((datum.call - 1) / {})", window),
+                    "as": "window",
+                }));
+                transforms.push(json!({
+                    "window": [{ "op": "sum", "field": vega_field(&field), "as": "total" }],
+                    "groupby": ["window"],
+                    "frame": [null, 0],
+                }));
+                field = "total".to_owned();
+            }
+            if percent_of_total {
+                transforms.push(json!({
+                    "joinaggregate": [{ "op": "sum", "field": vega_field(&field), "as": "total" }],
+                }));
+                transforms.push(json!({
+                    "calculate": format!("datum[{}] * 100 / datum.total", serde_json::to_string(&field).unwrap()),
+                    "as": "percent",
+                }));
+                field = "percent".to_owned();
+            }
+            if log10 {
+                // Values without a logarithm, i.e. 0s, are left out
+                transforms.push(json!({
+                    "calculate": format!("datum[{0}] > 0 ? log(datum[{0}]) / LN10 : null", serde_json::to_string(&field).unwrap()),
+                    "as": "log10",
+                }));
+                field = "log10".to_owned();
+            }
+
+            let mut mark = json!({ "type": "line", "point": true });
+            if let Some(dash) = series.style.dash.and_then(stroke_dash) {
+                mark["strokeDash"] = json!(dash);
+            }
+
+            json!({
+                "data": data,
+                "transform": transforms,
+                "mark": mark,
+                "encoding": {
+                    "x": { "field": "call", "type": "quantitative", "title": x_title },
+                    "y": { "field": vega_field(&field), "type": "quantitative", "title": y_title },
+                    "color": {
+                        "datum": series.label,
+                        "scale": { "domain": labels, "range": colors },
+                        "title": null,
+                    },
+                },
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "title": title,
+        "width": 800,
+        "height": 640,
+        "layer": layers,
+    })
+}
+
+/// A field name in a Vega-Lite field definition. `.`, `[`, and `]` would otherwise be taken as
+/// nested field accesses.
+fn vega_field(field: &str) -> String {
+    field
+        .replace('.', "\\.")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+/// A Jupyter notebook loading the data of the series into pandas data frames, followed by a
+/// matplotlib cell per plot drawing it like the gnuplot plot. The data of a series is read from
+/// `data_urls` when given, embedded as CSV otherwise.
+fn notebook(series: &[Series], data_urls: &[Option<String>], plots: &[(&str, usize)]) -> Value {
+    let colors = series_colors(series);
+
+    let mut cells = vec![
+        markdown_cell("# Canister perf plots\n\nGenerated by `generate_plots --export notebook`."),
+        code_cell(
+            "import io\n\nimport matplotlib.pyplot as plt\nimport numpy as np\nimport pandas as pd",
+        ),
+    ];
+
+    // JSON strings are also valid Python string literals
+    let mut data_cell = String::from("data = {}\n");
+    for (series, data_url) in series.iter().zip(data_urls) {
+        let label = serde_json::to_string(series.label).unwrap();
+        let source = match data_url {
+            Some(url) => serde_json::to_string(url).unwrap(),
+            None => {
+                let csv = std::fs::read_to_string(series.data).expect("Unable to read data");
+                format!("io.StringIO({})", serde_json::to_string(&csv).unwrap())
+            }
+        };
+        data_cell.push_str(&format!("data[{}] = pd.read_csv({})\n", label, source));
+    }
+    cells.push(code_cell(data_cell.trim_end()));
+
+    for (plot_name, column_idx) in plots {
+        let plot_cell = matplotlib_plot(series, &colors, plot_name, *column_idx) + "plt.show()";
+        cells.push(markdown_cell(&format!(
+            "## {}",
+            plot_name.replace('_', " ")
+        )));
+        cells.push(code_cell(&plot_cell));
+    }
+
+    json!({
+        "cells": cells,
+        "metadata": {
+            "kernelspec": { "display_name": "Python 3", "language": "python", "name": "python3" },
+            "language_info": { "name": "python" },
+        },
+        "nbformat": 4,
+        "nbformat_minor": 4,
+    })
+}
+
+/// Python script written by `write_matplotlib_script`
+const MATPLOTLIB_SCRIPT_FILE: &str = "plots.py";
+
+/// Write a Python script reproducing the plots with matplotlib as `<plot name>.png`, with the
+/// augmented CSVs of the series as `export_data_<label>.csv` next to it. Returns the path of the
+/// script.
+pub fn write_matplotlib_script(series: &[Series], plots: &[(&str, usize)]) -> PathBuf {
+    let colors = series_colors(series);
+
+    let mut script = String::new();
+    script.push_str("#!/usr/bin/env python3\n");
+    script.push_str(
+        "# Generated by `generate_plots --backend matplotlib-script`. Writes the plots to\n",
+    );
+    script.push_str("# the working directory from the data files next to this script.\n\n");
+    script.push_str("import os\n\nimport matplotlib\n\nmatplotlib.use(\"Agg\")\n\n");
+    script.push_str("import matplotlib.pyplot as plt\nimport numpy as np\nimport pandas as pd\n\n");
+    script.push_str("DATA_DIR = os.path.dirname(os.path.abspath(__file__))\n\n");
+
+    script.push_str("data = {}\n");
+    for series in series {
+        let data_path = write_data_file(series);
+        script.push_str(&format!(
+            "data[{}] = pd.read_csv(os.path.join(DATA_DIR, {}))\n",
+            serde_json::to_string(series.label).unwrap(),
+            serde_json::to_string(&data_path).unwrap()
+        ));
+    }
+
+    for (plot_name, column_idx) in plots {
+        script.push('\n');
+        script.push_str(&matplotlib_plot(series, &colors, plot_name, *column_idx));
+        script.push_str(&format!(
+            "fig.savefig({})\nplt.close(fig)\nprint({})\n",
+            serde_json::to_string(&format!("{}.png", plot_name)).unwrap(),
+            serde_json::to_string(&format!("Wrote {}.png", plot_name)).unwrap()
+        ));
+    }
+
+    let path = PathBuf::from(MATPLOTLIB_SCRIPT_FILE);
+    plot::write_output(&path, script.as_bytes()).expect("Unable to write matplotlib script");
+    log::info!("Wrote {}", path.display());
+    path
+}
+
+/// Python statements drawing a plot with matplotlib like the gnuplot plot, from the data frames
+/// in a `data` dictionary keyed by the labels of the series. The figure is left in `fig`.
+fn matplotlib_plot(
+    series: &[Series],
+    colors: &[String],
+    plot_name: &str,
+    column_idx: usize,
+) -> String {
+    let labels = plot::plot_labels(plot_name, column_idx);
+    let title = serde_json::to_string(labels.title.as_deref().unwrap_or(&labels.ylabel)).unwrap();
+    let x_label = serde_json::to_string(&labels.xlabel).unwrap();
+    let y_label = serde_json::to_string(&labels.ylabel).unwrap();
+
+    let mut code = format!(
+        "fig, ax = plt.subplots(figsize=(12, 9.6))\nax.set_title({})\n",
+        title
+    );
+    let mut y = format!("df.iloc[:, {}]", column_idx - 1);
+    let scale = metrics::scale(column_idx);
+    if scale != 1.0 {
+        y = format!("{} * {}", y, scale);
+    }
+    if metrics::is_cumulative_plot(plot_name) {
+        y = format!("({}).cumsum()", y);
+    }
+    if let Some(window) = metrics::cumulative_window(plot_name) {
+        y = format!(
+            "({y}).groupby(np.arange(len(df)) // {window}).cumsum()",
+            y = y,
+            window = window
+        );
+    }
+    if metrics::is_percent_of_total_plot(plot_name) {
+        y = format!("({y}) * 100 / ({y}).sum()", y = y);
+    }
+    if metrics::log10(column_idx) {
+        // Values without a logarithm, i.e. 0s, are left out
+        y = format!("np.log10(({y}).where(({y}) > 0))", y = y);
+    }
+
+    for (series, color) in series.iter().zip(colors) {
+        let dashes = match series.style.dash.and_then(stroke_dash) {
+            Some(dashes) => format!(", dashes={:?}", dashes),
+            None => String::new(),
+        };
+        code.push_str(&format!(
+            "df = data[{label}]\nax.plot(range(1, len(df) + 1), {y}, marker=\"o\", color={color}, label={label}{dashes})\n",
+            label = serde_json::to_string(series.label).unwrap(),
+            y = y,
+            color = serde_json::to_string(color).unwrap(),
+            dashes = dashes,
+        ));
+    }
+    code.push_str(&format!(
+        "ax.set_xlabel({})\nax.set_ylabel({})\nax.grid(True, which=\"both\", linestyle=\":\")\nax.legend()\n",
+        x_label, y_label
+    ));
+    code
+}
+
+fn markdown_cell(source: &str) -> Value {
+    json!({ "cell_type": "markdown", "metadata": {}, "source": cell_source(source) })
+}
+
+fn code_cell(source: &str) -> Value {
+    json!({
+        "cell_type": "code",
+        "execution_count": null,
+        "metadata": {},
+        "outputs": [],
+        "source": cell_source(source),
+    })
+}
+
+/// Notebook cell source: a list of lines, each but the last with its newline
+fn cell_source(source: &str) -> Vec<&str> {
+    source.split_inclusive('\n').collect()
+}
+
+/// Colors of the series, as picked by `plot::line_styles` or overridden in their styles
+fn series_colors(series: &[Series]) -> Vec<String> {
+    let labels: Vec<&str> = series.iter().map(|series| series.label).collect();
+    series
+        .iter()
+        .zip(plot::line_styles(&labels))
+        .map(|(series, line_style)| {
+            series.style.color.clone().unwrap_or_else(|| {
+                plot::theme().line_style_colors()[line_style as usize - 1].to_owned()
+            })
+        })
+        .collect()
+}
+
+/// Stroke dash array approximating a gnuplot dash type. `None` for solid lines.
+fn stroke_dash(dash: u32) -> Option<Vec<u32>> {
+    match dash {
+        2 => Some(vec![8, 4]),
+        3 => Some(vec![2, 4]),
+        4 => Some(vec![8, 4, 2, 4]),
+        5 => Some(vec![8, 4, 2, 4, 2, 4]),
+        _ => None,
+    }
+}
+
+/// Header of a (1-based) column of a CSV
+fn column_header(csv_path: &Path, col_idx: usize) -> String {
+    let mut reader = csv::Reader::from_path(csv_path).unwrap();
+    reader.headers().unwrap()[col_idx - 1].to_owned()
+}
+
+/// Values of a (1-based) column of a CSV as `{"value": ...}` objects. Values that aren't numbers
+/// are exported as `null`, which Vega-Lite leaves out of the line.
+fn column_json_values(csv_path: &Path, col_idx: usize) -> Vec<Value> {
+    let mut reader = csv::Reader::from_path(csv_path).unwrap();
+    let mut values = vec![];
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).unwrap() {
+        let field = record.get(col_idx - 1).unwrap_or("");
+        let value = match parse_u64(field.as_bytes()) {
+            Some(value) => json!(value),
+            None => field
+                .parse::<f64>()
+                .map_or(Value::Null, |value| json!(value)),
+        };
+        values.push(json!({ "value": value }));
+    }
+    values
+}
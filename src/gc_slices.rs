@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::table::print_table;
+use crate::{DIRTIED_HOST_PAGES_COL_IDX, INSTRUCTIONS_COL_IDX, WASM_PAGES_COL_IDX};
+
+/// A call is considered a dirtied pages jump when it dirties this many times the median number
+/// of pages...
+const DIRTIED_JUMP_FACTOR: u64 = 4;
+
+/// ... and at least this many pages
+const MIN_DIRTIED_JUMP: u64 = 8;
+
+/// ... while executing at most this many times the median number of instructions
+const MODEST_INSTRUCTIONS_FACTOR: u64 = 2;
+
+/// A call that looks dominated by GC work
+pub struct GcSlice {
+    /// 0-based index of the call
+    pub call_idx: usize,
+    pub reason: &'static str,
+}
+
+/// Heuristically find calls dominated by GC work: calls that dirty many more pages than usual
+/// without executing many more instructions than usual (a copying or compacting pass), and
+/// calls after which the number of Wasm pages in use drops (memory returned after collection).
+pub fn detect(csv_path: &Path) -> Vec<GcSlice> {
+    let instructions = augment::try_column_values(csv_path, INSTRUCTIONS_COL_IDX)
+        .unwrap_or_else(|err| errors::exit(errors::Category::Input, Some(csv_path), None, &err));
+    let dirtied_host_pages = augment::try_column_values(csv_path, DIRTIED_HOST_PAGES_COL_IDX)
+        .unwrap_or_else(|err| errors::exit(errors::Category::Input, Some(csv_path), None, &err));
+    let wasm_pages = augment::try_column_values(csv_path, WASM_PAGES_COL_IDX)
+        .unwrap_or_else(|err| errors::exit(errors::Category::Input, Some(csv_path), None, &err));
+
+    let median_instructions = median(&instructions);
+    let median_dirtied_host_pages = median(&dirtied_host_pages);
+
+    let dirtied_jump_threshold =
+        (median_dirtied_host_pages * DIRTIED_JUMP_FACTOR).max(MIN_DIRTIED_JUMP);
+    let modest_instructions_threshold = median_instructions * MODEST_INSTRUCTIONS_FACTOR;
+
+    let mut slices = vec![];
+    for call_idx in 0..instructions.len() {
+        if dirtied_host_pages[call_idx] >= dirtied_jump_threshold
+            && instructions[call_idx] <= modest_instructions_threshold
+        {
+            slices.push(GcSlice {
+                call_idx,
+                reason: "dirtied pages jump",
+            });
+        } else if call_idx > 0 && wasm_pages[call_idx] < wasm_pages[call_idx - 1] {
+            slices.push(GcSlice {
+                call_idx,
+                reason: "Wasm pages in use dropped",
+            });
+        }
+    }
+
+    slices
+}
+
+pub fn print_summary(inputs: &[Input], slices: &[Vec<GcSlice>]) {
+    let mut rows: Vec<Vec<String>> = vec![];
+    for (input, slices) in inputs.iter().zip(slices) {
+        for slice in slices {
+            rows.push(vec![
+                input.label.clone(),
+                (slice.call_idx + 1).to_string(),
+                slice.reason.to_owned(),
+            ]);
+        }
+    }
+
+    if rows.is_empty() {
+        println!("No GC slices detected");
+        return;
+    }
+
+    println!("Detected GC slices:");
+    print_table(&["input", "call", "reason"], &rows);
+}
+
+fn median(values: &[u64]) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted[sorted.len() / 2]
+}
@@ -0,0 +1,47 @@
+/// An HTML page without external references, with `nav` (e.g. links to other pages) before the
+/// title
+pub fn page(title: &str, nav: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>{style}</style>\n</head>\n<body>\n{nav}<h1>{title}</h1>\n{body}</body>\n</html>\n",
+        title = escape(title),
+        style = STYLE,
+        nav = nav,
+        body = body
+    )
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; } \
+                     table { border-collapse: collapse; } \
+                     th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; } \
+                     img { max-width: 100%; }";
+
+/// A table row, with `cell` (`th` or `td`) elements
+pub fn table_row<'a>(cell: &str, cells: impl IntoIterator<Item = &'a str>) -> String {
+    let cells: Vec<String> = cells
+        .into_iter()
+        .map(|content| format!("<{cell}>{}</{cell}>", escape(content), cell = cell))
+        .collect();
+    format!("<tr>{}</tr>\n", cells.concat())
+}
+
+/// Escape text for HTML
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape a path component for a URL
+pub fn url_escape(component: &str) -> String {
+    let mut escaped = String::new();
+    for byte in component.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
@@ -0,0 +1,2367 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tempfile::NamedTempFile;
+
+use crate::augment;
+use crate::config::{ColumnMapping, InputConfig};
+use crate::data::{Column, Table};
+use crate::errors;
+use crate::metrics;
+use crate::plot::{self, SeriesStyle};
+use crate::rolling;
+use crate::{
+    ACCESSED_HOST_PAGES_COL_IDX, DIRTIED_HOST_PAGES_COL_IDX, FILES, INSTRUCTIONS_COL_IDX, TOTALS,
+};
+
+/// An input CSV and how to draw its series
+pub struct Input {
+    pub path: PathBuf,
+    /// Path of the input as it was given, when `path` is a preprocessed copy of it
+    pub original: PathBuf,
+    pub label: String,
+    /// How the input was produced, e.g. the flags of drun, shown in reports
+    pub description: Option<String>,
+    pub style: SeriesStyle,
+    pub alignment: Alignment,
+    /// Other runs of the same build, aggregated with the run at `path` by `aggregate_repetitions`
+    pub repetitions: Vec<PathBuf>,
+    /// How the runs are aggregated when there are repetitions
+    pub aggregate: Aggregate,
+    /// Number of outliers clamped or calls dropped by `trim_outliers`
+    pub trimmed: usize,
+    /// Columns with missing values filled in by `fill_missing`
+    pub filled: Vec<FilledColumn>,
+    /// 1-based, inclusive ranges of calls of the install/upgrade phase, in addition to the ones
+    /// `separate_setup` detects by their method
+    pub setup_ranges: Vec<(usize, usize)>,
+    /// 0-based indices of the calls of the install/upgrade phase, unless `SetupPhase::Keep`. They
+    /// are left out of the CSV at `path` unless `SetupPhase::Mark`.
+    pub setup_calls: Vec<usize>,
+    /// The calls of the install/upgrade phase, with `SetupPhase::Separate`
+    pub setup_csv: Option<NamedTempFile>,
+    /// The CSV at `path` when it's a copy of the input with the columns in the drun layout, see
+    /// `map_columns`, without skipped rows, see `skip_rows`, with the repetitions aggregated, see
+    /// `aggregate_repetitions`, with a row per round, see `collapse_rounds`, with the calls of the
+    /// other inputs, see `align_by_key`, truncated or padded, see `match_row_counts`, with missing
+    /// values filled in, see `fill_missing`, or with outliers trimmed, see `trim_outliers`.
+    /// Deleted when the input is dropped.
+    _mapped: Option<NamedTempFile>,
+    /// Copies of the repetitions without the rows that aren't records, see `clean_rows`
+    _cleaned_repetitions: Vec<NamedTempFile>,
+}
+
+/// How the values of a call in repeated runs are combined into one
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    #[default]
+    Mean,
+    /// Less affected than the mean by the occasional run on a busy machine
+    Median,
+}
+
+impl Aggregate {
+    fn name(self) -> &'static str {
+        match self {
+            Aggregate::Mean => "mean",
+            Aggregate::Median => "median",
+        }
+    }
+
+    /// Combine the values of a call. `values` is non-empty.
+    fn apply(self, values: &mut [f64]) -> f64 {
+        match self {
+            Aggregate::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregate::Median => {
+                values.sort_by(f64::total_cmp);
+                let mid = values.len() / 2;
+                if values.len().is_multiple_of(2) {
+                    (values[mid - 1] + values[mid]) / 2.0
+                } else {
+                    values[mid]
+                }
+            }
+        }
+    }
+}
+
+/// How an input lines up with the others, for runs with extra calls (e.g. warm-up calls) at the
+/// start
+#[derive(Clone, Copy, Default)]
+pub struct Alignment {
+    /// Number of leading records left out by `skip_rows`, as if they weren't in the input
+    pub skip_rows: usize,
+    /// Number of leading calls that are kept, but plotted before the first call of the other
+    /// inputs, i.e. call `offset + 1` is plotted at 1
+    pub offset: usize,
+}
+
+/// How the inputs are prepared for plotting, by the stages of `prepare_inputs` in `main`
+#[derive(Default)]
+pub struct PipelineOptions {
+    pub bad_rows: BadRows,
+    pub strictness: Strictness,
+    /// Canister that `filter_canister` keeps the calls of (a textual principal), or `None` for
+    /// all calls
+    pub canister: Option<String>,
+    /// Whether `sum_canisters` sums the calls of the canisters
+    pub sum_canisters: bool,
+    /// How `collapse_rounds` groups calls into rounds, or `None` for a row per call
+    pub rounds: Option<Rounds>,
+    /// Header of the column that `align_by_key` joins the inputs on, or `None` to match their
+    /// calls by position
+    pub align_by: Option<String>,
+    pub setup_phase: SetupPhase,
+    pub length_mismatch: LengthMismatch,
+    /// Outlier trimming of `trim_outliers`, or `None` to keep the outliers
+    pub trim: Option<Trim>,
+    /// Format whose columns `map_columns` maps the inputs as
+    pub input_format: InputFormat,
+    /// Anomalies tolerated with `Strictness::Lenient`, since the last `summarize_anomalies`
+    pub anomalies: RefCell<Vec<String>>,
+}
+
+impl PipelineOptions {
+    /// What the rows of the inputs are, e.g. for axis labels: "round" with rounds enabled,
+    /// "call" otherwise
+    pub fn row_name(&self) -> &'static str {
+        if self.rounds.is_some() {
+            "round"
+        } else {
+            "call"
+        }
+    }
+
+    /// Report an anomaly of an input, of the one at `path` when given, see `Strictness`.
+    fn anomaly(&self, path: Option<&Path>, message: String) {
+        match self.strictness {
+            Strictness::Warn => log::warn!("{}", message),
+            Strictness::Strict => errors::exit(
+                errors::Category::Input,
+                path,
+                None,
+                &format!("{} (with --strict)", message),
+            ),
+            Strictness::Lenient => self.anomalies.borrow_mut().push(message),
+        }
+    }
+
+    /// Print the anomalies tolerated with `Strictness::Lenient` since the last call.
+    pub fn summarize_anomalies(&self) {
+        let anomalies = self.anomalies.take();
+        if anomalies.is_empty() {
+            return;
+        }
+        log::warn!("Tolerated {} anomalies of the inputs:", anomalies.len());
+        for anomaly in anomalies {
+            log::warn!("  {}", anomaly);
+        }
+    }
+}
+
+/// Exit with an error when an input (or one of its repetitions) has two columns with the same
+/// header, ignoring case as lookups by header do, or a column with the header of one of the
+/// cumulative columns that are appended to it.
+pub fn check_headers(inputs: &[Input]) {
+    for path in inputs
+        .iter()
+        .flat_map(|input| std::iter::once(&input.path).chain(&input.repetitions))
+    {
+        let headers: Vec<String> = match csv::Reader::from_path(path) {
+            Ok(mut reader) => match reader.headers() {
+                Ok(headers) => headers.iter().map(|h| h.trim().to_owned()).collect(),
+                Err(_) => continue,
+            },
+            Err(_) => continue,
+        };
+
+        for (col_idx, header) in headers.iter().enumerate() {
+            if let Some(other_idx) = headers[..col_idx]
+                .iter()
+                .position(|other| other.eq_ignore_ascii_case(header))
+            {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(path),
+                    None,
+                    &format!(
+                        "{}: columns {} and {} both have the header '{}', rename one of them",
+                        path.display(),
+                        other_idx + 1,
+                        col_idx + 1,
+                        header
+                    ),
+                );
+            }
+            if TOTALS
+                .iter()
+                .any(|(name, _, _)| name.eq_ignore_ascii_case(header))
+            {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(path),
+                    None,
+                    &format!(
+                        "{}: column {} has the header '{}' of a cumulative column that is \
+                         appended to the inputs, rename it (or give the input without its \
+                         cumulative columns)",
+                        path.display(),
+                        col_idx + 1,
+                        header
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// What `clean_rows` does with rows that aren't records of calls
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BadRows {
+    /// Exit with an error naming the first one
+    #[default]
+    Abort,
+    /// Leave them out, with a warning
+    Skip,
+}
+
+/// How anomalies of the inputs that the plots can be generated despite are handled, e.g. rows left
+/// out or inputs with different numbers of calls
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Warn about each one
+    #[default]
+    Warn,
+    /// Exit with an error on the first one
+    Strict,
+    /// Summarize them when done, see `PipelineOptions::summarize_anomalies`
+    Lenient,
+}
+
+/// Check the inputs and their repetitions for rows that aren't records of calls: comments
+/// (starting with `#`), repeated headers (e.g. of concatenated runs), rows with a different number
+/// of fields, and rows with non-numeric values in numeric columns. Depending on `options.bad_rows`,
+/// exit with an error or replace the CSVs with such rows with temporary CSVs without them.
+/// Missing values in numeric columns are left to `fill_missing` when a `[[metric]]` fills them
+/// in, and make rows bad otherwise. `mapping` has the headers of the numeric columns of inputs
+/// that aren't drun generated CSVs.
+pub fn clean_rows(
+    inputs: Vec<Input>,
+    mapping: &ColumnMapping,
+    options: &PipelineOptions,
+) -> Vec<Input> {
+    inputs
+        .into_iter()
+        .map(|mut input| {
+            let mut cleaned_repetitions = vec![];
+            for repetition in &mut input.repetitions {
+                if let Some(cleaned) = clean_csv(repetition, mapping, options) {
+                    *repetition = cleaned.path().to_owned();
+                    cleaned_repetitions.push(cleaned);
+                }
+            }
+            input._cleaned_repetitions = cleaned_repetitions;
+
+            match clean_csv(&input.path, mapping, options) {
+                Some(cleaned) => {
+                    plot::set_dry_run_placeholder(
+                        cleaned.path(),
+                        &format!("<{} without bad rows>", input.path.display()),
+                    );
+                    Input {
+                        path: cleaned.path().to_owned(),
+                        _mapped: Some(cleaned),
+                        ..input
+                    }
+                }
+                None => input,
+            }
+        })
+        .collect()
+}
+
+/// A copy of a CSV without the rows that aren't records (see `clean_rows`), or `None` when all of
+/// its rows are records. Exits with an error on the first such row with `BadRows::Abort`. The CSV
+/// is read once: the copy is started at the first bad row, with the bytes before it copied as
+/// they are.
+fn clean_csv(
+    path: &Path,
+    mapping: &ColumnMapping,
+    options: &PipelineOptions,
+) -> Option<NamedTempFile> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .ok()?;
+    let headers = reader.byte_headers().ok()?.clone();
+
+    // Lines and reasons of the bad rows
+    let mut bad: Vec<(u64, &str)> = vec![];
+    let mut numeric = numeric_columns(&headers, mapping);
+    let missing_is_bad = !metrics::fills_missing();
+    let mut writer: Option<csv::Writer<NamedTempFile>> = None;
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).unwrap_or(false) {
+        let reason = match bad_row_reason(&record, &headers, &mut numeric, missing_is_bad) {
+            Some(reason) => reason,
+            None => {
+                if let Some(writer) = &mut writer {
+                    writer.write_byte_record(&record).unwrap();
+                }
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        if options.bad_rows == BadRows::Abort {
+            let fields: Vec<String> = record
+                .iter()
+                .map(|field| String::from_utf8_lossy(field).into_owned())
+                .collect();
+            errors::exit(
+                errors::Category::Input,
+                Some(path),
+                Some(line as usize),
+                &format!(
+                    "{}: line {} is {}, not a record of a call: '{}'. Use --bad-rows skip to \
+                     leave out such rows{}.",
+                    path.display(),
+                    line,
+                    reason,
+                    fields.join(","),
+                    if reason == MISSING_VALUES_REASON {
+                        ", or set `missing` in the [[metric]] of the column"
+                    } else {
+                        ""
+                    }
+                ),
+            );
+        }
+        if writer.is_none() {
+            let start = record.position().map_or(0, |position| position.byte());
+            let mut copy = NamedTempFile::new().unwrap();
+            let mut input = std::fs::File::open(path).unwrap();
+            std::io::copy(&mut std::io::Read::take(&mut input, start), &mut copy).unwrap();
+            writer = Some(csv::Writer::from_writer(copy));
+        }
+        bad.push((line, reason));
+    }
+    let writer = writer?;
+
+    let lines: Vec<String> = bad
+        .iter()
+        .take(5)
+        .map(|(line, reason)| format!("line {} ({})", line, reason))
+        .collect();
+    options.anomaly(
+        Some(path),
+        format!(
+            "{}: leaving out {} rows that aren't records: {}{}",
+            path.display(),
+            bad.len(),
+            lines.join(", "),
+            if bad.len() > lines.len() { ", ..." } else { "" }
+        ),
+    );
+
+    let cleaned = writer.into_inner().unwrap();
+    log::debug!(
+        "{}: without bad rows in {}",
+        path.display(),
+        cleaned.path().display()
+    );
+    Some(cleaned)
+}
+
+/// Whether each column is numeric: `Some` for the columns of drun generated CSVs, the mapped ones
+/// of `mapping`, and the canister columns, `None` for other columns, which `bad_row_reason`
+/// decides on at their first value that isn't missing
+fn numeric_columns(headers: &csv::ByteRecord, mapping: &ColumnMapping) -> Vec<Option<bool>> {
+    let text_headers: Vec<&str> = DRUN_HEADERS[..2]
+        .iter()
+        .copied()
+        .chain(CANISTER_HEADERS.iter().copied())
+        .chain(mapping.message.as_deref())
+        .chain(mapping.method.as_deref())
+        .collect();
+    let numeric_headers: Vec<&str> = DRUN_HEADERS[2..]
+        .iter()
+        .copied()
+        .chain(std::iter::once(ROUNDS_HEADER))
+        .chain(mapping.instructions.as_deref())
+        .chain(mapping.accessed_pages.as_deref())
+        .chain(mapping.dirtied_pages.as_deref())
+        .chain(mapping.heap_size.as_deref())
+        .chain(mapping.rounds.as_deref())
+        .collect();
+    headers
+        .iter()
+        .map(|header| {
+            let header = String::from_utf8_lossy(header);
+            let header = header.trim();
+            let is = |headers: &[&str]| {
+                headers
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(header))
+            };
+            if is(&numeric_headers) {
+                Some(true)
+            } else if is(&text_headers) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Headers (ignoring case) of the columns with the canisters of the calls, in CSVs of workloads
+/// with several canisters
+const CANISTER_HEADERS: [&str; 3] = ["canister", "canister id", "canister_id"];
+
+/// Replace the inputs and repetitions with a canister column (see `CANISTER_HEADERS`) with
+/// temporary CSVs with only the calls of `options.canister`.
+pub fn filter_canister(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    let canister = match &options.canister {
+        Some(canister) => canister,
+        None => return inputs,
+    };
+
+    let mut filtered_any = false;
+    let inputs = inputs
+        .into_iter()
+        .map(|mut input| {
+            for repetition in &mut input.repetitions {
+                if let Some(filtered) = canister_calls(repetition, canister, options) {
+                    *repetition = filtered.path().to_owned();
+                    input._cleaned_repetitions.push(filtered);
+                }
+            }
+
+            match canister_calls(&input.path, canister, options) {
+                Some(filtered) => {
+                    filtered_any = true;
+                    plot::set_dry_run_placeholder(
+                        filtered.path(),
+                        &format!("<calls of {} in {}>", canister, input.path.display()),
+                    );
+                    Input {
+                        path: filtered.path().to_owned(),
+                        _mapped: Some(filtered),
+                        ..input
+                    }
+                }
+                None => input,
+            }
+        })
+        .collect();
+
+    if !filtered_any {
+        log::warn!(
+            "None of the inputs has a canister column ({}), plotting all calls",
+            CANISTER_HEADERS.join(", ")
+        );
+    }
+    inputs
+}
+
+/// A copy of a CSV with only the calls of `canister`, or `None` when it doesn't have a canister
+/// column
+fn canister_calls(path: &Path, canister: &str, options: &PipelineOptions) -> Option<NamedTempFile> {
+    let mut reader = match csv::Reader::from_path(path) {
+        Ok(reader) => reader,
+        Err(err) => {
+            errors::exit(
+                errors::Category::Input,
+                Some(path),
+                None,
+                &format!("Unable to read {}: {}", path.display(), err),
+            );
+        }
+    };
+    let headers = reader.byte_headers().unwrap().clone();
+    let col_idx = headers.iter().position(|header| {
+        let header = String::from_utf8_lossy(header);
+        CANISTER_HEADERS
+            .iter()
+            .any(|canister_header| header.trim().eq_ignore_ascii_case(canister_header))
+    })?;
+
+    let mut writer = csv::Writer::from_writer(NamedTempFile::new().unwrap());
+    writer.write_byte_record(&headers).unwrap();
+    let mut num_rows = 0;
+    let mut num_kept = 0;
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).unwrap() {
+        num_rows += 1;
+        let field = String::from_utf8_lossy(record.get(col_idx).unwrap_or_default());
+        if field.trim().eq_ignore_ascii_case(canister) {
+            writer.write_byte_record(&record).unwrap();
+            num_kept += 1;
+        }
+    }
+    if num_kept == 0 {
+        options.anomaly(
+            Some(path),
+            format!(
+                "{}: none of its {} calls are of canister {}",
+                path.display(),
+                num_rows,
+                canister
+            ),
+        );
+    }
+    log::debug!(
+        "{}: kept {} of {} calls of canister {}",
+        path.display(),
+        num_kept,
+        num_rows,
+        canister
+    );
+
+    Some(writer.into_inner().unwrap())
+}
+
+/// With `options.sum_canisters`, replace the inputs and repetitions with a canister column (see
+/// `CANISTER_HEADERS`) with temporary CSVs with a row per message, in the order of their first
+/// calls, for a view of the whole subnet. Numeric columns get the sums of the calls of the
+/// message, except the heap size, which gets the sum of the last heap sizes of every canister so
+/// far. The method and text columns get the values of the first call. The canister column is
+/// left out.
+pub fn sum_canisters(
+    inputs: Vec<Input>,
+    mapping: &ColumnMapping,
+    options: &PipelineOptions,
+) -> Vec<Input> {
+    if !options.sum_canisters {
+        return inputs;
+    }
+    let mapping = mapping.or(options.input_format.columns());
+    let message_header = mapping.message.as_deref().unwrap_or(DRUN_HEADERS[0]);
+    let heap_header = mapping.heap_size.as_deref().unwrap_or(DRUN_HEADERS[5]);
+
+    inputs
+        .into_iter()
+        .map(|mut input| {
+            for repetition in &mut input.repetitions {
+                if let Some(summed) = canister_sums(repetition, message_header, heap_header) {
+                    *repetition = summed.path().to_owned();
+                    input._cleaned_repetitions.push(summed);
+                }
+            }
+
+            match canister_sums(&input.path, message_header, heap_header) {
+                Some(summed) => {
+                    plot::set_dry_run_placeholder(
+                        summed.path(),
+                        &format!("<{} summed over canisters>", input.path.display()),
+                    );
+                    Input {
+                        path: summed.path().to_owned(),
+                        _mapped: Some(summed),
+                        ..input
+                    }
+                }
+                None => input,
+            }
+        })
+        .collect()
+}
+
+/// A copy of a CSV with the calls of each message summed over the canisters (see
+/// `sum_canisters`), or `None` when it doesn't have a canister column
+fn canister_sums(path: &Path, message_header: &str, heap_header: &str) -> Option<NamedTempFile> {
+    let mut table = read_table(path);
+    let find = |table: &Table, header: &str| {
+        table
+            .headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(header))
+    };
+
+    let canister_col_idx = CANISTER_HEADERS
+        .iter()
+        .find_map(|header| find(&table, header))?;
+    let canisters = table.columns.remove(canister_col_idx);
+    table.headers.remove(canister_col_idx);
+
+    let message_col_idx = find(&table, message_header).unwrap_or_else(|| {
+        errors::exit(
+            errors::Category::Input,
+            Some(path),
+            None,
+            &format!(
+                "{} has no column '{}' to sum the calls of the canisters by",
+                path.display(),
+                message_header
+            ),
+        )
+    });
+    let heap_col_idx = find(&table, heap_header);
+
+    // Rows of each message, in the order of their first rows
+    let mut messages: Vec<Vec<usize>> = vec![];
+    let mut message_idxs: HashMap<String, usize> = HashMap::new();
+    for row_idx in 0..table.num_rows {
+        let message = field(&table.columns[message_col_idx], row_idx);
+        let message_idx = *message_idxs.entry(message).or_insert_with(|| {
+            messages.push(vec![]);
+            messages.len() - 1
+        });
+        messages[message_idx].push(row_idx);
+    }
+    let first_rows: Vec<usize> = messages.iter().map(|rows| rows[0]).collect();
+
+    let columns = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(col_idx, column)| {
+            if col_idx == message_col_idx {
+                return pick_rows(column, &first_rows);
+            }
+            if Some(col_idx) == heap_col_idx && !matches!(column, Column::Text(_)) {
+                // Heap sizes of the canisters after the last call of each message
+                let mut last_message_rows: Vec<(usize, usize)> = messages
+                    .iter()
+                    .enumerate()
+                    .map(|(message_idx, rows)| (*rows.last().unwrap(), message_idx))
+                    .collect();
+                last_message_rows.sort_unstable();
+                let mut heap_sizes: HashMap<String, f64> = HashMap::new();
+                let mut sums = vec![0.0; messages.len()];
+                let mut last_message_rows = last_message_rows.into_iter().peekable();
+                for row_idx in 0..table.num_rows {
+                    let heap_size = column_value(column, row_idx).unwrap_or_default();
+                    heap_sizes.insert(field(&canisters, row_idx), heap_size);
+                    if let Some((_, message_idx)) =
+                        last_message_rows.next_if(|(last_row_idx, _)| *last_row_idx == row_idx)
+                    {
+                        sums[message_idx] = heap_sizes.values().sum();
+                    }
+                }
+                return match column {
+                    Column::UInt(_) => Column::UInt(sums.iter().map(|sum| *sum as u64).collect()),
+                    _ => Column::Float(sums),
+                };
+            }
+            match column {
+                Column::UInt(values) => Column::UInt(
+                    messages
+                        .iter()
+                        .map(|rows| rows.iter().map(|row_idx| values[*row_idx]).sum())
+                        .collect(),
+                ),
+                Column::Float(values) => Column::Float(
+                    messages
+                        .iter()
+                        .map(|rows| rows.iter().map(|row_idx| values[*row_idx]).sum())
+                        .collect(),
+                ),
+                Column::Text(_) => pick_rows(column, &first_rows),
+            }
+        })
+        .collect();
+    let summed = Table {
+        headers: table.headers,
+        columns,
+        num_rows: messages.len(),
+    };
+
+    let summed_csv = augment::write_temp_csv(&summed);
+    log::debug!(
+        "{}: {} calls of {} messages in {}",
+        path.display(),
+        table.num_rows,
+        messages.len(),
+        summed_csv.path().display()
+    );
+    Some(summed_csv)
+}
+
+/// Reason of `bad_row_reason` for rows with missing values
+const MISSING_VALUES_REASON: &str = "a row with missing values";
+
+/// Why a record isn't a record of a call, if it isn't. `numeric` has whether the columns are
+/// numeric, see `numeric_columns`. Columns it doesn't know yet are set at their first value that
+/// isn't missing, as numeric when it's a number. With `missing_is_bad`, rows with missing values
+/// in numeric columns are bad too.
+fn bad_row_reason(
+    record: &csv::ByteRecord,
+    headers: &csv::ByteRecord,
+    numeric: &mut [Option<bool>],
+    missing_is_bad: bool,
+) -> Option<&'static str> {
+    if record
+        .get(0)
+        .is_some_and(|field| field.trim_ascii_start().starts_with(b"#"))
+    {
+        return Some("a comment");
+    }
+    if record.len() == headers.len()
+        && record
+            .iter()
+            .zip(headers.iter())
+            .all(|(field, header)| field.trim_ascii() == header.trim_ascii())
+    {
+        return Some("a repeated header");
+    }
+    if record.len() != headers.len() {
+        return Some("a row with a different number of fields");
+    }
+    let is_number = |field: &[u8]| {
+        std::str::from_utf8(field).is_ok_and(|field| field.trim().parse::<f64>().is_ok())
+    };
+    let mut non_numeric = false;
+    let mut missing = false;
+    for (field, numeric) in record.iter().zip(numeric.iter_mut()) {
+        if is_missing(field) {
+            missing |= *numeric == Some(true);
+            continue;
+        }
+        match numeric {
+            Some(true) => non_numeric |= !is_number(field),
+            Some(false) => {}
+            None => *numeric = Some(is_number(field)),
+        }
+    }
+    if non_numeric {
+        return Some("a row with non-numeric values");
+    }
+    if missing && missing_is_bad {
+        return Some(MISSING_VALUES_REASON);
+    }
+    None
+}
+
+/// Whether a field is a missing value: empty or `NA`
+fn is_missing(field: &[u8]) -> bool {
+    let field = field.trim_ascii();
+    field.is_empty() || field.eq_ignore_ascii_case(b"NA")
+}
+
+/// How `fill_missing` fills in the missing values of a column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Missing {
+    Zero,
+    /// The previous value of the column, or the next one for missing values at the start
+    Previous,
+    /// Linear interpolation between the previous and the next values, or the nearest value at the
+    /// start and the end
+    Interpolate,
+    /// Leave out the calls with missing values
+    Drop,
+}
+
+impl Missing {
+    /// How the missing values were handled, for reports
+    pub fn description(self) -> &'static str {
+        match self {
+            Missing::Zero => "replaced with 0",
+            Missing::Previous => "replaced with the previous value",
+            Missing::Interpolate => "interpolated",
+            Missing::Drop => "calls left out",
+        }
+    }
+}
+
+/// A column with missing values filled in by `fill_missing`
+pub struct FilledColumn {
+    /// 1-based index of the column in the augmented CSVs
+    pub col_idx: usize,
+    pub header: String,
+    pub missing: Missing,
+    /// Number of missing values
+    pub count: usize,
+}
+
+/// Replace each input with missing values (empty or `NA`) in numeric columns with a temporary CSV
+/// with the values filled in as configured for the metric of the column (see
+/// `config::MetricConfig::missing`). Missing values in columns without a configuration are handled
+/// like rows that aren't records, see `PipelineOptions::bad_rows`. `Input::filled` is set to the columns with
+/// filled in values. When no metric fills in missing values, `clean_rows` has already handled
+/// them and the inputs are returned as they are.
+pub fn fill_missing(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    if !metrics::fills_missing() {
+        return inputs;
+    }
+    inputs
+        .into_iter()
+        .map(|input| {
+            let mut table = read_table(&input.path);
+            let mut keep = vec![true; table.num_rows];
+            let mut filled = vec![];
+
+            for (col_idx, column) in table.columns.iter_mut().enumerate() {
+                let values = match column {
+                    Column::Text(values) => values,
+                    _ => continue,
+                };
+                let parsed: Vec<Option<f64>> = values
+                    .iter()
+                    .map(|value| value.trim().parse().ok())
+                    .collect();
+                let missing: Vec<usize> = (0..values.len())
+                    .filter(|row_idx| is_missing(values[*row_idx].as_bytes()))
+                    .collect();
+                // Columns of text, rather than numbers with missing values
+                if missing.is_empty()
+                    || missing.len() == values.len()
+                    || parsed
+                        .iter()
+                        .zip(values.iter())
+                        .any(|(parsed, value)| parsed.is_none() && !is_missing(value.as_bytes()))
+                {
+                    continue;
+                }
+
+                // Metrics are of the columns of the augmented CSVs
+                let metric_col_idx = augment::augmented_col_idx(col_idx + 1);
+                let policy = match metrics::metric(metric_col_idx).and_then(|metric| metric.missing)
+                {
+                    Some(policy) => policy,
+                    None if options.bad_rows == BadRows::Skip => {
+                        options.anomaly(
+                            Some(&input.path),
+                            format!(
+                                "{}: leaving out {} calls with missing values of column {} ('{}')",
+                                input.path.display(),
+                                missing.len(),
+                                metric_col_idx,
+                                table.headers[col_idx]
+                            ),
+                        );
+                        Missing::Drop
+                    }
+                    None => {
+                        errors::exit(
+                            errors::Category::Input,
+                            Some(&input.path),
+                            Some(missing[0] + 2),
+                            &format!(
+                                "{}: column {} ('{}') has {} missing values, e.g. in call {}. \
+                                 Set `missing` in its [[metric]] or use --bad-rows skip to leave \
+                                 out their calls.",
+                                input.path.display(),
+                                metric_col_idx,
+                                table.headers[col_idx],
+                                missing.len(),
+                                missing[0] + 1
+                            ),
+                        );
+                    }
+                };
+                filled.push(FilledColumn {
+                    col_idx: metric_col_idx,
+                    header: table.headers[col_idx].clone(),
+                    missing: policy,
+                    count: missing.len(),
+                });
+
+                let present: Vec<usize> = (0..values.len())
+                    .filter(|row_idx| parsed[*row_idx].is_some())
+                    .collect();
+                let value_at = |row_idx: usize| parsed[row_idx].unwrap();
+                let filled_values: Vec<f64> = (0..values.len())
+                    .map(|row_idx| {
+                        if let Some(value) = parsed[row_idx] {
+                            return value;
+                        }
+                        // Index in `present` of the first present value after the row
+                        let next = present.partition_point(|idx| *idx < row_idx);
+                        let before = next.checked_sub(1).map(|idx| present[idx]);
+                        let after = present.get(next).copied();
+                        match (policy, before, after) {
+                            (Missing::Zero, _, _) => 0.0,
+                            (Missing::Drop, _, _) => {
+                                keep[row_idx] = false;
+                                0.0
+                            }
+                            (Missing::Previous, Some(before), _) => value_at(before),
+                            (Missing::Interpolate, Some(before), Some(after)) => {
+                                let fraction = (row_idx - before) as f64 / (after - before) as f64;
+                                value_at(before) + (value_at(after) - value_at(before)) * fraction
+                            }
+                            (_, Some(before), None) => value_at(before),
+                            (_, None, after) => value_at(after.unwrap()),
+                        }
+                    })
+                    .collect();
+
+                // Interpolated values of integer columns are rounded, so that the cumulative
+                // columns can still be added
+                *column = if parsed
+                    .iter()
+                    .flatten()
+                    .all(|value| *value >= 0.0 && value.fract() == 0.0)
+                {
+                    Column::UInt(
+                        filled_values
+                            .iter()
+                            .map(|value| value.round() as u64)
+                            .collect(),
+                    )
+                } else {
+                    Column::Float(filled_values)
+                };
+            }
+
+            if filled.is_empty() {
+                return input;
+            }
+            table.retain_rows(&keep);
+
+            let filled_csv = augment::write_temp_csv(&table);
+            log::debug!(
+                "{}: filled in missing values in {}",
+                input.path.display(),
+                filled_csv.path().display()
+            );
+            plot::set_dry_run_placeholder(
+                filled_csv.path(),
+                &format!("<{} with missing values filled in>", input.path.display()),
+            );
+
+            Input {
+                path: filled_csv.path().to_owned(),
+                filled,
+                _mapped: Some(filled_csv),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// Replace each input with repetitions with a temporary CSV of the mean or median (see
+/// `Input::aggregate`) of the runs, aligned by call index. Runs longer than the shortest one are
+/// truncated. Columns with integers in every run get rounded values, so that the cumulative
+/// columns can still be added, and columns with text in any run get the values of the first run.
+pub fn aggregate_repetitions(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    inputs
+        .into_iter()
+        .map(|input| {
+            if input.repetitions.is_empty() {
+                return input;
+            }
+
+            let paths: Vec<&PathBuf> = std::iter::once(&input.path)
+                .chain(&input.repetitions)
+                .collect();
+            let tables: Vec<Table> = paths.iter().map(|path| read_table(path)).collect();
+
+            let num_columns = tables[0].columns.len();
+            if let Some((path, table)) = paths
+                .iter()
+                .zip(&tables)
+                .find(|(_, table)| table.columns.len() != num_columns)
+            {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(path),
+                    None,
+                    &format!(
+                        "{} has {} columns, but {} has {}",
+                        path.display(),
+                        table.columns.len(),
+                        input.path.display(),
+                        num_columns
+                    ),
+                );
+            }
+
+            let num_rows = tables.iter().map(|table| table.num_rows).min().unwrap();
+            if tables.iter().any(|table| table.num_rows != num_rows) {
+                options.anomaly(
+                    Some(&input.path),
+                    format!(
+                        "{}: repetitions have different numbers of rows, using the first {}",
+                        input.path.display(),
+                        num_rows
+                    ),
+                );
+            }
+
+            let columns = (0..num_columns)
+                .map(|col_idx| {
+                    let columns: Vec<&Column> =
+                        tables.iter().map(|table| &table.columns[col_idx]).collect();
+                    aggregate_column(&columns, num_rows, input.aggregate)
+                })
+                .collect();
+            let aggregate = Table {
+                headers: tables[0].headers.clone(),
+                columns,
+                num_rows,
+            };
+
+            let aggregated = augment::write_temp_csv(&aggregate);
+            log::debug!(
+                "{}: {} of {} runs in {}",
+                input.path.display(),
+                input.aggregate.name(),
+                paths.len(),
+                aggregated.path().display()
+            );
+            let names: Vec<String> = paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            plot::set_dry_run_placeholder(
+                aggregated.path(),
+                &format!("<{} of {}>", input.aggregate.name(), names.join(", ")),
+            );
+
+            Input {
+                path: aggregated.path().to_owned(),
+                _mapped: Some(aggregated),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// Parse a CSV, exiting with an error when it can't be read.
+fn read_table(path: &Path) -> Table {
+    let mut reader = match csv::Reader::from_path(path) {
+        Ok(reader) => reader,
+        Err(err) => {
+            errors::exit(
+                errors::Category::Input,
+                Some(path),
+                None,
+                &format!("Unable to read {}: {}", path.display(), err),
+            );
+        }
+    };
+    let headers = reader
+        .headers()
+        .unwrap()
+        .iter()
+        .map(str::to_owned)
+        .collect();
+    let mut table = Table::new(headers);
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).unwrap() {
+        table.push_record(&record);
+    }
+    table
+}
+
+/// The first `num_rows` values of the same column in several runs, aggregated row by row, see
+/// `aggregate_repetitions`.
+fn aggregate_column(columns: &[&Column], num_rows: usize, aggregate: Aggregate) -> Column {
+    let mut row_values = vec![0.0; columns.len()];
+    let mut aggregate_row = |row_idx: usize| {
+        for (value, column) in row_values.iter_mut().zip(columns) {
+            *value = column_value(column, row_idx).unwrap();
+        }
+        aggregate.apply(&mut row_values)
+    };
+
+    if columns
+        .iter()
+        .all(|column| matches!(column, Column::UInt(_)))
+    {
+        Column::UInt(
+            (0..num_rows)
+                .map(|row_idx| aggregate_row(row_idx).round() as u64)
+                .collect(),
+        )
+    } else if columns
+        .iter()
+        .all(|column| !matches!(column, Column::Text(_)))
+    {
+        Column::Float((0..num_rows).map(aggregate_row).collect())
+    } else {
+        match columns[0] {
+            Column::UInt(values) => Column::UInt(values[..num_rows].to_vec()),
+            Column::Float(values) => Column::Float(values[..num_rows].to_vec()),
+            Column::Text(values) => Column::Text(values[..num_rows].to_vec()),
+        }
+    }
+}
+
+/// A value of a numeric column as a float. `None` for text columns.
+fn column_value(column: &Column, row_idx: usize) -> Option<f64> {
+    match column {
+        Column::UInt(values) => Some(values[row_idx] as f64),
+        Column::Float(values) => Some(values[row_idx]),
+        Column::Text(_) => None,
+    }
+}
+
+/// How `collapse_rounds` groups calls into rounds
+#[derive(Clone)]
+pub enum Rounds {
+    /// Consecutive calls with the same value in the column with this header
+    Column(String),
+    /// Every this many calls
+    Calls(usize),
+}
+
+/// With rounds enabled (see `PipelineOptions::rounds`), replace each input with a temporary CSV with a row per
+/// round. Numeric columns get the sums of the calls of the round, except the heap size, which
+/// gets the value of the last call. The message, the method, and text columns get the values of
+/// the first call. A round column is left out, so that the inputs have the layout of the others.
+pub fn collapse_rounds(
+    inputs: Vec<Input>,
+    mapping: &ColumnMapping,
+    options: &PipelineOptions,
+) -> Vec<Input> {
+    let rounds = match &options.rounds {
+        Some(rounds) => rounds,
+        None => return inputs,
+    };
+
+    // Headers of the columns that aren't summed, as mapped or in the drun layout
+    let mapping = mapping.or(options.input_format.columns());
+    let first_headers = [
+        mapping.message.as_deref().unwrap_or(DRUN_HEADERS[0]),
+        mapping.method.as_deref().unwrap_or(DRUN_HEADERS[1]),
+    ];
+    let last_header = mapping.heap_size.as_deref().unwrap_or(DRUN_HEADERS[5]);
+
+    inputs
+        .into_iter()
+        .map(|input| {
+            let mut table = read_table(&input.path);
+            if table.num_rows == 0 {
+                return input;
+            }
+
+            // Index of the first row of each round
+            let round_starts: Vec<usize> = match &rounds {
+                Rounds::Calls(calls) => (0..table.num_rows).step_by(*calls).collect(),
+                Rounds::Column(header) => {
+                    let col_idx = match table
+                        .headers
+                        .iter()
+                        .position(|h| h.trim().eq_ignore_ascii_case(header))
+                    {
+                        Some(col_idx) => col_idx,
+                        None => {
+                            errors::exit(
+                                errors::Category::Input,
+                                Some(&input.path),
+                                None,
+                                &format!("{} has no column '{}'", input.path.display(), header),
+                            );
+                        }
+                    };
+                    let round_column = table.columns.remove(col_idx);
+                    table.headers.remove(col_idx);
+                    (0..table.num_rows)
+                        .filter(|row_idx| {
+                            *row_idx == 0
+                                || field(&round_column, *row_idx)
+                                    != field(&round_column, row_idx - 1)
+                        })
+                        .collect()
+                }
+            };
+
+            let round_ends: Vec<usize> = round_starts
+                .iter()
+                .skip(1)
+                .copied()
+                .chain(std::iter::once(table.num_rows))
+                .collect();
+            let last_rows: Vec<usize> = round_ends.iter().map(|end| end - 1).collect();
+            let rounds: Vec<(usize, usize)> = round_starts
+                .iter()
+                .copied()
+                .zip(round_ends.iter().copied())
+                .collect();
+
+            let columns = table
+                .headers
+                .iter()
+                .zip(&table.columns)
+                .map(|(header, column)| {
+                    let header = header.trim();
+                    if first_headers
+                        .iter()
+                        .any(|first| first.eq_ignore_ascii_case(header))
+                    {
+                        pick_rows(column, &round_starts)
+                    } else if last_header.eq_ignore_ascii_case(header) {
+                        pick_rows(column, &last_rows)
+                    } else {
+                        match column {
+                            Column::UInt(values) => Column::UInt(
+                                rounds
+                                    .iter()
+                                    .map(|(start, end)| values[*start..*end].iter().sum())
+                                    .collect(),
+                            ),
+                            Column::Float(values) => Column::Float(
+                                rounds
+                                    .iter()
+                                    .map(|(start, end)| values[*start..*end].iter().sum())
+                                    .collect(),
+                            ),
+                            Column::Text(_) => pick_rows(column, &round_starts),
+                        }
+                    }
+                })
+                .collect();
+            let collapsed = Table {
+                headers: table.headers,
+                columns,
+                num_rows: rounds.len(),
+            };
+
+            let collapsed_csv = augment::write_temp_csv(&collapsed);
+            log::debug!(
+                "{}: {} calls in {} rounds in {}",
+                input.path.display(),
+                table.num_rows,
+                rounds.len(),
+                collapsed_csv.path().display()
+            );
+            plot::set_dry_run_placeholder(
+                collapsed_csv.path(),
+                &format!("<rounds of {}>", input.path.display()),
+            );
+
+            Input {
+                path: collapsed_csv.path().to_owned(),
+                _mapped: Some(collapsed_csv),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// The values of a column in the given rows
+fn pick_rows(column: &Column, row_idxs: &[usize]) -> Column {
+    match column {
+        Column::UInt(values) => Column::UInt(row_idxs.iter().map(|idx| values[*idx]).collect()),
+        Column::Float(values) => Column::Float(row_idxs.iter().map(|idx| values[*idx]).collect()),
+        Column::Text(values) => {
+            Column::Text(row_idxs.iter().map(|idx| values[*idx].clone()).collect())
+        }
+    }
+}
+
+/// A value of a column as in the CSV
+fn field(column: &Column, row_idx: usize) -> String {
+    match column {
+        Column::UInt(values) => values[row_idx].to_string(),
+        Column::Float(values) => values[row_idx].to_string(),
+        Column::Text(values) => values[row_idx].clone(),
+    }
+}
+
+/// With a key column set (see `PipelineOptions::align_by`), replace the inputs with temporary CSVs with the
+/// calls whose keys are in every input, in the order of the first input, so that the same row of
+/// each input is the same logical call. The n-th call with a key in an input matches the n-th
+/// call with the key in the others. Calls missing from other inputs are left out, with a
+/// warning.
+pub fn align_by_key(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    let header = match &options.align_by {
+        Some(header) => header,
+        None => return inputs,
+    };
+    if inputs.len() < 2 {
+        return inputs;
+    }
+
+    let tables: Vec<Table> = inputs.iter().map(|input| read_table(&input.path)).collect();
+
+    // Key of each row of each input: the value of the key column and its number of occurrences
+    // before the row
+    let keys: Vec<Vec<(String, usize)>> = inputs
+        .iter()
+        .zip(&tables)
+        .map(|(input, table)| {
+            let col_idx = match table
+                .headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(header))
+            {
+                Some(col_idx) => col_idx,
+                None => {
+                    errors::exit(
+                        errors::Category::Input,
+                        Some(&input.path),
+                        None,
+                        &format!("{} has no column '{}'", input.path.display(), header),
+                    );
+                }
+            };
+            let mut occurrences: HashMap<String, usize> = HashMap::new();
+            (0..table.num_rows)
+                .map(|row_idx| {
+                    let value = field(&table.columns[col_idx], row_idx);
+                    let occurrence = occurrences.entry(value.clone()).or_default();
+                    *occurrence += 1;
+                    (value, *occurrence - 1)
+                })
+                .collect()
+        })
+        .collect();
+
+    let row_idxs: Vec<HashMap<&(String, usize), usize>> = keys
+        .iter()
+        .map(|keys| {
+            keys.iter()
+                .enumerate()
+                .map(|(idx, key)| (key, idx))
+                .collect()
+        })
+        .collect();
+    let common_keys: Vec<&(String, usize)> = keys[0]
+        .iter()
+        .filter(|key| row_idxs.iter().all(|row_idxs| row_idxs.contains_key(key)))
+        .collect();
+
+    inputs
+        .into_iter()
+        .zip(tables)
+        .zip(&row_idxs)
+        .map(|((input, table), row_idxs)| {
+            let missing = table.num_rows - common_keys.len();
+            if missing > 0 {
+                options.anomaly(
+                    Some(&input.path),
+                    format!(
+                        "{}: leaving out {} of {} calls, their '{}' isn't in every input",
+                        input.path.display(),
+                        missing,
+                        table.num_rows,
+                        header
+                    ),
+                );
+            }
+
+            let picked: Vec<usize> = common_keys.iter().map(|key| row_idxs[key]).collect();
+            let aligned = Table {
+                columns: table
+                    .columns
+                    .iter()
+                    .map(|column| pick_rows(column, &picked))
+                    .collect(),
+                headers: table.headers,
+                num_rows: picked.len(),
+            };
+
+            let aligned_csv = augment::write_temp_csv(&aligned);
+            log::debug!(
+                "{}: aligned by '{}' in {}",
+                input.path.display(),
+                header,
+                aligned_csv.path().display()
+            );
+            plot::set_dry_run_placeholder(
+                aligned_csv.path(),
+                &format!("<{} aligned by {}>", input.path.display(), header),
+            );
+
+            Input {
+                path: aligned_csv.path().to_owned(),
+                _mapped: Some(aligned_csv),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// What `separate_setup` does with the calls of the install/upgrade phase of the inputs, whose
+/// costs dwarf the ones of the other calls
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SetupPhase {
+    /// Plot them like the other calls
+    #[default]
+    Keep,
+    /// Leave them out
+    Exclude,
+    /// Leave them out, and plot them in `setup_<plot>.png`s of their own
+    Separate,
+    /// Plot them like the other calls, marked with vertical lines
+    Mark,
+}
+
+/// Methods of the calls that drun makes for `install`, `reinstall`, and `upgrade` messages, and
+/// the messages themselves, as CSVs of other tools name them
+const SETUP_METHODS: [&str; 6] = [
+    "install",
+    "reinstall",
+    "upgrade",
+    "canister_init",
+    "canister_pre_upgrade",
+    "canister_post_upgrade",
+];
+
+/// Find the calls of the install/upgrade phase of each input: the ones with a method (the second
+/// column, in the drun layout) of `SETUP_METHODS`, and the ones in the input's `setup_ranges`.
+/// Keep their indices in `Input::setup_calls` and, depending on `options.setup_phase`, replace the
+/// inputs with temporary CSVs without them, also keeping them in `Input::setup_csv`.
+pub fn separate_setup(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    let setup_phase = options.setup_phase;
+    if setup_phase == SetupPhase::Keep {
+        return inputs;
+    }
+
+    inputs
+        .into_iter()
+        .map(|input| {
+            let mut reader = match csv::Reader::from_path(&input.path) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    errors::exit(
+                        errors::Category::Input,
+                        Some(&input.path),
+                        None,
+                        &format!("Unable to read {}: {}", input.path.display(), err),
+                    );
+                }
+            };
+            let headers = reader.byte_headers().unwrap().clone();
+
+            let mut setup_calls: Vec<usize> = vec![];
+            let mut records: Vec<csv::ByteRecord> = vec![];
+            let mut record = csv::ByteRecord::new();
+            while reader.read_byte_record(&mut record).unwrap() {
+                let call = records.len() + 1;
+                let method = String::from_utf8_lossy(record.get(1).unwrap_or_default());
+                if SETUP_METHODS
+                    .iter()
+                    .any(|setup_method| method.trim().eq_ignore_ascii_case(setup_method))
+                    || input
+                        .setup_ranges
+                        .iter()
+                        .any(|(start, end)| (*start..=*end).contains(&call))
+                {
+                    setup_calls.push(call - 1);
+                }
+                records.push(record.clone());
+            }
+            for (start, end) in &input.setup_ranges {
+                if *end > records.len() {
+                    options.anomaly(
+                        Some(&input.path),
+                        format!(
+                            "{}: setup calls {}-{} are past its last call, {}",
+                            input.path.display(),
+                            start,
+                            end,
+                            records.len()
+                        ),
+                    );
+                }
+            }
+            if setup_calls.is_empty() {
+                return input;
+            }
+            log::info!(
+                "{}: {} install/upgrade call{}",
+                input.path.display(),
+                setup_calls.len(),
+                if setup_calls.len() == 1 { "" } else { "s" }
+            );
+
+            if setup_phase == SetupPhase::Mark {
+                return Input {
+                    setup_calls,
+                    ..input
+                };
+            }
+
+            let mut steady_writer = csv::Writer::from_writer(NamedTempFile::new().unwrap());
+            let mut setup_writer = csv::Writer::from_writer(NamedTempFile::new().unwrap());
+            steady_writer.write_byte_record(&headers).unwrap();
+            setup_writer.write_byte_record(&headers).unwrap();
+            for (call_idx, record) in records.iter().enumerate() {
+                if setup_calls.binary_search(&call_idx).is_ok() {
+                    setup_writer.write_byte_record(record).unwrap();
+                } else {
+                    steady_writer.write_byte_record(record).unwrap();
+                }
+            }
+
+            let steady = steady_writer.into_inner().unwrap();
+            plot::set_dry_run_placeholder(
+                steady.path(),
+                &format!("<{} without install/upgrade calls>", input.path.display()),
+            );
+            let setup_csv = match setup_phase {
+                SetupPhase::Separate => {
+                    let setup = setup_writer.into_inner().unwrap();
+                    plot::set_dry_run_placeholder(
+                        setup.path(),
+                        &format!("<install/upgrade calls of {}>", input.path.display()),
+                    );
+                    Some(setup)
+                }
+                _ => None,
+            };
+
+            Input {
+                path: steady.path().to_owned(),
+                setup_calls,
+                setup_csv,
+                _mapped: Some(steady),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// What `match_row_counts` does when the inputs have different numbers of calls
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LengthMismatch {
+    /// Only warn
+    #[default]
+    Warn,
+    /// Leave out the calls after the end of the shortest input
+    Truncate,
+    /// Repeat the last call of the shorter inputs, with no instructions and pages, up to the
+    /// length of the longest input. The totals stay at their final values.
+    Pad,
+}
+
+/// Warn when the inputs have different numbers of calls (not counting the calls before their
+/// `Alignment::offset`), which makes comparisons of the totals misleading. With
+/// `LengthMismatch::Truncate` or `LengthMismatch::Pad` (see `options.length_mismatch`) the inputs are
+/// also replaced with temporary CSVs with the same numbers of calls.
+pub fn match_row_counts(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    if inputs.len() < 2 {
+        return inputs;
+    }
+
+    let num_rows: Vec<usize> = inputs
+        .iter()
+        .map(|input| match csv::Reader::from_path(&input.path) {
+            Ok(mut reader) => reader.byte_records().count(),
+            Err(_) => 0,
+        })
+        .collect();
+    let counts: Vec<usize> = inputs
+        .iter()
+        .zip(&num_rows)
+        .map(|(input, num_rows)| num_rows.saturating_sub(input.alignment.offset))
+        .collect();
+    let (min, max) = (*counts.iter().min().unwrap(), *counts.iter().max().unwrap());
+    if min == max {
+        return inputs;
+    }
+
+    let counts: Vec<String> = inputs
+        .iter()
+        .zip(&counts)
+        .map(|(input, count)| format!("{}: {}", input.label, count))
+        .collect();
+    let counts = counts.join(", ");
+    let target = match options.length_mismatch {
+        LengthMismatch::Warn => {
+            options.anomaly(
+                None,
+                format!(
+                    "The inputs have different numbers of calls ({}), totals after call {} are \
+                     not comparable, see --length-mismatch",
+                    counts, min
+                ),
+            );
+            return inputs;
+        }
+        LengthMismatch::Truncate => {
+            log::warn!(
+                "The inputs have different numbers of calls ({}), plotting the first {} calls \
+                 of each input",
+                counts,
+                min
+            );
+            min
+        }
+        LengthMismatch::Pad => {
+            log::warn!(
+                "The inputs have different numbers of calls ({}), padding them to {} calls",
+                counts,
+                max
+            );
+            max
+        }
+    };
+
+    inputs
+        .into_iter()
+        .zip(num_rows)
+        .map(|(input, input_rows)| {
+            let num_rows = target + input.alignment.offset;
+            if input_rows == num_rows || input_rows == 0 {
+                return input;
+            }
+            let table = read_table(&input.path);
+
+            let rows: Vec<usize> = (0..num_rows)
+                .map(|row_idx| row_idx.min(table.num_rows - 1))
+                .collect();
+            let mut columns: Vec<Column> = table
+                .columns
+                .iter()
+                .map(|column| pick_rows(column, &rows))
+                .collect();
+            if num_rows > table.num_rows {
+                for col_idx in TRIMMED_COL_IDXS {
+                    if let Some(Column::UInt(values)) = columns.get_mut(col_idx - 1) {
+                        values[table.num_rows..].fill(0);
+                    }
+                }
+            }
+            let matched = Table {
+                headers: table.headers,
+                columns,
+                num_rows,
+            };
+
+            let matched_csv = augment::write_temp_csv(&matched);
+            log::debug!(
+                "{}: {} calls in {}",
+                input.path.display(),
+                num_rows,
+                matched_csv.path().display()
+            );
+            plot::set_dry_run_placeholder(
+                matched_csv.path(),
+                &format!("<{} with {} calls>", input.path.display(), num_rows),
+            );
+
+            Input {
+                path: matched_csv.path().to_owned(),
+                _mapped: Some(matched_csv),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// What `trim_outliers` does with outliers
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TrimMode {
+    /// Replace outliers with the percentile they're beyond
+    #[default]
+    Clamp,
+    /// Leave out the calls with outliers
+    Drop,
+}
+
+/// Outlier trimming of the inputs, see `trim_outliers`
+#[derive(Clone, Copy)]
+pub struct Trim {
+    /// Percentile in (50, 100) above which values are outliers. Values below the `100 -
+    /// percentile`th percentile are outliers too.
+    pub percentile: f64,
+    pub mode: TrimMode,
+}
+
+/// Columns that outliers are trimmed in: the per-call costs that the cumulative columns sum up
+const TRIMMED_COL_IDXS: [usize; 3] = [
+    INSTRUCTIONS_COL_IDX,
+    ACCESSED_HOST_PAGES_COL_IDX,
+    DIRTIED_HOST_PAGES_COL_IDX,
+];
+
+/// With trimming enabled (see `PipelineOptions::trim`), replace each input with a temporary CSV where the
+/// values of the per-call cost columns beyond the percentiles of the input are clamped to them,
+/// or the calls with such values are left out. `Input::trimmed` is set to the number of values
+/// clamped or calls left out.
+pub fn trim_outliers(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    let trim = match options.trim {
+        Some(trim) => trim,
+        None => return inputs,
+    };
+
+    inputs
+        .into_iter()
+        .map(|input| {
+            let mut table = read_table(&input.path);
+            let mut keep = vec![true; table.num_rows];
+            let mut trimmed = 0;
+
+            for col_idx in TRIMMED_COL_IDXS {
+                let values = match table.columns.get_mut(col_idx - 1) {
+                    Some(Column::UInt(values)) if !values.is_empty() => values,
+                    _ => continue,
+                };
+                let mut sorted = values.clone();
+                sorted.sort_unstable();
+                let low = rolling::percentile(&sorted, (100.0 - trim.percentile) / 100.0);
+                let high = rolling::percentile(&sorted, trim.percentile / 100.0);
+
+                for (value, keep) in values.iter_mut().zip(keep.iter_mut()) {
+                    if *value >= low && *value <= high {
+                        continue;
+                    }
+                    match trim.mode {
+                        TrimMode::Clamp => {
+                            *value = (*value).clamp(low, high);
+                            trimmed += 1;
+                        }
+                        TrimMode::Drop => {
+                            if *keep {
+                                *keep = false;
+                                trimmed += 1;
+                            }
+                        }
+                    }
+                }
+            }
+            table.retain_rows(&keep);
+
+            let trimmed_csv = augment::write_temp_csv(&table);
+            log::debug!(
+                "{}: trimmed {} outliers in {}",
+                input.path.display(),
+                trimmed,
+                trimmed_csv.path().display()
+            );
+            plot::set_dry_run_placeholder(
+                trimmed_csv.path(),
+                &format!("<{} without outliers>", input.path.display()),
+            );
+
+            Input {
+                path: trimmed_csv.path().to_owned(),
+                trimmed,
+                _mapped: Some(trimmed_csv),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// Replace each input with leading records to skip with a temporary CSV without them.
+pub fn skip_rows(inputs: Vec<Input>, options: &PipelineOptions) -> Vec<Input> {
+    inputs
+        .into_iter()
+        .map(|input| {
+            let num_rows = input.alignment.skip_rows;
+            if num_rows == 0 {
+                return input;
+            }
+
+            let mut reader = match csv::Reader::from_path(&input.path) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    errors::exit(
+                        errors::Category::Input,
+                        Some(&input.path),
+                        None,
+                        &format!("Unable to read {}: {}", input.path.display(), err),
+                    );
+                }
+            };
+            let mut writer = csv::Writer::from_writer(NamedTempFile::new().unwrap());
+            writer
+                .write_byte_record(reader.byte_headers().unwrap())
+                .unwrap();
+
+            let mut record = csv::ByteRecord::new();
+            let mut row_idx = 0;
+            while reader.read_byte_record(&mut record).unwrap() {
+                if row_idx >= num_rows {
+                    writer.write_byte_record(&record).unwrap();
+                }
+                row_idx += 1;
+            }
+            if row_idx <= num_rows {
+                options.anomaly(
+                    Some(&input.path),
+                    format!(
+                        "{}: skipping {} rows leaves none of its {} rows",
+                        input.path.display(),
+                        num_rows,
+                        row_idx
+                    ),
+                );
+            }
+
+            let skipped = writer.into_inner().unwrap();
+            log::debug!(
+                "{}: skipped {} rows in {}",
+                input.path.display(),
+                num_rows,
+                skipped.path().display()
+            );
+            plot::set_dry_run_placeholder(
+                skipped.path(),
+                &format!("<{} without {} rows>", input.path.display(), num_rows),
+            );
+
+            Input {
+                path: skipped.path().to_owned(),
+                _mapped: Some(skipped),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// Headers of the columns of drun generated CSVs, in order
+pub const DRUN_HEADERS: [&str; 6] = [
+    "message",
+    "method",
+    "instructions",
+    "accessed host pages",
+    "dirtied host pages",
+    "total Wasm pages in use",
+];
+
+/// Tool that generated the inputs, whose columns `map_columns` maps to the ones of drun
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// drun's `canister_perf.csv`
+    #[default]
+    Drun,
+    /// CSVs of ic-replay replaying a state with production traffic: `message_index`,
+    /// `method_name`, `instructions_executed`, `accessed_pages`, `dirty_pages`, and
+    /// `wasm_memory_pages`, with the `rounds` of DTS
+    IcReplay,
+    /// CSVs of the state machine test harness: `call`, `method`, `instructions`,
+    /// `dirty_pages`, and `memory_pages`
+    StateMachine,
+}
+
+impl InputFormat {
+    /// Headers of the columns of the format, in the roles of the columns of drun
+    fn columns(self) -> ColumnMapping {
+        let header = |header: &str| Some(header.to_owned());
+        match self {
+            InputFormat::Drun => ColumnMapping::default(),
+            InputFormat::IcReplay => ColumnMapping {
+                message: header("message_index"),
+                method: header("method_name"),
+                instructions: header("instructions_executed"),
+                accessed_pages: header("accessed_pages"),
+                dirtied_pages: header("dirty_pages"),
+                heap_size: header("wasm_memory_pages"),
+                rounds: header("rounds"),
+            },
+            InputFormat::StateMachine => ColumnMapping {
+                message: header("call"),
+                method: header("method"),
+                instructions: header("instructions"),
+                accessed_pages: None,
+                dirtied_pages: header("dirty_pages"),
+                heap_size: header("memory_pages"),
+                rounds: None,
+            },
+        }
+    }
+}
+
+/// Header of the column of newer drun generated CSVs with the number of rounds the execution of
+/// each message took, with deterministic time slicing (DTS)
+pub const ROUNDS_HEADER: &str = "rounds";
+
+/// 1-based index in the augmented CSVs of the column with the given header (ignoring case), e.g.
+/// `ROUNDS_HEADER`, when all of the inputs have one at the same index
+pub fn common_column(inputs: &[Input], header: &str) -> Option<usize> {
+    let col_idxs: Vec<Option<usize>> = inputs
+        .iter()
+        .map(|input| augment::find_column(&input.path, header))
+        .collect();
+    match col_idxs.first() {
+        Some(Some(col_idx)) if col_idxs.iter().all(|other| other == &Some(*col_idx)) => {
+            Some(augment::augmented_col_idx(*col_idx))
+        }
+        _ => {
+            if col_idxs.iter().any(Option::is_some) {
+                log::warn!(
+                    "Only some of the inputs have a '{}' column at the same index, not plotting \
+                     it",
+                    header
+                );
+            }
+            None
+        }
+    }
+}
+
+/// When the configuration maps columns to their roles, replace each input with a temporary CSV
+/// with the mapped columns in the layout of drun generated CSVs, so that CSVs from other tools
+/// can be plotted like drun's, with the rounds (see `ROUNDS_HEADER`) after them when mapped.
+/// Columns that aren't mapped are left out. Missing roles get the 0-based row index as the
+/// message, an empty method, and zeros otherwise. The roles that the configuration doesn't map
+/// are mapped as in `options.input_format`, which only has rounds in some inputs.
+pub fn map_columns(
+    inputs: Vec<Input>,
+    mapping: &ColumnMapping,
+    options: &PipelineOptions,
+) -> Vec<Input> {
+    let rounds_optional = mapping.rounds.is_none();
+    let mapping = &mapping.or(options.input_format.columns());
+    if mapping.is_empty() {
+        return inputs;
+    }
+
+    let roles: [Option<&str>; 6] = [
+        mapping.message.as_deref(),
+        mapping.method.as_deref(),
+        mapping.instructions.as_deref(),
+        mapping.accessed_pages.as_deref(),
+        mapping.dirtied_pages.as_deref(),
+        mapping.heap_size.as_deref(),
+    ];
+
+    inputs
+        .into_iter()
+        .map(|input| {
+            let mut reader = match csv::Reader::from_path(&input.path) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    errors::exit(
+                        errors::Category::Input,
+                        Some(&input.path),
+                        None,
+                        &format!("Unable to read {}: {}", input.path.display(), err),
+                    );
+                }
+            };
+            let headers = reader.headers().unwrap().clone();
+
+            let col_idxs: Vec<Option<usize>> = roles
+                .iter()
+                .map(|role| {
+                    let header = (*role)?;
+                    match headers
+                        .iter()
+                        .position(|h| h.trim().eq_ignore_ascii_case(header.trim()))
+                    {
+                        Some(col_idx) => Some(col_idx),
+                        None => {
+                            let headers: Vec<&str> = headers.iter().collect();
+                            errors::exit(
+                                errors::Category::Input,
+                                Some(&input.path),
+                                None,
+                                &format!(
+                                    "{} has no column '{}', its columns are: {}",
+                                    input.path.display(),
+                                    header,
+                                    headers.join(", ")
+                                ),
+                            );
+                        }
+                    }
+                })
+                .collect();
+            let rounds_col_idx = mapping.rounds.as_deref().and_then(|header| {
+                match headers
+                    .iter()
+                    .position(|h| h.trim().eq_ignore_ascii_case(header.trim()))
+                {
+                    Some(col_idx) => Some(col_idx),
+                    None if rounds_optional => None,
+                    None => errors::exit(
+                        errors::Category::Input,
+                        Some(&input.path),
+                        None,
+                        &format!("{} has no column '{}'", input.path.display(), header),
+                    ),
+                }
+            });
+
+            let mut writer = csv::Writer::from_writer(NamedTempFile::new().unwrap());
+            for header in DRUN_HEADERS {
+                writer.write_field(header).unwrap();
+            }
+            if rounds_col_idx.is_some() {
+                writer.write_field(ROUNDS_HEADER).unwrap();
+            }
+            writer.write_record(None::<&[u8]>).unwrap();
+
+            let mut record = csv::StringRecord::new();
+            let mut row_idx: u64 = 0;
+            while reader.read_record(&mut record).unwrap() {
+                for (role_idx, col_idx) in col_idxs.iter().enumerate() {
+                    let field = match col_idx {
+                        Some(col_idx) => record.get(*col_idx).unwrap_or("").trim().to_owned(),
+                        None if role_idx == 0 => row_idx.to_string(),
+                        None if role_idx == 1 => String::new(),
+                        None => "0".to_owned(),
+                    };
+                    writer.write_field(field).unwrap();
+                }
+                if let Some(col_idx) = rounds_col_idx {
+                    writer
+                        .write_field(record.get(col_idx).unwrap_or("").trim())
+                        .unwrap();
+                }
+                writer.write_record(None::<&[u8]>).unwrap();
+                row_idx += 1;
+            }
+
+            let mapped = writer.into_inner().unwrap();
+            log::debug!(
+                "{}: mapped columns to {}",
+                input.path.display(),
+                mapped.path().display()
+            );
+            plot::set_dry_run_placeholder(
+                mapped.path(),
+                &format!("<mapped {}>", input.path.display()),
+            );
+
+            Input {
+                path: mapped.path().to_owned(),
+                _mapped: Some(mapped),
+                ..input
+            }
+        })
+        .collect()
+}
+
+/// Parse `PATH` or `PATH:LABEL` positional arguments, and `PATH:KEY=VALUE:...` `--input`
+/// arguments. Inputs without a label get one derived from their file names, see
+/// `derive_labels`. Without any inputs the inputs of the configuration file are used, or the
+/// default `FILES` when it doesn't have any.
+pub fn parse_inputs(
+    args: &[String],
+    specs: &[String],
+    config_inputs: &[InputConfig],
+) -> Vec<Input> {
+    if args.is_empty() && specs.is_empty() && config_inputs.is_empty() {
+        return FILES
+            .iter()
+            .map(|(path, label)| Input {
+                path: PathBuf::from(path),
+                original: PathBuf::from(path),
+                label: label.to_string(),
+                description: None,
+                style: SeriesStyle::default(),
+                alignment: Alignment::default(),
+                repetitions: vec![],
+                aggregate: Aggregate::default(),
+                trimmed: 0,
+                filled: vec![],
+                setup_ranges: vec![],
+                setup_calls: vec![],
+                setup_csv: None,
+                _mapped: None,
+                _cleaned_repetitions: vec![],
+            })
+            .collect();
+    }
+
+    let mut inputs: Vec<InputSpec> = args
+        .iter()
+        .map(|arg| match arg.split_once(':') {
+            Some((path, label)) => InputSpec::new(PathBuf::from(path), Some(label.to_owned())),
+            None => InputSpec::new(PathBuf::from(arg), None),
+        })
+        .collect();
+
+    inputs.extend(specs.iter().map(|spec| parse_input_spec(spec)));
+
+    if inputs.is_empty() {
+        inputs = config_inputs
+            .iter()
+            .map(|input| InputSpec {
+                style: SeriesStyle {
+                    color: input.color.clone(),
+                    dash: input.dash,
+                },
+                alignment: Alignment {
+                    skip_rows: input.skip_rows,
+                    offset: input.offset,
+                },
+                description: input.description.clone(),
+                repetitions: input.repetitions.clone(),
+                aggregate: input.aggregate,
+                setup_ranges: match &input.setup_calls {
+                    Some(ranges) => parse_call_ranges(ranges).unwrap_or_else(|err| {
+                        errors::exit(
+                            errors::Category::Config,
+                            None,
+                            None,
+                            &format!("Invalid setup_calls of {}: {}", input.path.display(), err),
+                        )
+                    }),
+                    None => vec![],
+                },
+                ..InputSpec::new(input.path.clone(), input.label.clone())
+            })
+            .collect();
+    }
+
+    let paths: Vec<&PathBuf> = inputs.iter().map(|input| &input.path).collect();
+    let derived_labels = derive_labels(&paths);
+
+    inputs
+        .into_iter()
+        .zip(derived_labels)
+        .map(|(input, derived_label)| Input {
+            original: input.path.clone(),
+            path: input.path,
+            label: input.label.unwrap_or(derived_label),
+            description: input.description,
+            style: input.style,
+            alignment: input.alignment,
+            repetitions: input.repetitions,
+            aggregate: input.aggregate,
+            trimmed: 0,
+            filled: vec![],
+            setup_ranges: input.setup_ranges,
+            setup_calls: vec![],
+            setup_csv: None,
+            _mapped: None,
+            _cleaned_repetitions: vec![],
+        })
+        .collect()
+}
+
+/// An input before its label is derived
+struct InputSpec {
+    path: PathBuf,
+    label: Option<String>,
+    description: Option<String>,
+    style: SeriesStyle,
+    alignment: Alignment,
+    repetitions: Vec<PathBuf>,
+    aggregate: Aggregate,
+    setup_ranges: Vec<(usize, usize)>,
+}
+
+impl InputSpec {
+    fn new(path: PathBuf, label: Option<String>) -> InputSpec {
+        InputSpec {
+            path,
+            label,
+            description: None,
+            style: SeriesStyle::default(),
+            alignment: Alignment::default(),
+            repetitions: vec![],
+            aggregate: Aggregate::default(),
+            setup_ranges: vec![],
+        }
+    }
+}
+
+/// Parse an `--input` argument: a path followed by `:KEY=VALUE` options. Values can be wrapped in
+/// double quotes to include `:`.
+fn parse_input_spec(spec: &str) -> InputSpec {
+    let parts = split_spec(spec);
+    let mut parts = parts.into_iter();
+
+    let mut input = InputSpec::new(PathBuf::from(parts.next().unwrap_or_default()), None);
+
+    for part in parts {
+        let (key, value) = match part.split_once('=') {
+            Some(key_value) => key_value,
+            None => input_spec_error(spec, &format!("expected KEY=VALUE, found '{}'", part)),
+        };
+
+        match key {
+            "label" => input.label = Some(value.to_owned()),
+            "description" => input.description = Some(value.to_owned()),
+            "color" => match plot::check_color(value) {
+                Ok(()) => input.style.color = Some(value.to_owned()),
+                Err(err) => input_spec_error(spec, &err),
+            },
+            "dash" => match value.parse() {
+                Ok(dash) => input.style.dash = Some(dash),
+                Err(_) => input_spec_error(spec, &format!("invalid dash type '{}'", value)),
+            },
+            "skip_rows" => match value.parse() {
+                Ok(skip_rows) => input.alignment.skip_rows = skip_rows,
+                Err(_) => input_spec_error(spec, &format!("invalid number of rows '{}'", value)),
+            },
+            "offset" => match value.parse() {
+                Ok(offset) => input.alignment.offset = offset,
+                Err(_) => input_spec_error(spec, &format!("invalid offset '{}'", value)),
+            },
+            "repetition" => input.repetitions.push(PathBuf::from(value)),
+            "aggregate" => match value {
+                "mean" => input.aggregate = Aggregate::Mean,
+                "median" => input.aggregate = Aggregate::Median,
+                _ => input_spec_error(spec, &format!("invalid aggregate '{}'", value)),
+            },
+            "setup_calls" => match parse_call_ranges(value) {
+                Ok(ranges) => input.setup_ranges = ranges,
+                Err(err) => input_spec_error(spec, &err),
+            },
+            _ => input_spec_error(spec, &format!("unknown key '{}'", key)),
+        }
+    }
+
+    input
+}
+
+/// Parse 1-based calls and inclusive ranges of calls separated by commas, e.g. `1-3,50`.
+fn parse_call_ranges(ranges: &str) -> Result<Vec<(usize, usize)>, String> {
+    ranges
+        .split(',')
+        .map(|range| {
+            let range = range.trim();
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            match (start.trim().parse(), end.trim().parse()) {
+                (Ok(start), Ok(end)) if 1 <= start && start <= end => Ok((start, end)),
+                _ => Err(format!("invalid calls '{}', expected e.g. `1-3,50`", range)),
+            }
+        })
+        .collect()
+}
+
+/// Split on `:`s that are not in double quotes, removing the quotes.
+fn split_spec(spec: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut in_quotes = false;
+
+    for c in spec.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ':' if !in_quotes => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(c),
+        }
+    }
+
+    parts
+}
+
+fn input_spec_error(spec: &str, msg: &str) -> ! {
+    errors::exit(
+        errors::Category::Usage,
+        None,
+        None,
+        &format!("Invalid --input '{}': {}", spec, msg),
+    );
+}
+
+/// Generate legend titles from file stems: words (separated by `_` or `-`) shared as a prefix by
+/// all of the files are dropped, and the rest are joined with spaces. For example
+/// `canister_perf_copying_gc.csv` and `canister_perf_compacting_gc.csv` become "copying gc" and
+/// "compacting gc".
+fn derive_labels(paths: &[&PathBuf]) -> Vec<String> {
+    let words: Vec<Vec<String>> = paths
+        .iter()
+        .map(|path| {
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .split(['_', '-'])
+                .filter(|word| !word.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .collect();
+
+    let mut common_prefix_len = 0;
+    if words.len() > 1 {
+        let shortest = words.iter().map(Vec::len).min().unwrap_or(0);
+        while common_prefix_len < shortest
+            && words
+                .iter()
+                .all(|w| w[common_prefix_len] == words[0][common_prefix_len])
+        {
+            common_prefix_len += 1;
+        }
+    }
+
+    words
+        .iter()
+        .map(|words| {
+            // Don't strip everything when a file name is a prefix of another
+            let words = if common_prefix_len < words.len() {
+                &words[common_prefix_len..]
+            } else {
+                &words[..]
+            };
+            words.join(" ")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_input_spec_options() {
+        let input = parse_input_spec(
+            "runs/a.csv:label=\"Copying GC: 4 GiB\":color=#A00000:dash=2:skip_rows=3:offset=1:\
+             repetition=runs/b.csv:repetition=runs/c.csv",
+        );
+        assert_eq!(input.path, PathBuf::from("runs/a.csv"));
+        assert_eq!(input.label.as_deref(), Some("Copying GC: 4 GiB"));
+        assert_eq!(input.style.color.as_deref(), Some("#A00000"));
+        assert_eq!(input.style.dash, Some(2));
+        assert_eq!(input.alignment.skip_rows, 3);
+        assert_eq!(input.alignment.offset, 1);
+        assert_eq!(
+            input.repetitions,
+            [PathBuf::from("runs/b.csv"), PathBuf::from("runs/c.csv")]
+        );
+    }
+
+    #[test]
+    fn parse_input_spec_defaults() {
+        let input = parse_input_spec("a.csv");
+        assert_eq!(input.path, PathBuf::from("a.csv"));
+        assert_eq!(input.label, None);
+        assert_eq!(input.style.color, None);
+        assert_eq!(input.style.dash, None);
+        assert_eq!(input.alignment.skip_rows, 0);
+        assert_eq!(input.alignment.offset, 0);
+        assert!(input.repetitions.is_empty());
+    }
+
+    /// The input of a CSV with the given contents, in `dir`
+    fn csv_input(dir: &Path, contents: &str) -> Input {
+        let path = dir.join("input.csv");
+        std::fs::write(&path, contents).unwrap();
+        parse_inputs(&[path.display().to_string()], &[], &[]).remove(0)
+    }
+
+    fn uint_column(table: &Table, header: &str) -> Vec<u64> {
+        let col_idx = table.headers.iter().position(|h| h == header).unwrap();
+        match &table.columns[col_idx] {
+            Column::UInt(values) => values.clone(),
+            _ => panic!("{} isn't a column of integers", header),
+        }
+    }
+
+    #[test]
+    fn collapse_rounds_empty_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = csv_input(dir.path(), &format!("{}\n", DRUN_HEADERS.join(",")));
+        let options = PipelineOptions {
+            rounds: Some(Rounds::Calls(5)),
+            ..Default::default()
+        };
+
+        let collapsed = collapse_rounds(vec![input], &ColumnMapping::default(), &options);
+        assert_eq!(collapsed[0].path, dir.path().join("input.csv"));
+    }
+
+    #[test]
+    fn collapse_rounds_input_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = csv_input(
+            dir.path(),
+            "message_index,method_name,instructions_executed,accessed_pages,dirty_pages,\
+             wasm_memory_pages,rounds\n\
+             0,a,10,1,1,16,1\n\
+             1,b,20,2,1,17,1\n\
+             2,c,30,3,1,18,2\n\
+             3,d,40,4,1,20,1\n\
+             4,e,50,5,1,21,1\n",
+        );
+        let options = PipelineOptions {
+            rounds: Some(Rounds::Calls(2)),
+            input_format: InputFormat::IcReplay,
+            ..Default::default()
+        };
+
+        let collapsed = collapse_rounds(vec![input], &ColumnMapping::default(), &options);
+        let table = read_table(&collapsed[0].path);
+        assert_eq!(table.num_rows, 3);
+        assert_eq!(uint_column(&table, "message_index"), [0, 2, 4]);
+        assert_eq!(uint_column(&table, "instructions_executed"), [30, 70, 50]);
+        assert_eq!(uint_column(&table, "wasm_memory_pages"), [17, 20, 21]);
+    }
+}
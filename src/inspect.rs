@@ -0,0 +1,65 @@
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::augment;
+use crate::table::format_table;
+
+/// Pager used when `$PAGER` is not set. `-S` keeps wide rows on one line, `-F` exits right away
+/// when the table fits on the screen.
+const DEFAULT_PAGER: &str = "less -SFX";
+
+/// Print the augmented records of a CSV as a table with 1-based row numbers. When `paged` and
+/// standard output is a terminal the table is shown in `$PAGER`.
+pub fn inspect(csv_path: &Path, paged: bool) {
+    let augmented = augment::add_cumulative_columns(csv_path);
+
+    let mut reader = csv::Reader::from_path(augmented.path()).unwrap();
+    let csv_headers = reader.headers().unwrap().to_owned();
+
+    let mut headers = vec!["#"];
+    headers.extend(csv_headers.iter());
+
+    let rows: Vec<Vec<String>> = reader
+        .records()
+        .enumerate()
+        .map(|(row_idx, record)| {
+            let mut row = vec![(row_idx + 1).to_string()];
+            row.extend(record.unwrap().iter().map(str::to_owned));
+            row
+        })
+        .collect();
+
+    let table = format_table(&headers, &rows);
+
+    if paged && std::io::stdout().is_terminal() {
+        page(&table);
+    } else {
+        print!("{}", table);
+    }
+}
+
+fn page(text: &str) {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_owned());
+    let mut words = pager.split_whitespace();
+
+    let process = words.next().and_then(|program| {
+        Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok()
+    });
+
+    let mut process = match process {
+        Some(process) => process,
+        None => {
+            print!("{}", text);
+            return;
+        }
+    };
+
+    // The pager closes its input when the user quits before reaching the end
+    let _ = process.stdin.take().unwrap().write_all(text.as_bytes());
+    let _ = process.wait();
+}
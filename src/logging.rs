@@ -0,0 +1,40 @@
+use std::io::Write;
+
+use log::{Level, LevelFilter};
+
+/// Set up logging to standard error. Info messages, the progress of the tool, are shown by
+/// default, `-v` adds debug and `-vv` trace messages, and `--quiet` leaves only warnings and
+/// errors. `RUST_LOG` overrides the level.
+pub fn init(verbose: u8, quiet: bool) {
+    let level = if quiet {
+        LevelFilter::Warn
+    } else {
+        match verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    };
+
+    let mut builder = env_logger::Builder::new();
+    if std::env::var_os("RUST_LOG").is_some() {
+        builder.parse_default_env();
+    } else {
+        builder.filter_module("generate_plots", level);
+    }
+
+    builder
+        .format(|buf, record| {
+            if record.level() == Level::Info {
+                writeln!(buf, "{}", record.args())
+            } else {
+                writeln!(
+                    buf,
+                    "{}: {}",
+                    record.level().as_str().to_lowercase(),
+                    record.args()
+                )
+            }
+        })
+        .init();
+}
@@ -1,170 +1,1755 @@
-use std::io::Write;
-use std::path::Path;
-use std::process::{Command, Stdio};
+mod auc;
+mod augment;
+mod badge;
+mod cache;
+mod columnar;
+mod columns;
+mod compare;
+mod config;
+mod correlation;
+mod data;
+mod diff;
+mod divergence;
+mod errors;
+mod export;
+mod gc_slices;
+mod html;
+mod inputs;
+mod inspect;
+mod logging;
+mod matrix;
+mod methods;
+mod metrics;
+mod pareto;
+mod plot;
+mod progress;
+mod query;
+mod ratios;
+mod report;
+mod rolling;
+mod runs;
+mod serve;
+mod site;
+mod stacks;
+mod stats;
+mod table;
+mod tui;
+#[cfg(feature = "upload")]
+mod upload;
 
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use clap::{Parser, Subcommand, ValueEnum};
 use tempfile::NamedTempFile;
 
-fn main() {
-    let files: Vec<(NamedTempFile, &'static str)> = FILES
-        .iter()
-        .map(|(file_name, plot_name)| {
-            let tmp = add_cumulative_columns(Path::new(file_name));
-            (tmp, *plot_name)
-        })
-        .collect();
+#[derive(Parser)]
+#[command(about = "Generate gnuplot plots from drun canister perf CSVs")]
+/// Options with an environment variable (`DRUN_BENCH_*`, shown in `--help`) take their value from
+/// it when they're not given on the command line, e.g. for tweaking runs in CI jobs.
+struct Args {
+    /// Input CSVs, as `PATH` or `PATH:LABEL`. Without a label the legend title is derived from
+    /// the file name. Defaults to the copying and compacting GC CSVs in the working directory.
+    inputs: Vec<String>,
+
+    /// Input CSV with per-input options, as `PATH:KEY=VALUE:...`. Keys are `label`, `color` (a
+    /// gnuplot color, e.g. `#00A000`), `dash` (a gnuplot dash type), `skip_rows` (leading records
+    /// to leave out, e.g. warm-up calls), `offset` (leading calls to plot before the first call of
+    /// the other inputs), `repetition` (another run of the same build to aggregate with, can be
+    /// given several times), and `aggregate` (`mean` or `median` of the repetitions). Values can
+    /// be quoted.
+    #[arg(long = "input", value_name = "SPEC")]
+    input_specs: Vec<String>,
+
+    /// Archive the augmented inputs and generated plots as a run with this tag
+    #[arg(long, env = "DRUN_BENCH_TAG")]
+    tag: Option<String>,
+
+    /// Regenerate every plot even when its inputs haven't changed since the last run
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Keep augmented CSVs between invocations and only process rows appended to the inputs
+    /// since the previous run. Inputs that are preprocessed (e.g. with skipped rows or mapped
+    /// columns) are augmented from scratch.
+    #[arg(long)]
+    incremental: bool,
+
+    /// Keep the augmented inputs in a columnar (Arrow IPC) cache, so that later runs with the
+    /// same inputs don't need to parse them again
+    #[arg(long, conflicts_with = "incremental")]
+    columnar_cache: bool,
+
+    /// Memory map the inputs instead of reading them with buffered reads. Faster for multi-GB
+    /// inputs on fast disks.
+    #[arg(long, conflicts_with = "incremental")]
+    mmap: bool,
+
+    /// Also plot the rolling p95 and p99 of per-call instructions over a window of this many
+    /// calls, as `rolling_percentiles_instructions.png`
+    #[arg(long, value_name = "WINDOW")]
+    rolling_percentiles: Option<usize>,
+
+    /// Also plot per-call instructions smoothed with a moving mean (`window=CALLS`) or an
+    /// exponential moving average (`ema=ALPHA`, with ALPHA in (0, 1]), as
+    /// `smoothed_instructions.png`
+    #[arg(long, value_name = "SPEC", value_parser = parse_smoothing)]
+    smooth: Option<rolling::Smoothing>,
+
+    /// Also plot a Pareto chart of per-call instructions for each input, as
+    /// `pareto_instructions_<label>.png`
+    #[arg(long)]
+    pareto: bool,
+
+    /// Detect calls that look dominated by GC work, mark them in the plots, and list them
+    #[arg(long)]
+    gc_slices: bool,
+
+    /// Configuration file. Defaults to `generate_plots.toml` in the working directory, when it
+    /// exists.
+    #[arg(long, global = true, value_name = "FILE", env = "DRUN_BENCH_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Profile of the configuration file to use, see `[profile.<name>]` in the file written by
+    /// `init`
+    #[arg(long, global = true, value_name = "NAME", env = "DRUN_BENCH_PROFILE")]
+    profile: Option<String>,
+
+    /// Only print warnings and errors, and don't show progress bars
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// How errors are printed to standard error: messages, or JSON objects for scripts
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = errors::ErrorFormat::Text,
+        env = "DRUN_BENCH_ERROR_FORMAT"
+    )]
+    error_format: errors::ErrorFormat,
+
+    /// Print debug messages, or with `-vv` also trace messages such as the gnuplot programs
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Colors of the plots
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = plot::Theme::Light,
+        env = "DRUN_BENCH_THEME"
+    )]
+    theme: plot::Theme,
+
+    /// Sizes of the plots, text, lines, and points
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = plot::Preset::Standard,
+        env = "DRUN_BENCH_PRESET"
+    )]
+    preset: plot::Preset,
+
+    /// Fonts of the plots, in order of preference. The first one that's installed is used.
+    #[arg(
+        long,
+        global = true,
+        value_name = "FONTS",
+        value_delimiter = ',',
+        default_value = "JetBrains Mono,DejaVu Sans Mono,Liberation Mono",
+        env = "DRUN_BENCH_FONTS"
+    )]
+    fonts: Vec<String>,
+
+    /// Print numbers in tables and reports as they are, rather than with the thousands and decimal
+    /// separators of the locale
+    #[arg(long, global = true)]
+    raw_numbers: bool,
+
+    /// Trim the outliers of each input before plotting and statistics: per-call instructions and
+    /// accessed and dirtied pages above the input's P-th percentile (e.g. "99%") or below its
+    /// (100-P)-th. Reports note how many values were trimmed.
+    #[arg(long, global = true, value_name = "P", value_parser = parse_trim_percentile)]
+    trim_outliers: Option<f64>,
+
+    /// Whether `--trim-outliers` clamps outliers to the percentiles or leaves out their calls
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = inputs::TrimMode::Clamp,
+        requires = "trim_outliers"
+    )]
+    trim_mode: inputs::TrimMode,
+
+    /// Collapse the calls of each input into rounds of consecutive calls with the same value in
+    /// this column, e.g. a round or batch number, and plot the sums of the rounds
+    #[arg(
+        long,
+        global = true,
+        value_name = "HEADER",
+        conflicts_with = "calls_per_round"
+    )]
+    round_column: Option<String>,
+
+    /// Collapse every N calls of each input into a round, and plot the sums of the rounds
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    calls_per_round: Option<u64>,
+
+    /// Match the calls of the inputs by their values in this column, e.g. a message id, instead
+    /// of by position. Calls that aren't in every input are left out.
+    #[arg(long, global = true, value_name = "HEADER")]
+    align_by: Option<String>,
+
+    /// Only plot the calls of this canister (e.g. `rwlgt-iiaaa-aaaaa-aaaaa-cai`), in inputs with
+    /// a `canister` or `canister id` column
+    #[arg(long, global = true, value_name = "PRINCIPAL", value_parser = parse_principal)]
+    canister: Option<String>,
+
+    /// Sum the calls of all canisters with the same message, in inputs with a `canister` or
+    /// `canister id` column, to plot the whole subnet rather than the canisters
+    #[arg(long, global = true, conflicts_with = "canister")]
+    sum_canisters: bool,
+
+    /// Tool that generated the inputs. The columns of the inputs of other tools than drun are
+    /// mapped to the ones of drun, with the headers of the `[columns]` table of the configuration
+    /// taking precedence.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = inputs::InputFormat::Drun,
+        env = "DRUN_BENCH_INPUT_FORMAT"
+    )]
+    input_format: inputs::InputFormat,
+
+    /// Plot the per-call instructions and accessed and dirtied pages divided by the numbers of
+    /// execution rounds of the calls, in the `rounds` column of newer drun CSVs. Calls without
+    /// rounds are left out.
+    #[arg(long, global = true)]
+    per_round: bool,
+
+    /// What to do with the calls of the install/upgrade phase of the inputs: the ones of
+    /// `install`, `reinstall`, and `upgrade` messages, and the `setup_calls` of the inputs
+    /// (`--input PATH:setup_calls=1-3,50`)
+    #[arg(long, global = true, value_enum, default_value_t = inputs::SetupPhase::Keep)]
+    setup_phase: inputs::SetupPhase,
+
+    /// What to do when the inputs have different numbers of calls, which makes the totals
+    /// misleading to compare
+    #[arg(long, global = true, value_enum, default_value_t = inputs::LengthMismatch::Warn)]
+    length_mismatch: inputs::LengthMismatch,
+
+    /// What to do with rows of the inputs that aren't records of calls: comments, repeated
+    /// headers of concatenated runs, and rows with missing or non-numeric values
+    #[arg(long, global = true, value_enum, default_value_t = inputs::BadRows::Abort)]
+    bad_rows: inputs::BadRows,
+
+    /// Exit with an error on any anomaly of the inputs, e.g. rows that aren't records, calls left
+    /// out, or different numbers of calls, rather than warning about it. For CI.
+    #[arg(long, global = true, conflicts_with_all = ["lenient", "bad_rows"])]
+    strict: bool,
+
+    /// Leave out the rows of the inputs that aren't records (like `--bad-rows skip`), and
+    /// summarize the anomalies of the inputs when done rather than warning about each one
+    #[arg(long, global = true, conflicts_with = "bad_rows")]
+    lenient: bool,
+
+    /// Plot every numeric column of the inputs (selected by the `[discover]` table of the
+    /// configuration), with the running totals of the per-call ones, instead of the configured
+    /// or built-in plots
+    #[arg(long)]
+    all_columns: bool,
+
+    /// Generate the plots of this `[suite.<name>]` of the configuration file, in its directory
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["inputs", "input_specs", "all"])]
+    suite: Option<String>,
+
+    /// Generate the plots of every suite of the configuration file, and `index.org` linking to
+    /// them
+    #[arg(
+        long,
+        conflicts_with_all = ["inputs", "input_specs", "tag", "open", "export", "backend"]
+    )]
+    all: bool,
+
+    /// Keep running, generating the plots again when the inputs or the configuration file change.
+    /// Edits to the configuration (plots, labels, metrics) are picked up without restarting.
+    #[arg(long, conflicts_with_all = ["dry_run", "tag", "open", "all"])]
+    watch: bool,
+
+    /// Print the gnuplot programs instead of running gnuplot, with placeholders for the paths of
+    /// the generated data files. Every plot is printed, and nothing is cached or archived.
+    #[arg(long, conflicts_with_all = ["tag", "open"])]
+    dry_run: bool,
+
+    /// Names of the plots. Placeholders are `{plot}` (the plot name), `{ext}` (`png`), `{date}`
+    /// (today's date), and `{config}` (the labels of the inputs), e.g.
+    /// `{date}_{config}_{plot}.{ext}`.
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = plot::DEFAULT_OUTPUT_TEMPLATE,
+        env = "DRUN_BENCH_OUTPUT_TEMPLATE"
+    )]
+    output_template: String,
+
+    /// Directory to write the plots to, created when it doesn't exist. Defaults to the working
+    /// directory.
+    #[arg(long, value_name = "DIR", env = "DRUN_BENCH_OUTPUT_DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Upload the plots, thumbnails, reports, exports, and index to object storage after
+    /// generating them, e.g. `s3://bucket/prefix` or `gs://bucket/prefix`, and print their URLs.
+    /// Their keys are their paths relative to `--output-dir`. Uses the `aws` or `gsutil` command
+    /// line tool and its credentials.
+    #[cfg(feature = "upload")]
+    #[arg(
+        long,
+        value_name = "URL",
+        value_parser = upload::parse_destination,
+        conflicts_with_all = ["dry_run", "watch"],
+        env = "DRUN_BENCH_UPLOAD"
+    )]
+    upload: Option<upload::Destination>,
+
+    /// Overwrite plots, reports, and exports of earlier runs. Without it (or `--backup`) existing
+    /// ones that would change are an error, as their inputs may be gone.
+    #[arg(long, global = true, conflicts_with = "backup")]
+    force: bool,
+
+    /// Rename plots, reports, and exports of earlier runs that would be overwritten to
+    /// `<name>.bak` (or `<name>.bak.N`) instead
+    #[arg(long, global = true)]
+    backup: bool,
+
+    /// Also write a 300x240 thumbnail of each plot as `<plot name>_thumb.png`. Reports show the
+    /// thumbnails, linking to the plots.
+    #[arg(long)]
+    thumbnails: bool,
+
+    /// Open the plots in the default image viewer when done
+    #[arg(long)]
+    open: bool,
+
+    /// How to render the plots. With `matplotlib-script`, `plots.py` and the data files it reads
+    /// are written instead of the plots.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = plot::Backend::Gnuplot,
+        env = "DRUN_BENCH_BACKEND",
+        conflicts_with_all = ["dry_run", "tag", "open", "rolling_percentiles", "smooth", "pareto"]
+    )]
+    backend: plot::Backend,
+
+    /// Also export every plot in this format
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with = "dry_run",
+        env = "DRUN_BENCH_EXPORT"
+    )]
+    export: Option<export::Format>,
+
+    /// Also write a report with a table of the totals of each input and links to the plots
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with = "dry_run",
+        env = "DRUN_BENCH_REPORT_FORMAT"
+    )]
+    report_format: Option<report::Format>,
+
+    /// Also write the tables of the report, the inputs, and the plots to a single HTML file
+    /// without references to other files, e.g. to attach to tickets
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["dry_run", "all"])]
+    bundle: Option<PathBuf>,
+
+    /// Whether exported plots embed their data or refer to copies of the augmented inputs
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = export::Data::Inline,
+        requires = "export",
+        env = "DRUN_BENCH_EXPORT_DATA"
+    )]
+    export_data: export::Data,
+
+    #[command(subcommand)]
+    command: Option<Cmd>,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Write a starter configuration file with the CSVs in the working directory as inputs
+    Init {
+        /// Overwrite the configuration file when it exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Manage archived runs
+    Runs {
+        #[command(subcommand)]
+        command: RunsCmd,
+    },
+
+    /// Work with generated reports
+    Report {
+        #[command(subcommand)]
+        command: ReportCmd,
+    },
 
-    for (plot_name, column_idx) in PLOTS.iter() {
-        println!("{}", plot_name);
+    /// Check that the plots and reports were generated from the current inputs and
+    /// configuration, with the hashes recorded when they were generated, and print which of them
+    /// are stale. Takes the same options as generating them. Exits with an error when any are.
+    Verify {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`
+        inputs: Vec<String>,
+    },
 
-        // plot_defs output uses $COLUMN_IDX so replace $PLOTS before $COLUMN_IDX
-        let gnuplot = GNUPLOT_TEMPLATE
-            .replace("$PLOTS", &plot_defs(&files))
-            .replace("$COLUMN_IDX", &column_idx.to_string())
-            .replace("$YLABEL", &plot_name.replace("_", " "));
+    /// Publish archived runs
+    Site {
+        #[command(subcommand)]
+        command: SiteCmd,
+    },
 
-        let process = Command::new("gnuplot")
-            .arg("-p")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .expect("Unable to spawn gnuplot process");
+    /// Write an SVG badge per cumulative metric and input of an archived run, with the change of
+    /// its total from a baseline run, e.g. to embed in READMEs
+    Badge {
+        /// Tag of the run, the latest run by default
+        tag: Option<String>,
 
-        process
-            .stdin
-            .as_ref()
-            .unwrap()
-            .write_all(gnuplot.as_bytes())
-            .expect("Unable to write gnuplot file to gnuplot stdin");
+        /// Tag of the run to compare with, the latest baseline before the run by default
+        #[arg(long, env = "DRUN_BENCH_BASELINE")]
+        baseline: Option<String>,
 
-        let output = process.wait_with_output().expect("gnuplot failed");
+        /// Directory to write the badges to
+        #[arg(long, default_value = badge::DEFAULT_BADGE_DIR)]
+        dir: PathBuf,
+    },
 
-        std::fs::write(format!("{}.png", plot_name), output.stdout)
-            .expect("Unable to write gnuplot output to file");
+    /// Overlay the plots of two archived runs and print the differences in their totals
+    Compare {
+        /// Tags of the runs. With a single tag the run is compared with `--baseline`.
+        #[arg(required = true, num_args = 1..=2)]
+        tags: Vec<String>,
+
+        /// Tag of the run to compare with when only one tag is given
+        #[arg(long, env = "DRUN_BENCH_BASELINE")]
+        baseline: Option<String>,
+    },
+
+    /// Print the area under the cumulative curves of each input, and its ratio to the first
+    /// input's. Only the calls present in all inputs are included.
+    Auc {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`
+        inputs: Vec<String>,
+    },
+
+    /// Print every call at which a metric of two CSVs differs by more than a threshold
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+
+        /// Plot name of the metric, e.g. "instructions" or "total_dirtied_host_pages"
+        #[arg(long, default_value = "instructions")]
+        metric: String,
+
+        /// Relative difference, e.g. "10%" or "10"
+        #[arg(long, value_parser = parse_percentage, default_value = "10%")]
+        threshold: f64,
+    },
+
+    /// Print the first call at which each cumulative metric of each input differs from the first
+    /// input's by more than a threshold
+    Divergence {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`
+        inputs: Vec<String>,
+
+        /// Relative difference, e.g. "5%" or "5"
+        #[arg(long, value_parser = parse_percentage, default_value = "5%")]
+        threshold: f64,
+    },
+
+    /// Print statistics of each input grouped by the called method, and plot total instructions
+    /// by method as `methods_instructions.png`. Needs a "method" column in the inputs.
+    Methods {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`
+        inputs: Vec<String>,
+    },
+
+    /// Answer queries like `max(instructions)` or `sum(dirtied_host_pages where call > 500)`
+    /// read from standard input, one per line, for each input
+    Query {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`
+        inputs: Vec<String>,
+    },
+
+    /// Serve a dashboard of the inputs to browsers, with charts that are extended as rows are
+    /// appended to the inputs, e.g. while drun writes them. The archived runs are served as JSON
+    /// at `/runs`, and the values of a plot's column in a run, with their summary statistics, at
+    /// `/runs/<tag>/metrics/<plot>`. Edits to the configuration file are applied to the open
+    /// dashboards without a restart.
+    Serve {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`. They don't have to exist yet.
+        inputs: Vec<String>,
+
+        /// Address to listen on
+        #[arg(long, default_value = serve::DEFAULT_ADDR)]
+        addr: String,
+    },
+
+    /// Browse the plots of the inputs in the terminal
+    Tui {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`
+        inputs: Vec<String>,
+    },
+
+    /// Show the records of a CSV with the cumulative columns appended, in a pager when the
+    /// output is a terminal
+    Inspect {
+        file: PathBuf,
+
+        /// Print to standard output even when it's a terminal
+        #[arg(long)]
+        no_pager: bool,
+    },
+
+    /// Print the columns of a CSV with their 1-based indices (as used in the plots), inferred
+    /// types, and example values
+    Columns { file: PathBuf },
+
+    /// Print the correlation between the per-call metrics of each input
+    Correlation {
+        /// Input CSVs, as `PATH` or `PATH:LABEL`
+        inputs: Vec<String>,
+
+        #[arg(long, value_enum, default_value_t = correlation::Method::Pearson)]
+        method: correlation::Method,
+
+        /// Also render each matrix as a heatmap, `correlation_<label>.png`
+        #[arg(long)]
+        heatmap: bool,
+    },
+
+    /// Run a drun workload against each of several Wasm builds of a canister, and generate the
+    /// plots comparing them. The CSVs are kept as `canister_perf_<label>.csv` next to the plots.
+    Run {
+        /// Wasm modules, as `PATH` or `PATH:LABEL`
+        #[arg(required = true)]
+        wasms: Vec<String>,
+
+        /// drun message file, with `{wasm}` where the Wasm module is installed, e.g.
+        /// `install rwlgt-iiaaa-aaaaa-aaaaa-cai {wasm} ""`
+        #[arg(long, value_name = "FILE")]
+        workload: PathBuf,
+
+        /// drun program to run
+        #[arg(
+            long,
+            value_name = "PROGRAM",
+            default_value = "drun",
+            env = "DRUN_BENCH_DRUN"
+        )]
+        drun: PathBuf,
+
+        /// drun flags to run the workload with, separated by whitespace, optionally named as in
+        /// `incremental=--gc incremental`. Given several times, the workload is run with each
+        /// flag set, and the labels of the series are followed by the names of their flag sets.
+        #[arg(long, value_name = "[NAME=]FLAGS", allow_hyphen_values = true)]
+        flag_set: Vec<String>,
+
+        /// Name of the CSV that drun writes to its working directory
+        #[arg(long, value_name = "NAME", default_value = "canister_perf.csv")]
+        csv_name: String,
+
+        /// Regular expression of the lines of drun's standard error with the wall-clock times of
+        /// the messages, with the time (e.g. `1.5ms`) in a `time` group, and optionally the
+        /// message number in a `message` group. The times are added to the CSVs as a
+        /// "wall time" column, in nanoseconds, and plotted. Other lines are shown.
+        #[arg(long, value_name = "REGEX", default_value = matrix::DEFAULT_TIMING_PATTERN)]
+        timing_pattern: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum RunsCmd {
+    /// List archived runs with their total instructions and dirtied pages
+    List,
+
+    /// Mark a run as a baseline. Baselines are never deleted by `runs gc`.
+    Baseline {
+        tag: String,
+
+        /// Remove the baseline mark instead of adding it
+        #[arg(long)]
+        unset: bool,
+    },
+
+    /// Delete old runs. Runs marked as baselines are always kept.
+    Gc {
+        /// Keep this many of the most recent non-baseline runs
+        #[arg(long)]
+        keep_last: Option<usize>,
+
+        /// Delete non-baseline runs older than this, e.g. "30days" or "2weeks"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        max_age: Option<Duration>,
+
+        /// Only print the runs that would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Bundle a run's data, plots, and metadata into a zstd-compressed tarball
+    Export { tag: String, file: PathBuf },
+
+    /// Add a run exported with `runs export` to the archive
+    Import { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum ReportCmd {
+    /// Pair up the plots of two output directories by name, print which of them changed, and
+    /// render the ones that changed as `diff_<plot>.png`, showing both versions
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+
+        /// How the two versions of a plot are shown
+        #[arg(long, value_enum, default_value = "side-by-side")]
+        mode: report::DiffMode,
+    },
+}
+
+#[derive(Subcommand)]
+enum SiteCmd {
+    /// Write a static website of the archived runs: an index of the runs, a page per cumulative
+    /// metric with its totals across the runs, a page per run with its totals and plots, and an
+    /// Atom feed of the totals that grew from one run to the next, `feed.xml`
+    Build {
+        /// Directory to write the site to
+        #[arg(default_value = site::DEFAULT_SITE_DIR)]
+        dir: PathBuf,
+
+        /// Relative growth of a total from the previous run that is a regression, e.g. "5%" or "5"
+        #[arg(long, value_parser = parse_percentage, default_value = "5%")]
+        regression_threshold: f64,
+    },
+}
+
+fn main() {
+    let args = Args::try_parse().unwrap_or_else(|err| {
+        // Help and version requests are "errors" too, printed to standard output
+        if err.use_stderr() && errors::json_requested() {
+            errors::set_error_format(errors::ErrorFormat::Json);
+            let message = err.to_string();
+            errors::exit(errors::Category::Usage, None, None, message.trim());
+        }
+        err.exit()
+    });
+    errors::set_error_format(args.error_format);
+    logging::init(args.verbose, args.quiet);
+    plot::set_theme(args.theme);
+    plot::set_preset(args.preset);
+    plot::set_fonts(args.fonts.clone());
+    table::set_raw_numbers(args.raw_numbers);
+    plot::set_overwrite(if args.force {
+        plot::Overwrite::Force
+    } else if args.backup {
+        plot::Overwrite::Backup
+    } else {
+        plot::Overwrite::Refuse
+    });
+    let options = inputs::PipelineOptions {
+        bad_rows: if args.lenient {
+            inputs::BadRows::Skip
+        } else {
+            args.bad_rows
+        },
+        strictness: if args.strict {
+            inputs::Strictness::Strict
+        } else if args.lenient {
+            inputs::Strictness::Lenient
+        } else {
+            inputs::Strictness::Warn
+        },
+        canister: args.canister.clone(),
+        sum_canisters: args.sum_canisters,
+        rounds: match (&args.round_column, args.calls_per_round) {
+            (Some(header), _) => Some(inputs::Rounds::Column(header.clone())),
+            (None, Some(calls)) => Some(inputs::Rounds::Calls(calls as usize)),
+            (None, None) => None,
+        },
+        align_by: args.align_by.clone(),
+        setup_phase: args.setup_phase,
+        length_mismatch: args.length_mismatch,
+        trim: args.trim_outliers.map(|percentile| inputs::Trim {
+            percentile,
+            mode: args.trim_mode,
+        }),
+        input_format: args.input_format,
+        ..Default::default()
+    };
+    plot::set_row_name(options.row_name());
+
+    let config_path = args
+        .config
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(config::CONFIG_FILE));
+
+    if let Some(Cmd::Init { force }) = args.command {
+        config::init(&config_path, force);
+        return;
     }
 
-    std::mem::forget(files);
+    let config = config::load(&config_path, args.config.is_some(), args.profile.as_deref());
+    apply_config(&config);
+
+    match args.command {
+        Some(Cmd::Runs { command }) => match command {
+            RunsCmd::List => runs::list(),
+            RunsCmd::Baseline { tag, unset } => runs::set_baseline(&tag, !unset),
+            RunsCmd::Gc {
+                keep_last,
+                max_age,
+                dry_run,
+            } => runs::gc(keep_last, max_age, dry_run),
+            RunsCmd::Export { tag, file } => runs::export(&tag, &file),
+            RunsCmd::Import { file } => runs::import(&file),
+        },
+        Some(Cmd::Report { command }) => match command {
+            ReportCmd::Diff { a, b, mode } => report::diff(&a, &b, mode),
+        },
+        Some(Cmd::Site { command }) => match command {
+            SiteCmd::Build {
+                dir,
+                regression_threshold,
+            } => site::build(&dir, regression_threshold),
+        },
+        Some(Cmd::Serve { inputs, addr }) => {
+            let dashboard = move |config: &config::Config| {
+                serve::dashboard(&inputs::parse_inputs(&inputs, &[], &config.inputs), config)
+            };
+            let reload_path = config_path.clone();
+            let explicit = args.config.is_some();
+            let profile = args.profile.clone();
+            serve::serve(dashboard(&config), &addr, &config_path, move || {
+                reload_config(&reload_path, explicit, profile.as_deref())
+                    .map(|config| dashboard(&config))
+            })
+        }
+        Some(Cmd::Badge { tag, baseline, dir }) => {
+            badge::write_badges(tag.as_deref(), baseline.as_deref(), &dir)
+        }
+        Some(Cmd::Compare { tags, baseline }) => {
+            let (tag_a, tag_b) = match (&tags[..], baseline) {
+                ([tag_a, tag_b], _) => (tag_a.clone(), tag_b.clone()),
+                ([tag], Some(baseline)) => (baseline, tag.clone()),
+                _ => {
+                    errors::exit(
+                        errors::Category::Usage,
+                        None,
+                        None,
+                        "Give two tags, or one with --baseline or DRUN_BENCH_BASELINE",
+                    );
+                }
+            };
+            compare::compare(&tag_a, &tag_b, args.quiet)
+        }
+        Some(Cmd::Auc { inputs }) => auc::auc(&load_inputs(&inputs, &[], &config, &options)),
+        Some(Cmd::Diff {
+            a,
+            b,
+            metric,
+            threshold,
+        }) => diff::diff(&a, &b, &metric, threshold),
+        Some(Cmd::Divergence { inputs, threshold }) => {
+            divergence::divergence(&load_inputs(&inputs, &[], &config, &options), threshold)
+        }
+        Some(Cmd::Methods { inputs }) => {
+            methods::methods(&load_inputs(&inputs, &[], &config, &options))
+        }
+        Some(Cmd::Query { inputs }) => query::query(&load_inputs(&inputs, &[], &config, &options)),
+        Some(Cmd::Tui { inputs }) => tui::tui(&load_inputs(&inputs, &[], &config, &options)),
+        Some(Cmd::Inspect { file, no_pager }) => inspect::inspect(&file, !no_pager),
+        Some(Cmd::Columns { file }) => columns::columns(&file),
+        Some(Cmd::Correlation {
+            inputs,
+            method,
+            heatmap,
+        }) => correlation::correlation(
+            &load_inputs(&inputs, &[], &config, &options),
+            method,
+            heatmap,
+        ),
+        Some(Cmd::Run {
+            ref wasms,
+            ref workload,
+            ref drun,
+            ref flag_set,
+            ref csv_name,
+            ref timing_pattern,
+        }) => {
+            let suite = matrix::run(
+                wasms,
+                workload,
+                drun,
+                flag_set,
+                csv_name,
+                timing_pattern,
+                args.output_dir.as_deref(),
+            );
+            generate(&args, &config, &options, Some(("run", &suite)));
+        }
+        Some(Cmd::Verify { .. }) => {
+            if args.all {
+                generate_all(&args, &config, &options);
+            } else {
+                let suite = args
+                    .suite
+                    .as_deref()
+                    .map(|name| (name, find_suite(&config, name)));
+                generate(&args, &config, &options, suite);
+            }
+            let num_stale = cache::num_stale();
+            if num_stale != 0 {
+                errors::exit(
+                    errors::Category::Output,
+                    None,
+                    None,
+                    &format!(
+                        "{} outputs aren't up to date, generate them again",
+                        num_stale
+                    ),
+                );
+            }
+        }
+        Some(Cmd::Init { .. }) => unreachable!(),
+        None if args.all => generate_all(&args, &config, &options),
+        None if args.watch => watch(&args, config, &config_path, &options),
+        None => {
+            let suite = args
+                .suite
+                .as_deref()
+                .map(|name| (name, find_suite(&config, name)));
+            generate(&args, &config, &options, suite);
+        }
+    }
+
+    options.summarize_anomalies();
+    plot::stop_gnuplot();
 }
 
-// Given a canister perf CSV file path, write to a temporary path with a "total instructions",
-// "total accessed host pages", and "total dirtied host pages" columns.
-fn add_cumulative_columns(csv_path: &Path) -> NamedTempFile {
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .from_path(csv_path)
-        .unwrap();
+/// Use the metrics and plot labels of a configuration.
+fn apply_config(config: &config::Config) {
+    metrics::set_metrics(&config.metrics);
+    plot::set_label_overrides(
+        config
+            .plots
+            .iter()
+            .map(|plot| {
+                (
+                    plot.name.clone(),
+                    plot::PlotLabelOverrides {
+                        title: plot.title.clone(),
+                        xlabel: plot.xlabel.clone(),
+                        ylabel: plot.ylabel.clone(),
+                    },
+                )
+            })
+            .collect(),
+    );
+}
 
-    let mut headers = reader.headers().unwrap().to_owned();
+/// Load the edited configuration file again and apply it, see `apply_config`. When it's invalid
+/// the error is reported and `None` is returned, so that the previous configuration is kept.
+fn reload_config(
+    config_path: &Path,
+    explicit: bool,
+    profile: Option<&str>,
+) -> Option<config::Config> {
+    match config::try_load(config_path, explicit, profile) {
+        Ok(config) => {
+            log::info!("Reloaded {}", config_path.display());
+            apply_config(&config);
+            Some(config)
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            log::error!("Keeping the previous configuration");
+            None
+        }
+    }
+}
 
-    headers.push_field("total instructions");
-    headers.push_field("total accessed host pages");
-    headers.push_field("total dirtied host pages");
+/// How often `--watch` checks the inputs and the configuration file for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
 
-    let mut records: Vec<csv::StringRecord> = reader.into_records().map(|r| r.unwrap()).collect();
+/// Generate the plots, then again whenever the inputs or the configuration file change. Edits to
+/// the configuration are applied from the next generation on. When the edited configuration is
+/// invalid the error is reported and the previous configuration is kept.
+fn watch(
+    args: &Args,
+    mut config: config::Config,
+    config_path: &Path,
+    options: &inputs::PipelineOptions,
+) {
+    loop {
+        let suite = args
+            .suite
+            .as_deref()
+            .map(|name| (name, find_suite(&config, name)));
+        generate(args, &config, options, suite);
+        options.summarize_anomalies();
 
-    let mut total_instructions: u64 = 0;
-    let mut total_accessed_host_pages: u64 = 0;
-    let mut total_dirtied_host_pages: u64 = 0;
+        // The inputs as given, with their repetitions, before they're aggregated or mapped or
+        // rows are skipped
+        let input_paths: Vec<PathBuf> = match suite {
+            Some((_, suite)) => suite
+                .inputs
+                .iter()
+                .flat_map(|input| std::iter::once(&input.path).chain(&input.repetitions))
+                .cloned()
+                .collect(),
+            None => inputs::parse_inputs(&args.inputs, &args.input_specs, &config.inputs)
+                .into_iter()
+                .flat_map(|input| std::iter::once(input.path).chain(input.repetitions))
+                .collect(),
+        };
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let config_modified = modified(config_path);
+        let inputs_modified: Vec<_> = input_paths.iter().map(|path| modified(path)).collect();
 
-    for record in &mut records {
-        let instructions = record
-            .get(INSTRUCTIONS_COL_IDX - 1)
-            .unwrap()
-            .parse::<u64>()
-            .unwrap();
+        log::info!("Watching {} inputs for changes", input_paths.len());
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            if modified(config_path) != config_modified {
+                if let Some(new_config) =
+                    reload_config(config_path, args.config.is_some(), args.profile.as_deref())
+                {
+                    config = new_config;
+                }
+                break;
+            }
+            if input_paths
+                .iter()
+                .zip(&inputs_modified)
+                .any(|(path, input_modified)| modified(path) != *input_modified)
+            {
+                break;
+            }
+        }
+    }
+}
 
-        total_instructions += instructions;
+/// Parse a percentage like "5%" or "5" into a fraction (0.05).
+fn parse_percentage(s: &str) -> Result<f64, String> {
+    let percentage: f64 = s
+        .strip_suffix('%')
+        .unwrap_or(s)
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid percentage '{}'", s))?;
 
-        record.push_field(&total_instructions.to_string());
+    if percentage < 0.0 {
+        return Err(format!("percentage '{}' is negative", s));
+    }
 
-        let accessed_host_pages = record
-            .get(ACCESSED_HOST_PAGES_COL_IDX - 1)
-            .unwrap()
-            .parse::<u64>()
-            .unwrap();
+    Ok(percentage / 100.0)
+}
 
-        total_accessed_host_pages += accessed_host_pages;
+/// Parse the smoothing of `--smooth`, like "window=20" or "ema=0.1".
+fn parse_smoothing(s: &str) -> Result<rolling::Smoothing, String> {
+    match s.split_once('=') {
+        Some(("window", window)) => match window.parse() {
+            Ok(window) if window > 0 => Ok(rolling::Smoothing::Window(window)),
+            _ => Err(format!("invalid window '{}'", window)),
+        },
+        Some(("ema", alpha)) => match alpha.parse() {
+            Ok(alpha) if alpha > 0.0 && alpha <= 1.0 => Ok(rolling::Smoothing::Ema(alpha)),
+            _ => Err(format!(
+                "invalid alpha '{}', expected a number in (0, 1]",
+                alpha
+            )),
+        },
+        _ => Err(format!(
+            "invalid smoothing '{}', expected `window=CALLS` or `ema=ALPHA`",
+            s
+        )),
+    }
+}
 
-        record.push_field(&total_accessed_host_pages.to_string());
+/// Parse the percentile of `--trim-outliers`, like "99%" or "99", which must be between 50 and
+/// 100.
+fn parse_trim_percentile(s: &str) -> Result<f64, String> {
+    let percentile: f64 = s
+        .strip_suffix('%')
+        .unwrap_or(s)
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid percentile '{}'", s))?;
 
-        let dirtied_host_pages = record
-            .get(DIRTIED_HOST_PAGES_COL_IDX - 1)
-            .unwrap()
-            .parse::<u64>()
-            .unwrap();
+    if !(percentile > 50.0 && percentile < 100.0) {
+        return Err(format!("percentile '{}' is not between 50 and 100", s));
+    }
 
-        total_dirtied_host_pages += dirtied_host_pages;
+    Ok(percentile)
+}
 
-        record.push_field(&total_dirtied_host_pages.to_string());
+/// Check that a `--canister` is a principal in the textual format: groups of 5 (and a shorter
+/// last group) of lowercase base32 characters separated by `-`s.
+fn parse_principal(s: &str) -> Result<String, String> {
+    let groups: Vec<&str> = s.trim().split('-').collect();
+    let valid = groups.iter().enumerate().all(|(idx, group)| {
+        (if idx + 1 == groups.len() {
+            (1..=5).contains(&group.len())
+        } else {
+            group.len() == 5
+        }) && group.chars().all(|c| matches!(c, 'a'..='z' | '2'..='7'))
+    });
+    if valid {
+        Ok(s.trim().to_owned())
+    } else {
+        Err(format!(
+            "'{}' is not a principal, e.g. rwlgt-iiaaa-aaaaa-aaaaa-cai",
+            s
+        ))
     }
+}
 
-    let tmp_file = NamedTempFile::new().unwrap();
-    let mut csv_writer = csv::Writer::from_writer(tmp_file);
-    csv_writer.write_record(&headers).unwrap();
+/// Parse the inputs (see `inputs::parse_inputs`) and prepare them with `prepare_inputs`.
+fn load_inputs(
+    args: &[String],
+    specs: &[String],
+    config: &config::Config,
+    options: &inputs::PipelineOptions,
+) -> Vec<inputs::Input> {
+    prepare_inputs(
+        inputs::parse_inputs(args, specs, &config.inputs),
+        config,
+        options,
+    )
+}
+
+/// Check the headers and rows of the inputs, keep the calls of the `--canister` or sum the calls
+/// of all canisters, aggregate their repetitions, leave out their skipped rows, collapse them
+/// into rounds, join them on their keys, map their columns as configured, fill in their missing
+/// values, separate their install/upgrade calls, match their numbers of calls, and trim their
+/// outliers, as given by `options`.
+fn prepare_inputs(
+    inputs: Vec<inputs::Input>,
+    config: &config::Config,
+    options: &inputs::PipelineOptions,
+) -> Vec<inputs::Input> {
+    let columns = &config.columns;
+    inputs::check_headers(&inputs);
+    let inputs = inputs::clean_rows(inputs, columns, options);
+    let inputs = inputs::filter_canister(inputs, options);
+    let inputs = inputs::sum_canisters(inputs, columns, options);
+    let inputs = inputs::aggregate_repetitions(inputs, options);
+    let inputs = inputs::skip_rows(inputs, options);
+    let inputs = inputs::collapse_rounds(inputs, columns, options);
+    let inputs = inputs::align_by_key(inputs, options);
+    let inputs = inputs::map_columns(inputs, columns, options);
+    let inputs = inputs::fill_missing(inputs, options);
+    let inputs = inputs::separate_setup(inputs, options);
+    let inputs = inputs::match_row_counts(inputs, options);
+    inputs::trim_outliers(inputs, options)
+}
 
-    for record in records {
-        csv_writer.write_record(&record).unwrap();
+/// Plot the calls of the install/upgrade phase that `--setup-phase separate` takes out of the
+/// inputs, as `setup_<plot>.png`s of the plots of columns of the inputs. Returns the paths of the
+/// plots.
+fn plot_setup_calls(inputs: &[inputs::Input], plots: &[(&str, usize)]) -> Vec<PathBuf> {
+    let setup_inputs: Vec<(&inputs::Input, &Path)> = inputs
+        .iter()
+        .filter_map(|input| Some((input, input.setup_csv.as_ref()?.path())))
+        .collect();
+    if setup_inputs.is_empty() {
+        return vec![];
     }
 
-    csv_writer.into_inner().unwrap()
+    // The setup calls aren't augmented, so only the plots of their columns can be rendered
+    let num_columns = setup_inputs
+        .iter()
+        .map(|(_, path)| match csv::Reader::from_path(path) {
+            Ok(mut reader) => reader.headers().map_or(0, |headers| headers.len()),
+            Err(_) => 0,
+        })
+        .min()
+        .unwrap_or(0);
+    let setup_plots: Vec<(&str, usize)> = plots
+        .iter()
+        .copied()
+        .filter(|(_, column_idx)| *column_idx <= num_columns.min(inputs::DRUN_HEADERS.len()))
+        .collect();
+
+    let series: Vec<plot::Series> = setup_inputs
+        .iter()
+        .map(|(input, path)| plot::Series {
+            data: path,
+            label: &input.label,
+            style: &input.style,
+            offset: 0,
+            markers: &[],
+        })
+        .collect();
+    plot::render_plots(
+        &series,
+        &setup_plots,
+        "setup_",
+        &indicatif::ProgressBar::hidden(),
+    )
 }
 
-const FILES: [(&str, &str); 2] = [
-    ("canister_perf_copying_gc.csv", "Copying GC"),
-    ("canister_perf_compacting_gc.csv", "Compacting GC"),
-];
+/// The `[suite.<name>]` of the configuration. Exits with an error when it doesn't exist.
+fn find_suite<'a>(config: &'a config::Config, name: &str) -> &'a config::Suite {
+    match config.suites.get(name) {
+        Some(suite) => suite,
+        None => {
+            let names: Vec<&str> = config.suites.keys().map(String::as_str).collect();
+            errors::exit(
+                errors::Category::Config,
+                None,
+                None,
+                &format!(
+                    "No suite '{}' in the configuration, suites are: {}",
+                    name,
+                    if names.is_empty() {
+                        "(none)".to_owned()
+                    } else {
+                        names.join(", ")
+                    }
+                ),
+            );
+        }
+    }
+}
 
-fn plot_defs(files: &[(NamedTempFile, &'static str)]) -> String {
-    files
+/// Generate the plots of every suite, then an index of them in the output directory.
+fn generate_all(args: &Args, config: &config::Config, options: &inputs::PipelineOptions) {
+    if config.suites.is_empty() {
+        errors::exit(
+            errors::Category::Config,
+            None,
+            None,
+            "The configuration has no [suite.<name>] tables",
+        );
+    }
+
+    let suites: Vec<(&str, Vec<PathBuf>)> = config
+        .suites
         .iter()
-        .map(|(file, name)| {
-            format!(
-                r##""{}" using ($0+1):$COLUMN_IDX with linespoints title "{}", "##,
-                file.path().to_string_lossy(),
-                name,
+        .map(|(name, suite)| {
+            log::info!("Suite {}", name);
+            (
+                name.as_str(),
+                generate(args, config, options, Some((name, suite))),
             )
         })
-        .collect::<Vec<_>>()
-        .join(" ")
+        .collect();
+
+    if !args.dry_run && !verifying(args) {
+        let index_dir = args.output_dir.as_deref().unwrap_or(Path::new(""));
+        report::write_index(&index_dir.join(report::INDEX_FILE), &suites);
+        #[cfg(feature = "upload")]
+        upload_outputs(args, &[], &[index_dir.join(report::INDEX_FILE)]);
+    }
+}
+
+/// Whether the outputs are only checked, with `verify`
+fn verifying(args: &Args) -> bool {
+    matches!(args.command, Some(Cmd::Verify { .. }))
 }
 
-const GNUPLOT_TEMPLATE: &str = r###"
-set terminal png notransparent rounded giant font "JetBrains Mono" 24 \
-  size 1200,960 
+/// Upload plots (with their thumbnails) and other outputs with `--upload`, when given
+#[cfg(feature = "upload")]
+fn upload_outputs(args: &Args, plot_paths: &[PathBuf], outputs: &[PathBuf]) {
+    if let Some(destination) = &args.upload {
+        let paths: Vec<PathBuf> = plot_paths
+            .iter()
+            .flat_map(|path| [path.clone(), plot::thumbnail_path(path)])
+            .chain(outputs.iter().cloned())
+            .collect();
+        let dir = args.output_dir.as_deref().unwrap_or(Path::new(""));
+        upload::upload(destination, &paths, dir);
+    }
+}
+
+/// Generate the plots of the inputs, or of a suite. Returns the paths of all plots, including
+/// the ones that were up to date.
+fn generate(
+    args: &Args,
+    config: &config::Config,
+    options: &inputs::PipelineOptions,
+    suite: Option<(&str, &config::Suite)>,
+) -> Vec<PathBuf> {
+    let tag = args.tag.as_deref();
+
+    // Before loading the inputs, which can register placeholders
+    if args.dry_run {
+        plot::enable_dry_run();
+    }
+
+    let inputs = match suite {
+        Some((_, suite)) => prepare_inputs(
+            inputs::parse_inputs(&[], &[], &suite.inputs),
+            config,
+            options,
+        ),
+        None => match &args.command {
+            Some(Cmd::Verify { inputs }) if !inputs.is_empty() => {
+                load_inputs(inputs, &args.input_specs, config, options)
+            }
+            _ => load_inputs(&args.inputs, &args.input_specs, config, options),
+        },
+    };
+
+    if let Some(tag) = tag {
+        if tag.is_empty() || tag.contains(std::path::is_separator) || tag.starts_with('.') {
+            errors::exit(
+                errors::Category::Usage,
+                None,
+                None,
+                &format!("Invalid run tag: '{}'", tag),
+            );
+        }
+    }
+
+    if args.thumbnails {
+        plot::enable_thumbnails();
+    }
+
+    let config_name: Vec<String> = inputs
+        .iter()
+        .map(|input| plot::file_name_slug(&input.label))
+        .collect();
+    let output_dir = match suite {
+        Some((name, suite)) => Some(
+            args.output_dir
+                .clone()
+                .unwrap_or_default()
+                .join(suite.output_dir.as_deref().unwrap_or(Path::new(name))),
+        ),
+        None => args.output_dir.clone(),
+    };
+    if let Some(output_dir) = &output_dir {
+        if !args.dry_run && !verifying(args) {
+            std::fs::create_dir_all(output_dir).unwrap_or_else(|err| {
+                errors::exit(
+                    errors::Category::Output,
+                    Some(output_dir),
+                    None,
+                    &format!("Unable to create {}: {}", output_dir.display(), err),
+                );
+            });
+        }
+        plot::set_output_dir(output_dir);
+    }
+    if let Err(err) = plot::set_output_template(&args.output_template, &config_name.join("_vs_")) {
+        errors::exit(errors::Category::Usage, None, None, &err);
+    }
+
+    let mut cache = cache::Cache::load();
+
+    // Hash of the inputs of each plot: the input CSVs, their labels, and the plot definition.
+    // Archiving a run, exporting, reports, and other backends need the augmented CSVs and all
+    // plots, so the cache is not used then.
+    let input_hashes: Vec<String> = inputs
+        .iter()
+        .map(|input| cache::hash_file(&input.path))
+        .collect();
+
+    let rounds_col_idx = inputs::common_column(&inputs, inputs::ROUNDS_HEADER);
+    let time_col_idx = inputs::common_column(&inputs, matrix::TIME_HEADER);
+    if args.per_round && rounds_col_idx.is_none() {
+        errors::exit(
+            errors::Category::Usage,
+            None,
+            None,
+            &format!(
+                "--per-round needs a '{}' column in each of the inputs",
+                inputs::ROUNDS_HEADER
+            ),
+        );
+    }
+    plot::set_per_round(rounds_col_idx.filter(|_| args.per_round));
+
+    let discovered_plots: Vec<(String, usize)> = if args.all_columns {
+        config::plottable_columns(inputs.first().map(|input| &input.path), &config.discover)
+    } else {
+        vec![]
+    };
+    let mut plots: Vec<(&str, usize)> = if args.all_columns {
+        discovered_plots
+            .iter()
+            .map(|(plot_name, column_idx)| (plot_name.as_str(), *column_idx))
+            .collect()
+    } else if config.plots.is_empty() {
+        let mut plots = PLOTS.to_vec();
+        if let Some(rounds_col_idx) = rounds_col_idx {
+            plots.push(("rounds", rounds_col_idx));
+        }
+        if let Some(time_col_idx) = time_col_idx {
+            plots.push(("wall_time", time_col_idx));
+        }
+        plots
+    } else {
+        config
+            .plots
+            .iter()
+            .map(|plot| (plot.name.as_str(), plot.column))
+            .collect()
+    };
+    // Discovered columns that aren't totals, or the per-call values of the ones added by
+    // `augment`, get plots of their running totals
+    let discovered_total_plots: Vec<(String, usize)> = discovered_plots
+        .iter()
+        .filter(|(plot_name, column_idx)| {
+            !plot_name.to_lowercase().starts_with("total")
+                && TOTALS.iter().all(|(_, total_col_idx, source_col_idx)| {
+                    total_col_idx != column_idx && source_col_idx != column_idx
+                })
+        })
+        .map(|(plot_name, column_idx)| (format!("total_{}", plot_name), *column_idx))
+        .filter(|(total_plot_name, _)| {
+            !plots
+                .iter()
+                .any(|(plot_name, _)| plot_name == total_plot_name)
+        })
+        .collect();
+    for (total_plot_name, column_idx) in &discovered_total_plots {
+        metrics::add_cumulative_plot(total_plot_name);
+        plots.push((total_plot_name, *column_idx));
+    }
+    // The rounds and times get plots of their totals, like the other per-call columns
+    for (total_plot_name, col_idx) in [
+        ("total_rounds", rounds_col_idx),
+        ("total_wall_time", time_col_idx),
+    ] {
+        if let Some(col_idx) = col_idx {
+            if plots.iter().any(|(_, column_idx)| *column_idx == col_idx)
+                && !plots
+                    .iter()
+                    .any(|(plot_name, _)| *plot_name == total_plot_name)
+            {
+                metrics::add_cumulative_plot(total_plot_name);
+                plots.push((total_plot_name, col_idx));
+            }
+        }
+    }
+    let cumulative_plots = metrics::cumulative_plots(&plots);
+    let windowed_cumulative_plots = metrics::windowed_cumulative_plots(&plots);
+    let percent_of_total_plots = metrics::percent_of_total_plots(&plots);
+    plots.extend(
+        cumulative_plots
+            .iter()
+            .chain(&windowed_cumulative_plots)
+            .chain(&percent_of_total_plots)
+            .map(|(plot_name, column_idx)| (plot_name.as_str(), *column_idx)),
+    );
+
+    let mut stale_plots: Vec<(&str, usize)> = vec![];
+    let mut plot_hashes: Vec<String> = vec![];
+    // Of all plots, for the hashes of the reports
+    let mut all_plot_hashes: Vec<String> = vec![];
+
+    for (plot_name, column_idx) in plots.iter() {
+        let mut parts: Vec<&[u8]> = vec![plot::GNUPLOT_TEMPLATE.as_bytes(), plot_name.as_bytes()];
+        let column_idx_str = column_idx.to_string();
+        parts.push(column_idx_str.as_bytes());
+        let styles: Vec<String> = inputs
+            .iter()
+            .map(|input| {
+                format!(
+                    "{:?} {:?} {}",
+                    input.style.color, input.style.dash, input.alignment.offset
+                )
+            })
+            .collect();
+        for ((input, input_hash), style) in inputs.iter().zip(input_hashes.iter()).zip(&styles) {
+            parts.push(input.label.as_bytes());
+            parts.push(input_hash.as_bytes());
+            parts.push(style.as_bytes());
+        }
+        if args.gc_slices {
+            parts.push(b"gc slices");
+        }
+        if args.per_round {
+            parts.push(b"per round");
+        }
+        let setup_phase = args.setup_phase.to_possible_value().unwrap();
+        parts.push(setup_phase.get_name().as_bytes());
+        let theme = args.theme.to_possible_value().unwrap();
+        parts.push(theme.get_name().as_bytes());
+        let preset = args.preset.to_possible_value().unwrap();
+        parts.push(preset.get_name().as_bytes());
+        if args.thumbnails {
+            parts.push(b"thumbnails");
+        }
+        if !args.dry_run && plot::old_gnuplot() {
+            parts.push(b"old gnuplot");
+        }
+        let font = plot::font();
+        parts.push(font.as_bytes());
+        let labels = plot::plot_labels(plot_name, *column_idx);
+        let labels = format!("{:?} {} {}", labels.title, labels.xlabel, labels.ylabel);
+        parts.push(labels.as_bytes());
+        let metric = format!(
+            "{:?} {} {:?} {}",
+            metrics::metric(*column_idx),
+            metrics::is_cumulative_plot(plot_name),
+            metrics::cumulative_window(plot_name),
+            metrics::is_percent_of_total_plot(plot_name)
+        );
+        parts.push(metric.as_bytes());
+        let hash = cache::hash_parts(&parts);
+        all_plot_hashes.push(hash.clone());
+        if verifying(args) {
+            continue;
+        }
+
+        if !args.no_cache
+            && !args.dry_run
+            && tag.is_none()
+            && args.export.is_none()
+            && args.report_format.is_none()
+            && args.bundle.is_none()
+            && args.backend == plot::Backend::Gnuplot
+            && cache.is_up_to_date(&plot::plot_path(plot_name, ""), &hash)
+        {
+            log::info!("{}: up to date", plot_name);
+            continue;
+        }
+
+        log::debug!("{}: column {}, hash {}", plot_name, column_idx, hash);
+        stale_plots.push((plot_name, *column_idx));
+        plot_hashes.push(hash);
+    }
+
+    // Reports are generated from the plots, and their totals from the same inputs
+    let report_hash = |kind: &str| {
+        let mut parts: Vec<&[u8]> = vec![kind.as_bytes()];
+        parts.extend(all_plot_hashes.iter().map(|hash| hash.as_bytes()));
+        cache::hash_parts(&parts)
+    };
+    let report_paths: Vec<PathBuf> = [report::Format::Org, report::Format::Html]
+        .iter()
+        .map(|format| {
+            output_dir
+                .as_deref()
+                .unwrap_or(Path::new(""))
+                .join(format.file_name())
+        })
+        .collect();
+
+    if verifying(args) {
+        let mut outputs: Vec<(PathBuf, String)> = plots
+            .iter()
+            .map(|(plot_name, _)| plot::plot_path(plot_name, ""))
+            .zip(all_plot_hashes.iter().cloned())
+            .collect();
+        for report_path in report_paths.iter().filter(|path| path.exists()) {
+            outputs.push((report_path.clone(), report_hash("report")));
+        }
+        if let Some(bundle) = &args.bundle {
+            outputs.push((bundle.clone(), report_hash("bundle")));
+        }
+        cache.verify(&outputs);
+        return outputs.into_iter().map(|(path, _)| path).collect();
+    }
+
+    // Plots that are up to date are opened too, they are as much a part of the result
+    let mut plots_to_open: Vec<PathBuf> = plots
+        .iter()
+        .map(|(plot_name, _)| plot::plot_path(plot_name, ""))
+        .collect();
+
+    let ratios = ratios::ratios(config);
+    let stacks = stacks::stacks(config);
+
+    // These don't need the augmented inputs, so they are rendered while the inputs are augmented
+    let render_extra_plots = || {
+        let mut plot_paths: Vec<PathBuf> = vec![];
+        if let Some(window) = args.rolling_percentiles {
+            plot_paths.push(rolling::plot_rolling_percentiles(&inputs, window));
+        }
+        if let Some(smoothing) = args.smooth {
+            plot_paths.push(rolling::plot_smoothed(&inputs, smoothing));
+        }
+        if args.pareto {
+            plot_paths.extend(pareto::plot_pareto(&inputs));
+        }
+        if args.backend == plot::Backend::Gnuplot {
+            plot_paths.extend(ratios::plot_ratios(&inputs, &ratios));
+            plot_paths.extend(stacks::plot_stacks(&inputs, &stacks));
+            plot_paths.extend(matrix::plot_usage());
+        }
+        plot_paths.extend(plot_setup_calls(&inputs, &plots));
+        plot_paths
+    };
+
+    if stale_plots.is_empty() {
+        plots_to_open.extend(render_extra_plots());
+        if args.open {
+            plot::open_in_viewer(&plots_to_open);
+        }
+        #[cfg(feature = "upload")]
+        upload_outputs(args, &plots_to_open, &[]);
+        return plots_to_open;
+    }
+
+    let gc_slices: Vec<Vec<gc_slices::GcSlice>> = if args.gc_slices {
+        let gc_slices: Vec<Vec<gc_slices::GcSlice>> = inputs
+            .iter()
+            .map(|input| gc_slices::detect(&input.path))
+            .collect();
+        gc_slices::print_summary(&inputs, &gc_slices);
+        gc_slices
+    } else {
+        inputs.iter().map(|_| vec![]).collect()
+    };
+
+    let markers: Vec<Vec<usize>> = gc_slices
+        .iter()
+        .zip(&inputs)
+        .map(|(slices, input)| {
+            let mut markers: Vec<usize> = slices.iter().map(|slice| slice.call_idx).collect();
+            if args.setup_phase == inputs::SetupPhase::Mark {
+                markers.extend(&input.setup_calls);
+            }
+            markers
+        })
+        .collect();
 
-set xtics nomirror
-set ytics nomirror
+    // Plots of columns that are in all inputs don't need the augmented inputs, so they are
+    // rendered from the inputs while the rest wait for the augmentation
+    let num_input_columns = inputs
+        .iter()
+        .map(|input| match csv::Reader::from_path(&input.path) {
+            Ok(mut reader) => reader.headers().map_or(0, |headers| headers.len()),
+            Err(_) => 0,
+        })
+        .min()
+        .unwrap_or(0);
+    // Other backends render the plots from the augmented inputs once they're all done
+    let rendered_plots: &[(&str, usize)] = if args.backend == plot::Backend::Gnuplot {
+        &stale_plots
+    } else {
+        &[]
+    };
+    let (early_plots, late_plots): (Vec<_>, Vec<_>) =
+        rendered_plots.iter().copied().partition(|(_, column_idx)| {
+            // The rounds that per-call values are divided by are at another index in the inputs
+            !args.per_round && *column_idx <= num_input_columns.min(inputs::DRUN_HEADERS.len())
+        });
 
-set style line 80 lt 0 lc rgb "#808080"
+    let input_series: Vec<plot::Series> = inputs
+        .iter()
+        .zip(markers.iter())
+        .map(|(input, markers)| plot::Series {
+            data: &input.path,
+            label: &input.label,
+            style: &input.style,
+            offset: input.alignment.offset,
+            markers,
+        })
+        .collect();
 
-set border 3 back ls 80 
+    let input_bytes: u64 = inputs
+        .iter()
+        .map(|input| std::fs::metadata(&input.path).map_or(0, |metadata| metadata.len()))
+        .sum();
+    let (bytes_progress, plots_progress) = progress::bars(
+        input_bytes,
+        rendered_plots.len() as u64,
+        // The programs printed in dry-run mode would be garbled by the bars
+        args.quiet || args.dry_run,
+    );
+
+    let stage_start = Instant::now();
+    let (augmented, plot_paths, extra_plots) = std::thread::scope(|scope| {
+        let extra_plots = scope.spawn(render_extra_plots);
+        let early_plot_paths =
+            scope.spawn(|| plot::render_plots(&input_series, &early_plots, "", &plots_progress));
 
-set style line 81 lt 0 lc rgb "#808080" lw 0.5
+        // Inputs are independent, so augment them in parallel, one thread per input
+        let threads: Vec<_> = inputs
+            .iter()
+            .map(|input| {
+                let progress = &bytes_progress;
+                scope.spawn(move || {
+                    // Preprocessed inputs are temporary copies, which are new in every run
+                    if args.incremental && input.path != input.original {
+                        log::warn!(
+                            "{} is preprocessed, so it's augmented from scratch despite \
+                             --incremental",
+                            input.original.display()
+                        );
+                    }
+                    if args.incremental && input.path == input.original {
+                        let path = augment::add_cumulative_columns_incremental(&input.path);
+                        progress.inc(std::fs::metadata(&input.path).unwrap().len());
+                        (path, None)
+                    } else if args.columnar_cache {
+                        let tmp = columnar::add_cumulative_columns_cached(
+                            &input.path,
+                            progress,
+                            args.mmap,
+                        );
+                        (tmp.path().to_owned(), Some(tmp))
+                    } else {
+                        let tmp = augment::add_cumulative_columns_with_progress(
+                            &input.path,
+                            progress,
+                            args.mmap,
+                        );
+                        (tmp.path().to_owned(), Some(tmp))
+                    }
+                })
+            })
+            .collect();
 
-set grid xtics
-set grid ytics
-set grid mxtics
-set grid mytics
+        let augmented: Vec<(PathBuf, Option<NamedTempFile>)> = threads
+            .into_iter()
+            .map(|thread| thread.join().expect("Augmenting an input failed"))
+            .collect();
+        bytes_progress.finish_and_clear();
+        log::debug!("Augmenting inputs took {:?}", stage_start.elapsed());
 
-set grid back ls 81
+        for (input, (path, _)) in inputs.iter().zip(augmented.iter()) {
+            log::debug!("{}: augmented as {}", input.path.display(), path.display());
+            plot::set_dry_run_placeholder(path, &format!("<augmented {}>", input.path.display()));
+        }
 
-set style line 1 lt 1 lc rgb "#A00000" lw 2 pt 7 ps 1.5
-set style line 2 lt 1 lc rgb "#00A000" lw 2 pt 11 ps 1.5
-set style line 3 lt 1 lc rgb "#5060D0" lw 2 pt 9 ps 1.5
-set style line 4 lt 1 lc rgb "#0000A0" lw 2 pt 8 ps 1.5
-set style line 5 lt 1 lc rgb "#D0D000" lw 2 pt 13 ps 1.5
-set style line 6 lt 1 lc rgb "#00D0D0" lw 2 pt 12 ps 1.5
-set style line 7 lt 1 lc rgb "#B200B2" lw 2 pt 5 ps 1.5
+        let series: Vec<plot::Series> = augmented
+            .iter()
+            .zip(inputs.iter())
+            .zip(markers.iter())
+            .map(|(((path, _), input), markers)| plot::Series {
+                data: path,
+                label: &input.label,
+                style: &input.style,
+                offset: input.alignment.offset,
+                markers,
+            })
+            .collect();
 
-set datafile separator ','
+        let mut plot_paths = plot::render_plots(&series, &late_plots, "", &plots_progress);
+        plot_paths.extend(early_plot_paths.join().expect("Rendering plots failed"));
 
-set xlabel "call"
-set ylabel "$YLABEL"
+        (
+            augmented,
+            plot_paths,
+            extra_plots.join().expect("Rendering plots failed"),
+        )
+    });
+    plots_progress.finish_and_clear();
+    log::debug!("Rendering plots took {:?}", stage_start.elapsed());
+    plots_to_open.extend(extra_plots);
 
-set xrange [0:100]
+    let (augmented, tmp_files): (Vec<PathBuf>, Vec<Option<NamedTempFile>>) =
+        augmented.into_iter().unzip();
 
-plot $PLOTS
-"###;
+    let series: Vec<plot::Series> = augmented
+        .iter()
+        .zip(inputs.iter())
+        .zip(markers.iter())
+        .map(|((path, input), markers)| plot::Series {
+            data: path,
+            label: &input.label,
+            style: &input.style,
+            offset: input.alignment.offset,
+            markers,
+        })
+        .collect();
+
+    if args.dry_run {
+        return plots_to_open;
+    }
+
+    if args.backend == plot::Backend::MatplotlibScript {
+        export::write_matplotlib_script(&series, &stale_plots);
+    } else {
+        for ((plot_name, _), hash) in stale_plots.iter().zip(plot_hashes) {
+            cache.insert(plot::plot_path(plot_name, ""), hash);
+        }
+        cache.save();
+    }
+
+    if let Some(tag) = tag {
+        let input_paths: Vec<&Path> = inputs
+            .iter()
+            .map(|input| input.original.as_path())
+            .collect();
+        runs::archive(tag, &input_paths, &series, &plot_paths);
+    }
+
+    // Written besides the plots
+    let mut outputs: Vec<PathBuf> = vec![];
+
+    if let Some(format) = args.export {
+        outputs.extend(export::export(format, args.export_data, &series, &plots));
+    }
+
+    if let Some(format) = args.report_format {
+        let (path, data_paths) = report::write_report(
+            format,
+            &series,
+            &inputs,
+            options,
+            &plots_to_open,
+            output_dir.as_deref(),
+        );
+        cache.insert(path.clone(), report_hash("report"));
+        outputs.push(path);
+        outputs.extend(data_paths);
+    }
+
+    if let Some(path) = &args.bundle {
+        report::write_bundle(path, &series, &inputs, options, &plots_to_open);
+        cache.insert(path.clone(), report_hash("bundle"));
+        outputs.push(path.clone());
+    }
+    if args.report_format.is_some() || args.bundle.is_some() {
+        cache.save();
+    }
+
+    #[cfg(feature = "upload")]
+    upload_outputs(args, &plots_to_open, &outputs);
+
+    if args.open {
+        plot::open_in_viewer(&plots_to_open);
+    }
+
+    // Deleting the temporary files takes a while for large inputs, so they're left for the system
+    // to clean up, except with `--watch` where they'd pile up
+    if args.watch {
+        drop(tmp_files);
+    } else {
+        std::mem::forget(tmp_files);
+    }
+    plots_to_open
+}
+
+/// Inputs used when none are given on the command line
+const FILES: [(&str, &str); 2] = [
+    ("canister_perf_copying_gc.csv", "Copying GC"),
+    ("canister_perf_compacting_gc.csv", "Compacting GC"),
+];
 
 /// 1-based index of "instructions" column in drun generated CSVs
 const INSTRUCTIONS_COL_IDX: usize = 3;
@@ -175,14 +1760,46 @@ const ACCESSED_HOST_PAGES_COL_IDX: usize = 4;
 /// 1-based index of "dirtied host pages" column in drun generated CSVs
 const DIRTIED_HOST_PAGES_COL_IDX: usize = 5;
 
+/// 1-based index of "total Wasm pages in use" column in drun generated CSVs
+const WASM_PAGES_COL_IDX: usize = 6;
+
+/// 1-based index of the appended "total instructions" column in augmented CSVs
+const INSTRUCTIONS_TOTAL_COL_IDX: usize = 7;
+
+/// 1-based index of the appended "total accessed host pages" column in augmented CSVs
+const ACCESSED_TOTAL_COL_IDX: usize = 8;
+
+/// 1-based index of the appended "total dirtied host pages" column in augmented CSVs
+const DIRTIED_TOTAL_COL_IDX: usize = 9;
+
+/// Cumulative metrics, as names, 1-based indices of the total columns, and
+/// 1-based indices of the per-call columns the totals are computed from
+const TOTALS: [(&str, usize, usize); 3] = [
+    (
+        "total instructions",
+        INSTRUCTIONS_TOTAL_COL_IDX,
+        INSTRUCTIONS_COL_IDX,
+    ),
+    (
+        "total accessed host pages",
+        ACCESSED_TOTAL_COL_IDX,
+        ACCESSED_HOST_PAGES_COL_IDX,
+    ),
+    (
+        "total dirtied host pages",
+        DIRTIED_TOTAL_COL_IDX,
+        DIRTIED_HOST_PAGES_COL_IDX,
+    ),
+];
+
 /// 1-based column indices and names of plots. Note that column indices are for gnuplot, i.e. they
 /// start from 1. Make sure to run `add_cumulative_fields` before using this.
 const PLOTS: [(&str, usize); 7] = [
     ("instructions", INSTRUCTIONS_COL_IDX),
     ("accessed_host_pages", ACCESSED_HOST_PAGES_COL_IDX),
     ("dirtied_host_pages", DIRTIED_HOST_PAGES_COL_IDX),
-    ("total_Wasm_pages_in_use", 6),
-    ("total_instructions", 7),
-    ("total_accessed_host_pages", 8),
-    ("total_dirtied_host_pages", 9),
+    ("total_Wasm_pages_in_use", WASM_PAGES_COL_IDX),
+    ("total_instructions", INSTRUCTIONS_TOTAL_COL_IDX),
+    ("total_accessed_host_pages", ACCESSED_TOTAL_COL_IDX),
+    ("total_dirtied_host_pages", DIRTIED_TOTAL_COL_IDX),
 ];
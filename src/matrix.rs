@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use tempfile::NamedTempFile;
+
+use crate::config::{InputConfig, Suite};
+use crate::errors;
+use crate::inputs;
+use crate::plot;
+use crate::table::{localize, print_table};
+
+/// Placeholder of the Wasm module in `run` workloads
+const WASM_PLACEHOLDER: &str = "{wasm}";
+
+/// Default `--timing-pattern`: lines like `message 12 took 1.5ms`
+pub const DEFAULT_TIMING_PATTERN: &str =
+    r"(?i)message\s+(?P<message>\d+)\b.*?(?P<time>\d+(?:\.\d+)?\s*(?:ns|us|µs|ms|s))\b";
+
+/// Header of the column of wall-clock times of the calls, in nanoseconds, that `run` adds to the
+/// CSVs of drun
+pub const TIME_HEADER: &str = "wall time";
+
+/// drun flags that `run` runs the workload with, see `parse_flag_set`
+struct FlagSet {
+    /// Shown in the labels of the series when there are several flag sets
+    name: String,
+    flags: Vec<String>,
+}
+
+/// Resources used by a drun process
+#[derive(Clone, Copy)]
+pub struct Usage {
+    pub wall_time: Duration,
+    /// In bytes, `None` when the system doesn't report it
+    pub peak_rss: Option<u64>,
+}
+
+impl Usage {
+    /// The wall time in seconds, e.g. `12.35 s`
+    pub fn format_wall_time(&self) -> String {
+        format!(
+            "{} s",
+            localize(&format!("{:.2}", self.wall_time.as_secs_f64()))
+        )
+    }
+
+    /// The peak RSS in MiB, e.g. `120.4 MiB`
+    pub fn format_peak_rss(&self) -> String {
+        match self.peak_rss {
+            Some(peak_rss) => format!("{} MiB", localize(&format!("{:.1}", mib(peak_rss)))),
+            None => "-".to_owned(),
+        }
+    }
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Resources used by the drun processes of `run`, by the labels of their inputs
+static USAGE: Mutex<Vec<(String, Usage)>> = Mutex::new(vec![]);
+
+/// Resources used by the drun process of the input with the label, when `run` produced it
+pub fn usage(label: &str) -> Option<Usage> {
+    USAGE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(usage_label, _)| usage_label == label)
+        .map(|(_, usage)| *usage)
+}
+
+/// Parse a `--flag-set`: flags separated by whitespace, optionally after a name and a `=`, as in
+/// `incremental=--gc incremental`. Flag sets without a name are named after their flags.
+fn parse_flag_set(flag_set: &str) -> FlagSet {
+    let (name, flags) = match flag_set.split_once('=') {
+        Some((name, flags)) if !name.starts_with('-') && !name.contains(char::is_whitespace) => {
+            (Some(name.trim()), flags)
+        }
+        _ => (None, flag_set),
+    };
+    let flags: Vec<String> = flags.split_whitespace().map(str::to_owned).collect();
+    FlagSet {
+        name: match name {
+            Some(name) => name.to_owned(),
+            None if flags.is_empty() => "default flags".to_owned(),
+            None => flags.join(" "),
+        },
+        flags,
+    }
+}
+
+/// Run a drun workload (a message file with `WASM_PLACEHOLDER`s) against each of the Wasm
+/// modules (`PATH` or `PATH:LABEL` arguments) with each of the `flag_sets` (see
+/// `parse_flag_set`), in a temporary working directory each. The CSV named `csv_name` that drun
+/// writes there is kept as `canister_perf_<label>.csv` in `dir`, or in the working directory,
+/// with the times of the calls that drun prints to standard error in lines matching
+/// `timing_pattern` as a `TIME_HEADER` column, see `add_times`. With several flag sets the labels
+/// are followed by the names of the flag sets. The flags are the descriptions of the inputs.
+/// The wall time and peak RSS of each drun process are printed and kept for `usage` and
+/// `plot_usage`. Returns a suite with the CSVs as inputs, to generate the plots of.
+pub fn run(
+    wasms: &[String],
+    workload: &Path,
+    drun: &Path,
+    flag_sets: &[String],
+    csv_name: &str,
+    timing_pattern: &str,
+    dir: Option<&Path>,
+) -> Suite {
+    let timing_pattern = match Regex::new(timing_pattern) {
+        Ok(regex) if regex.capture_names().any(|name| name == Some("time")) => regex,
+        Ok(_) => errors::exit(
+            errors::Category::Usage,
+            None,
+            None,
+            "--timing-pattern needs a `(?P<time>...)` group",
+        ),
+        Err(err) => errors::exit(
+            errors::Category::Usage,
+            None,
+            None,
+            &format!("Invalid --timing-pattern: {}", err),
+        ),
+    };
+
+    let messages = std::fs::read_to_string(workload).unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Input,
+            Some(workload),
+            None,
+            &format!("Unable to read {}: {}", workload.display(), err),
+        )
+    });
+    if !messages.contains(WASM_PLACEHOLDER) {
+        errors::exit(
+            errors::Category::Input,
+            Some(workload),
+            None,
+            &format!(
+                "{} doesn't install the Wasm modules, e.g. with `install <canister id> {} \"\"`",
+                workload.display(),
+                WASM_PLACEHOLDER
+            ),
+        );
+    }
+    let dir = dir.unwrap_or(Path::new(""));
+
+    let flag_sets: Vec<FlagSet> = if flag_sets.is_empty() {
+        vec![parse_flag_set("")]
+    } else {
+        flag_sets
+            .iter()
+            .map(|flag_set| parse_flag_set(flag_set))
+            .collect()
+    };
+    let wasms = inputs::parse_inputs(wasms, &[], &[]);
+    let runs: Vec<(&inputs::Input, &FlagSet)> = wasms
+        .iter()
+        .flat_map(|wasm| flag_sets.iter().map(move |flag_set| (wasm, flag_set)))
+        .collect();
+
+    let inputs: Vec<InputConfig> = runs
+        .into_iter()
+        .map(|(wasm, flag_set)| {
+            let label = if flag_sets.len() == 1 {
+                wasm.label.clone()
+            } else {
+                format!("{}, {}", wasm.label, flag_set.name)
+            };
+            let wasm_path = std::fs::canonicalize(&wasm.path).unwrap_or_else(|err| {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(&wasm.path),
+                    None,
+                    &format!("Unable to read {}: {}", wasm.path.display(), err),
+                )
+            });
+            log::info!(
+                "Running {} with {}{}",
+                workload.display(),
+                wasm.path.display(),
+                if flag_set.flags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" and {}", flag_set.flags.join(" "))
+                }
+            );
+
+            let work_dir = tempfile::tempdir().expect("Unable to create drun working directory");
+            let messages_path = work_dir.path().join("workload");
+            std::fs::write(
+                &messages_path,
+                messages.replace(WASM_PLACEHOLDER, &wasm_path.to_string_lossy()),
+            )
+            .expect("Unable to write drun workload");
+
+            let started = Instant::now();
+            let Exited {
+                status,
+                stdout,
+                stderr,
+                peak_rss,
+            } = Command::new(drun)
+                .args(&flag_set.flags)
+                .arg(&messages_path)
+                .current_dir(work_dir.path())
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .and_then(wait_with_output)
+                .unwrap_or_else(|err| {
+                    errors::exit(
+                        errors::Category::Usage,
+                        None,
+                        None,
+                        &format!("Unable to run {}: {}", drun.display(), err),
+                    )
+                });
+            let usage = Usage {
+                wall_time: started.elapsed(),
+                peak_rss,
+            };
+            log::info!(
+                "drun ran for {}, peak RSS {}",
+                usage.format_wall_time(),
+                usage.format_peak_rss()
+            );
+            USAGE.lock().unwrap().push((label.clone(), usage));
+
+            // drun prints the responses to the messages, which are only interesting when
+            // debugging the workload
+            log::debug!("drun output:\n{}", String::from_utf8_lossy(&stdout));
+            let mut times: Vec<(Option<String>, u64)> = vec![];
+            for line in String::from_utf8_lossy(&stderr).lines() {
+                match timing_pattern.captures(line).and_then(|captures| {
+                    Some((captures.name("message"), parse_time(&captures["time"])?))
+                }) {
+                    Some((message, time)) => {
+                        times.push((message.map(|message| message.as_str().to_owned()), time))
+                    }
+                    None => eprintln!("{}", line),
+                }
+            }
+            if !status.success() {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(&wasm.path),
+                    None,
+                    &format!(
+                        "drun failed on {} with {} ({})",
+                        workload.display(),
+                        wasm.path.display(),
+                        status
+                    ),
+                );
+            }
+
+            let csv = std::fs::read(work_dir.path().join(csv_name)).unwrap_or_else(|err| {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(&wasm.path),
+                    None,
+                    &format!(
+                        "drun didn't write {} with {} ({}), see --csv-name",
+                        csv_name,
+                        wasm.path.display(),
+                        err
+                    ),
+                )
+            });
+            let path: PathBuf = dir.join(format!(
+                "canister_perf_{}.csv",
+                plot::file_name_slug(&label).to_lowercase()
+            ));
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir).expect("Unable to create output directory");
+            }
+            let csv = if times.is_empty() {
+                log::debug!("{}: no timing lines", wasm.path.display());
+                csv
+            } else {
+                add_times(&csv, &times, &wasm.path)
+            };
+            plot::write_output(&path, &csv).expect("Unable to write drun CSV");
+            log::info!("Wrote {}", path.display());
+
+            InputConfig {
+                path,
+                label: Some(label),
+                color: None,
+                dash: None,
+                skip_rows: 0,
+                offset: 0,
+                repetitions: vec![],
+                aggregate: inputs::Aggregate::default(),
+                setup_calls: None,
+                description: if flag_set.flags.is_empty() {
+                    None
+                } else {
+                    Some(format!("drun {}", flag_set.flags.join(" ")))
+                },
+            }
+        })
+        .collect();
+
+    let rows: Vec<Vec<String>> = USAGE
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(label, usage)| {
+            vec![
+                label.clone(),
+                usage.format_wall_time(),
+                usage.format_peak_rss(),
+            ]
+        })
+        .collect();
+    print_table(&["input", "drun wall time", "drun peak RSS"], &rows);
+
+    Suite {
+        inputs,
+        // The plots go to `--output-dir`, next to the CSVs
+        output_dir: Some(PathBuf::new()),
+    }
+}
+
+/// A process that exited, see `wait_with_output`
+struct Exited {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    /// In bytes, `None` when the system doesn't report it
+    peak_rss: Option<u64>,
+}
+
+/// Wait for a child with piped standard output and error to exit, reading its outputs. The peak
+/// RSS is the `ru_maxrss` of the `wait4` of the child, as `Child::wait` doesn't return resource
+/// usage.
+fn wait_with_output(mut child: Child) -> std::io::Result<Exited> {
+    // Read standard output in another thread, so that the child doesn't block on either pipe
+    let mut stdout_pipe = child.stdout.take().unwrap();
+    let stdout_reader = std::thread::spawn(move || {
+        let mut stdout = vec![];
+        stdout_pipe.read_to_end(&mut stdout).map(|_| stdout)
+    });
+    let mut stderr = vec![];
+    child.stderr.take().unwrap().read_to_end(&mut stderr)?;
+    let stdout = stdout_reader.join().unwrap()?;
+    let (status, peak_rss) = wait(child)?;
+    Ok(Exited {
+        status,
+        stdout,
+        stderr,
+        peak_rss,
+    })
+}
+
+#[cfg(unix)]
+fn wait(child: Child) -> std::io::Result<(ExitStatus, Option<u64>)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: rusage is plain data, and wait4 only writes to the two pointers
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let pid = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut rusage) };
+        if pid != -1 {
+            break;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    // Kilobytes, except on macOS where it's bytes
+    let peak_rss = rusage.ru_maxrss as u64;
+    let peak_rss = if cfg!(target_os = "macos") {
+        peak_rss
+    } else {
+        peak_rss * 1024
+    };
+    Ok((ExitStatus::from_raw(status), Some(peak_rss)))
+}
+
+#[cfg(not(unix))]
+fn wait(mut child: Child) -> std::io::Result<(ExitStatus, Option<u64>)> {
+    Ok((child.wait()?, None))
+}
+
+/// Plot the wall times and peak RSSs of the drun processes of `run` as bar charts with a bar per
+/// input, as `drun_wall_time.png` and `drun_peak_rss.png`. Returns the paths of the plots, none
+/// when `run` didn't run drun.
+pub fn plot_usage() -> Vec<PathBuf> {
+    let usage = USAGE.lock().unwrap().clone();
+    if usage.is_empty() {
+        return vec![];
+    }
+    let labels: Vec<&str> = usage.iter().map(|(label, _)| label.as_str()).collect();
+    let line_styles = plot::line_styles(&labels);
+
+    let charts: [(&str, &str, Vec<Option<f64>>); 2] = [
+        (
+            "drun_wall_time",
+            "drun wall time (s)",
+            usage
+                .iter()
+                .map(|(_, usage)| Some(usage.wall_time.as_secs_f64()))
+                .collect(),
+        ),
+        (
+            "drun_peak_rss",
+            "drun peak RSS (MiB)",
+            usage
+                .iter()
+                .map(|(_, usage)| usage.peak_rss.map(mib))
+                .collect(),
+        ),
+    ];
+
+    let mut plot_paths = vec![];
+    for (plot_name, ylabel, values) in charts {
+        if values.iter().all(Option::is_none) {
+            log::warn!("Peak RSS of drun isn't available on this system");
+            continue;
+        }
+        log::info!("{}", plot_name);
+
+        // One row, one column per input
+        let mut writer = csv::Writer::from_writer(NamedTempFile::new().unwrap());
+        let mut record = vec!["drun".to_owned()];
+        record.extend(values.iter().map(|value| match value {
+            Some(value) => value.to_string(),
+            None => "NaN".to_owned(),
+        }));
+        writer.write_record(&record).unwrap();
+        let data_file = writer.into_inner().unwrap();
+        plot::set_dry_run_placeholder(data_file.path(), &format!("<{}>", plot_name));
+
+        let path = data_file.path().to_string_lossy();
+        let lines = labels
+            .iter()
+            .zip(&line_styles)
+            .enumerate()
+            .map(|(idx, (label, line_style))| plot::PlotLine {
+                data: path.to_string(),
+                using: format!("{}{}", idx + 2, if idx == 0 { ":xtic(1)" } else { "" }),
+                style: format!("ls {}", line_style),
+                title: label.to_string(),
+            })
+            .collect();
+
+        let mut program = plot::PlotProgram::new(ylabel, lines);
+        program.extra_commands = USAGE_COMMANDS.to_owned();
+        let gnuplot = program.program();
+
+        let plot_path = plot::plot_path(plot_name, "");
+        plot::run_gnuplot(&gnuplot, &plot_path);
+        plot_paths.push(plot_path);
+    }
+    plot_paths
+}
+
+/// Settings overriding the defaults of the plot template for the bar charts of `plot_usage`
+const USAGE_COMMANDS: &str = r###"
+set xlabel ""
+set xrange [*:*]
+set yrange [0:*]
+set style data histograms
+set style histogram clustered gap 1
+set style fill solid 0.8 border -1
+"###;
+
+/// Add a `TIME_HEADER` column to a drun CSV, with the times of the calls. Times with a message
+/// number go to the call with the number in its `message` column, and the others to the calls in
+/// order. Calls without a time get an empty (missing) value, see `inputs::fill_missing`.
+fn add_times(csv: &[u8], times: &[(Option<String>, u64)], wasm: &Path) -> Vec<u8> {
+    let by_message: HashMap<&str, u64> = times
+        .iter()
+        .filter_map(|(message, time)| Some((message.as_deref()?, *time)))
+        .collect();
+    let mut in_order = times
+        .iter()
+        .filter(|(message, _)| message.is_none())
+        .map(|(_, time)| *time);
+
+    let mut reader = csv::Reader::from_reader(csv);
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let mut headers = reader.byte_headers().unwrap().clone();
+    headers.push_field(TIME_HEADER.as_bytes());
+    writer.write_byte_record(&headers).unwrap();
+
+    let mut num_missing = 0;
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Input,
+            Some(wasm),
+            None,
+            &format!("drun wrote an invalid CSV with {}: {}", wasm.display(), err),
+        )
+    }) {
+        let message = String::from_utf8_lossy(record.get(0).unwrap_or_default());
+        let time = by_message
+            .get(message.trim())
+            .copied()
+            .or_else(|| in_order.next());
+        match time {
+            Some(time) => record.push_field(time.to_string().as_bytes()),
+            None => {
+                num_missing += 1;
+                record.push_field(b"");
+            }
+        }
+        writer.write_byte_record(&record).unwrap();
+    }
+    if num_missing != 0 {
+        log::warn!(
+            "{}: drun printed no times of {} calls",
+            wasm.display(),
+            num_missing
+        );
+    }
+
+    writer.into_inner().unwrap()
+}
+
+/// Parse a time like `1.5ms` or `200 us`, in nanoseconds
+fn parse_time(time: &str) -> Option<u64> {
+    let unit_idx = time.find(|c: char| c.is_alphabetic())?;
+    let value: f64 = time[..unit_idx].trim().parse().ok()?;
+    let nanos_per_unit = match &time[unit_idx..] {
+        "ns" => 1.0,
+        "us" | "µs" => 1e3,
+        "ms" => 1e6,
+        "s" => 1e9,
+        _ => return None,
+    };
+    Some((value * nanos_per_unit).round() as u64)
+}
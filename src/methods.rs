@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use tempfile::NamedTempFile;
+
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::plot;
+use crate::table::{localize, print_table};
+use crate::{DIRTIED_HOST_PAGES_COL_IDX, INSTRUCTIONS_COL_IDX};
+
+/// Header of the column with the called method's name
+const METHOD_HEADER: &str = "method";
+
+/// Per-method sums of an input
+#[derive(Default)]
+struct MethodStats {
+    calls: u64,
+    instructions: u64,
+    dirtied_host_pages: u64,
+}
+
+/// Print per-method call counts, instructions, and dirtied pages of each input, and plot total
+/// instructions per method as bars clustered by method.
+pub fn methods(inputs: &[Input]) {
+    let mut all_stats: Vec<BTreeMap<String, MethodStats>> = vec![];
+
+    for input in inputs {
+        let method_col_idx = match augment::find_column(&input.path, METHOD_HEADER) {
+            Some(col_idx) => col_idx,
+            None => {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(&input.path),
+                    None,
+                    &format!(
+                        "{} doesn't have a '{}' column",
+                        input.path.display(),
+                        METHOD_HEADER
+                    ),
+                );
+            }
+        };
+
+        let mut stats: BTreeMap<String, MethodStats> = BTreeMap::new();
+
+        let mut reader = csv::Reader::from_path(&input.path).unwrap();
+        for record in reader.records() {
+            let record = record.unwrap();
+            let method_stats = stats
+                .entry(record[method_col_idx - 1].to_owned())
+                .or_default();
+            method_stats.calls += 1;
+            method_stats.instructions += record[INSTRUCTIONS_COL_IDX - 1].parse::<u64>().unwrap();
+            method_stats.dirtied_host_pages += record[DIRTIED_HOST_PAGES_COL_IDX - 1]
+                .parse::<u64>()
+                .unwrap();
+        }
+
+        all_stats.push(stats);
+    }
+
+    let mut methods: Vec<&String> = all_stats.iter().flat_map(|stats| stats.keys()).collect();
+    methods.sort();
+    methods.dedup();
+
+    let mut rows: Vec<Vec<String>> = vec![];
+    for method in &methods {
+        for (input, stats) in inputs.iter().zip(&all_stats) {
+            if let Some(method_stats) = stats.get(*method) {
+                rows.push(vec![
+                    method.to_string(),
+                    input.label.clone(),
+                    localize(&method_stats.calls.to_string()),
+                    localize(&method_stats.instructions.to_string()),
+                    localize(&(method_stats.instructions / method_stats.calls).to_string()),
+                    localize(&method_stats.dirtied_host_pages.to_string()),
+                ]);
+            }
+        }
+    }
+
+    print_table(
+        &[
+            "method",
+            "input",
+            "calls",
+            "instructions",
+            "instructions per call",
+            "dirtied host pages",
+        ],
+        &rows,
+    );
+
+    // One row per method, one column per input
+    let data_file = NamedTempFile::new().unwrap();
+    let mut writer = csv::Writer::from_writer(data_file);
+    for method in &methods {
+        let mut record = vec![method.to_string()];
+        record.extend(all_stats.iter().map(|stats| {
+            stats
+                .get(*method)
+                .map_or(0, |method_stats| method_stats.instructions)
+                .to_string()
+        }));
+        writer.write_record(&record).unwrap();
+    }
+    let data_file = writer.into_inner().unwrap();
+
+    let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+    let line_styles = plot::line_styles(&labels);
+
+    let path = data_file.path().to_string_lossy();
+    let lines = inputs
+        .iter()
+        .zip(line_styles)
+        .enumerate()
+        .map(|(idx, (input, line_style))| plot::PlotLine {
+            data: path.to_string(),
+            using: format!("{}{}", idx + 2, if idx == 0 { ":xtic(1)" } else { "" }),
+            style: format!("ls {}", line_style),
+            title: input.label.clone(),
+        })
+        .collect();
+
+    let mut program = plot::PlotProgram::new("total instructions", lines);
+    program.extra_commands = METHODS_COMMANDS.to_owned();
+    let gnuplot = program.program();
+
+    let plot_path = plot::plot_path("methods_instructions", "");
+    plot::run_gnuplot(&gnuplot, &plot_path);
+    log::info!("Wrote {}", plot_path.display());
+}
+
+/// Settings overriding the defaults of `GNUPLOT_TEMPLATE` for per-method bar charts
+const METHODS_COMMANDS: &str = r###"
+set xlabel "method"
+set xrange [*:*]
+set xtics rotate by -30
+set style data histograms
+set style histogram clustered gap 1
+set style fill solid 0.8 border -1
+"###;
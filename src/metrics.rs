@@ -0,0 +1,233 @@
+use std::sync::Mutex;
+
+use crate::config::MetricConfig;
+use crate::inputs::Missing;
+use crate::table;
+
+/// How the values of a column are shown, from a `[[metric]]` table of the configuration
+#[derive(Clone, Debug)]
+pub struct Metric {
+    /// 1-based index of the column in the augmented CSVs
+    pub column: usize,
+    /// Name shown instead of the one derived from the plot or column name
+    pub name: Option<String>,
+    /// Unit of the scaled values, shown after the name
+    pub unit: Option<String>,
+    /// Factor the values are multiplied with before they're shown
+    pub scale: f64,
+    /// Whether plots of the column also get a variant with the running total of the values
+    pub cumulative: bool,
+    /// Number of calls after which the running totals of a variant of the plots of the column
+    /// restart
+    pub cumulative_window: Option<usize>,
+    /// Whether plots of the column also get a variant with the values as percentages of the
+    /// total of the input
+    pub percent_of_total: bool,
+    /// Whether plots of the column show log10 of the values
+    pub log10: bool,
+    /// How missing values of the column are filled in, see `inputs::fill_missing`
+    pub missing: Option<Missing>,
+}
+
+/// Metrics of the configuration, see `set_metrics`
+static METRICS: Mutex<Vec<Metric>> = Mutex::new(vec![]);
+
+/// Names of the plots added by `cumulative_plots`
+static CUMULATIVE_PLOTS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Names of the plots added by `windowed_cumulative_plots`, with their windows
+static WINDOWED_CUMULATIVE_PLOTS: Mutex<Vec<(String, usize)>> = Mutex::new(vec![]);
+
+/// Names of the plots added by `percent_of_total_plots`
+static PERCENT_OF_TOTAL_PLOTS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Show columns as configured in the `[[metric]]` tables of the configuration.
+pub fn set_metrics(configs: &[MetricConfig]) {
+    *METRICS.lock().unwrap() = configs
+        .iter()
+        .map(|config| Metric {
+            column: config.column,
+            name: config.name.clone(),
+            unit: config.unit.clone(),
+            scale: config.scale,
+            cumulative: config.cumulative,
+            cumulative_window: config.cumulative_window,
+            percent_of_total: config.percent_of_total,
+            log10: config.log10,
+            missing: config.missing,
+        })
+        .collect();
+}
+
+/// The metric of a (1-based) column, when configured
+pub fn metric(col_idx: usize) -> Option<Metric> {
+    METRICS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|metric| metric.column == col_idx)
+        .cloned()
+}
+
+/// Whether a metric fills in missing values, see `inputs::fill_missing`
+pub fn fills_missing() -> bool {
+    METRICS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|metric| metric.missing.is_some())
+}
+
+/// The (1-based) column of the metric with the given name, ignoring case
+pub fn find_metric(name: &str) -> Option<usize> {
+    METRICS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|metric| {
+            metric
+                .name
+                .as_deref()
+                .is_some_and(|metric_name| metric_name.eq_ignore_ascii_case(name))
+        })
+        .map(|metric| metric.column)
+}
+
+/// Factor the values of a column are multiplied with when shown, 1 by default
+pub fn scale(col_idx: usize) -> f64 {
+    metric(col_idx).map_or(1.0, |metric| metric.scale)
+}
+
+/// Whether plots of a column show log10 of the values, see `Metric::log10`
+pub fn log10(col_idx: usize) -> bool {
+    metric(col_idx).is_some_and(|metric| metric.log10)
+}
+
+/// Name of a column, without its unit. `default_name` is used when the metric doesn't have a name.
+pub fn name(col_idx: usize, default_name: &str) -> String {
+    metric(col_idx)
+        .and_then(|metric| metric.name)
+        .unwrap_or_else(|| default_name.to_owned())
+}
+
+/// Name of a column with its unit, e.g. "instructions (millions)". `default_name` is used when
+/// the metric doesn't have a name.
+pub fn display_name(col_idx: usize, default_name: &str) -> String {
+    match metric(col_idx) {
+        Some(metric) => {
+            let name = metric.name.as_deref().unwrap_or(default_name);
+            match &metric.unit {
+                Some(unit) => format!("{} ({})", name, unit),
+                None => name.to_owned(),
+            }
+        }
+        None => default_name.to_owned(),
+    }
+}
+
+/// A value of a column, scaled, see `table::localize`. Unscaled values are shown as integers.
+pub fn format_value(col_idx: usize, value: u64) -> String {
+    let scale = scale(col_idx);
+    if scale == 1.0 {
+        table::localize(&value.to_string())
+    } else {
+        table::localize(&format!("{:.2}", value as f64 * scale))
+    }
+}
+
+/// `cumulative_<plot name>` plots of the running totals of the columns of the given plots whose
+/// metrics have `cumulative` set. Plots that already exist aren't repeated.
+pub fn cumulative_plots(plots: &[(&str, usize)]) -> Vec<(String, usize)> {
+    let cumulative_plots: Vec<(String, usize)> = plots
+        .iter()
+        .filter(|(_, col_idx)| metric(*col_idx).is_some_and(|metric| metric.cumulative))
+        .map(|(plot_name, col_idx)| (format!("cumulative_{}", plot_name), *col_idx))
+        .filter(|(plot_name, _)| !plots.iter().any(|(name, _)| name == plot_name))
+        .collect();
+
+    CUMULATIVE_PLOTS.lock().unwrap().extend(
+        cumulative_plots
+            .iter()
+            .map(|(plot_name, _)| plot_name.clone()),
+    );
+    cumulative_plots
+}
+
+/// Plot the running totals of the column of a plot, like the plots added by `cumulative_plots`.
+pub fn add_cumulative_plot(plot_name: &str) {
+    CUMULATIVE_PLOTS.lock().unwrap().push(plot_name.to_owned());
+}
+
+/// Whether a plot was added by `cumulative_plots` or `add_cumulative_plot`
+pub fn is_cumulative_plot(plot_name: &str) -> bool {
+    CUMULATIVE_PLOTS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|name| name == plot_name)
+}
+
+/// `windowed_cumulative_<plot name>` plots of running totals restarting every
+/// `cumulative_window` calls, for the columns of the given plots whose metrics have a
+/// `cumulative_window`. Plots that already exist aren't repeated.
+pub fn windowed_cumulative_plots(plots: &[(&str, usize)]) -> Vec<(String, usize)> {
+    let windowed_plots: Vec<(String, usize, usize)> = plots
+        .iter()
+        .filter_map(|(plot_name, col_idx)| {
+            let window = metric(*col_idx)?.cumulative_window?;
+            Some((
+                format!("windowed_cumulative_{}", plot_name),
+                *col_idx,
+                window,
+            ))
+        })
+        .filter(|(plot_name, _, _)| !plots.iter().any(|(name, _)| name == plot_name))
+        .collect();
+
+    WINDOWED_CUMULATIVE_PLOTS.lock().unwrap().extend(
+        windowed_plots
+            .iter()
+            .map(|(plot_name, _, window)| (plot_name.clone(), *window)),
+    );
+    windowed_plots
+        .into_iter()
+        .map(|(plot_name, col_idx, _)| (plot_name, col_idx))
+        .collect()
+}
+
+/// The window of a plot added by `windowed_cumulative_plots`
+pub fn cumulative_window(plot_name: &str) -> Option<usize> {
+    WINDOWED_CUMULATIVE_PLOTS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(name, _)| name == plot_name)
+        .map(|(_, window)| *window)
+}
+
+/// `percent_of_total_<plot name>` plots of the values as percentages of the totals of the
+/// inputs, for the columns of the given plots whose metrics have `percent_of_total` set. Plots
+/// that already exist aren't repeated.
+pub fn percent_of_total_plots(plots: &[(&str, usize)]) -> Vec<(String, usize)> {
+    let percent_plots: Vec<(String, usize)> = plots
+        .iter()
+        .filter(|(_, col_idx)| metric(*col_idx).is_some_and(|metric| metric.percent_of_total))
+        .map(|(plot_name, col_idx)| (format!("percent_of_total_{}", plot_name), *col_idx))
+        .filter(|(plot_name, _)| !plots.iter().any(|(name, _)| name == plot_name))
+        .collect();
+
+    PERCENT_OF_TOTAL_PLOTS
+        .lock()
+        .unwrap()
+        .extend(percent_plots.iter().map(|(plot_name, _)| plot_name.clone()));
+    percent_plots
+}
+
+/// Whether a plot was added by `percent_of_total_plots`
+pub fn is_percent_of_total_plot(plot_name: &str) -> bool {
+    PERCENT_OF_TOTAL_PLOTS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|name| name == plot_name)
+}
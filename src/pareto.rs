@@ -0,0 +1,109 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use tempfile::NamedTempFile;
+
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::plot;
+use crate::INSTRUCTIONS_COL_IDX;
+
+/// Shares of the most expensive calls reported on the console
+const REPORTED_SHARES: [f64; 3] = [0.01, 0.1, 0.2];
+
+/// For each input, plot per-call instructions sorted in descending order as bars, with the
+/// cumulative percentage of total instructions as a line on the second y axis. Also print the
+/// share of instructions of the most expensive 1%, 10%, and 20% of calls. Returns the paths of
+/// the plots.
+pub fn plot_pareto(inputs: &[Input]) -> Vec<PathBuf> {
+    let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+    let line_styles = plot::line_styles(&labels);
+
+    let mut plot_paths = vec![];
+
+    for (input, line_style) in inputs.iter().zip(line_styles) {
+        let plot_name = format!("pareto_instructions_{}", plot::file_name_slug(&input.label));
+        log::info!("{}", plot_name);
+
+        let mut values = augment::try_column_values(&input.path, INSTRUCTIONS_COL_IDX)
+            .unwrap_or_else(|err| {
+                errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+            });
+        values.sort_unstable_by(|a, b| b.cmp(a));
+
+        let total: u64 = values.iter().sum();
+
+        let mut data_file = NamedTempFile::new().unwrap();
+        let mut cumulative: u64 = 0;
+        let mut cumulative_percentages: Vec<f64> = Vec::with_capacity(values.len());
+        for value in &values {
+            cumulative += value;
+            let percentage = if total == 0 {
+                0.0
+            } else {
+                cumulative as f64 / total as f64 * 100.0
+            };
+            cumulative_percentages.push(percentage);
+            writeln!(data_file, "{},{}", value, percentage).unwrap();
+        }
+        data_file.flush().unwrap();
+        plot::set_dry_run_placeholder(
+            data_file.path(),
+            &format!("<sorted instructions of {}>", input.path.display()),
+        );
+
+        let shares: Vec<String> = REPORTED_SHARES
+            .iter()
+            .filter_map(|share| {
+                let num_calls = (share * values.len() as f64).ceil() as usize;
+                let percentage = cumulative_percentages.get(num_calls.checked_sub(1)?)?;
+                Some(format!(
+                    "top {}% of calls: {:.1}%",
+                    share * 100.0,
+                    percentage
+                ))
+            })
+            .collect();
+        println!("  {} instructions: {}", input.label, shares.join(", "));
+
+        let path = data_file.path().to_string_lossy();
+        let lines = vec![
+            plot::PlotLine {
+                data: path.to_string(),
+                using: "($0+1):1".to_owned(),
+                style: format!("with boxes fs solid 0.5 ls {}", line_style),
+                title: input.label.clone(),
+            },
+            plot::PlotLine {
+                data: path.to_string(),
+                using: "($0+1):2".to_owned(),
+                style: format!(
+                    r#"axes x1y2 with lines lw 3 lc rgb "{}""#,
+                    plot::theme().foreground()
+                ),
+                title: "cumulative % of total".to_owned(),
+            },
+        ];
+
+        let mut program = plot::PlotProgram::new("instructions", lines);
+        program.extra_commands = PARETO_COMMANDS.to_owned();
+        let gnuplot = program.program();
+
+        let plot_path = plot::plot_path(&plot_name, "");
+        plot::run_gnuplot(&gnuplot, &plot_path);
+        plot_paths.push(plot_path);
+    }
+
+    plot_paths
+}
+
+/// Settings overriding the defaults of `GNUPLOT_TEMPLATE` for Pareto charts
+const PARETO_COMMANDS: &str = r###"
+set xlabel "calls, most expensive first"
+set xrange [0:*]
+set y2label "cumulative % of total"
+set y2range [0:100]
+set y2tics nomirror
+set boxwidth 1.0 relative
+"###;
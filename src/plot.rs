@@ -0,0 +1,1376 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::SystemTime;
+
+use indicatif::ProgressBar;
+use serde::Serialize;
+
+use crate::augment;
+use crate::errors;
+use crate::metrics;
+use crate::progress;
+use crate::stats;
+use crate::TOTALS;
+
+/// How plots are rendered
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Run gnuplot to write the PNGs
+    Gnuplot,
+    /// Write a Python script and data files that write the PNGs with matplotlib, instead of
+    /// rendering them
+    MatplotlibScript,
+}
+
+/// Colors of the plots
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Theme {
+    /// Dark lines on a white background
+    #[default]
+    Light,
+    /// Light lines and text on a dark background
+    Dark,
+    /// The Okabe-Ito palette, which stays distinguishable with color vision deficiencies
+    Colorblind,
+}
+
+impl Theme {
+    /// Colors of the line styles of `GNUPLOT_TEMPLATE`, indexed by style - 1
+    pub fn line_style_colors(self) -> [&'static str; NUM_LINE_STYLES as usize] {
+        match self {
+            Theme::Light => [
+                "#A00000", "#00A000", "#5060D0", "#0000A0", "#D0D000", "#00D0D0", "#B200B2",
+            ],
+            Theme::Dark => [
+                "#FF6B6B", "#6BCB77", "#8FA8FF", "#4D96FF", "#FFD93D", "#4DD0E1", "#E07CE0",
+            ],
+            Theme::Colorblind => [
+                "#E69F00", "#56B4E9", "#009E73", "#F0E442", "#0072B2", "#D55E00", "#CC79A7",
+            ],
+        }
+    }
+
+    /// Color of text and lines that aren't series, such as the cumulative line of Pareto charts
+    pub fn foreground(self) -> &'static str {
+        match self {
+            Theme::Light | Theme::Colorblind => "#000000",
+            Theme::Dark => "#E0E0E0",
+        }
+    }
+
+    /// gnuplot terminal options and commands applying the theme's background and text color.
+    /// Empty for the light theme, which uses gnuplot's defaults.
+    fn background(self) -> (&'static str, &'static str) {
+        match self {
+            Theme::Light | Theme::Colorblind => ("", ""),
+            Theme::Dark => (
+                r##"background "#1E1E1E""##,
+                r##"set xtics textcolor rgb "#E0E0E0"
+set ytics textcolor rgb "#E0E0E0"
+set xlabel textcolor rgb "#E0E0E0"
+set ylabel textcolor rgb "#E0E0E0"
+set title textcolor rgb "#E0E0E0"
+set key textcolor rgb "#E0E0E0"
+"##,
+            ),
+        }
+    }
+}
+
+/// Theme applied by `run_gnuplot`
+static THEME: Mutex<Theme> = Mutex::new(Theme::Light);
+
+/// Render the plots with the given theme's colors.
+pub fn set_theme(theme: Theme) {
+    *THEME.lock().unwrap() = theme;
+}
+
+pub fn theme() -> Theme {
+    *THEME.lock().unwrap()
+}
+
+/// Sizes of the plots and of what's drawn in them
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    /// 1200x960 with 24pt text, the original settings
+    #[default]
+    Standard,
+    /// Smaller images with thinner lines and no minor grid lines, for papers
+    Paper,
+    /// 1920x1080 with large text and thick lines, readable when projected
+    Slides,
+    /// Small images for web pages
+    Web,
+}
+
+/// Settings of a `Preset`
+struct PresetSettings {
+    width: u32,
+    height: u32,
+    font_size: u32,
+    line_width: f64,
+    point_size: f64,
+    minor_grid: bool,
+}
+
+impl Preset {
+    fn settings(self) -> PresetSettings {
+        match self {
+            Preset::Standard => PresetSettings {
+                width: 1200,
+                height: 960,
+                font_size: 24,
+                line_width: 2.0,
+                point_size: 1.5,
+                minor_grid: true,
+            },
+            Preset::Paper => PresetSettings {
+                width: 1000,
+                height: 700,
+                font_size: 16,
+                line_width: 1.5,
+                point_size: 1.0,
+                minor_grid: false,
+            },
+            Preset::Slides => PresetSettings {
+                width: 1920,
+                height: 1080,
+                font_size: 32,
+                line_width: 4.0,
+                point_size: 2.5,
+                minor_grid: false,
+            },
+            Preset::Web => PresetSettings {
+                width: 960,
+                height: 720,
+                font_size: 18,
+                line_width: 2.0,
+                point_size: 1.2,
+                minor_grid: true,
+            },
+        }
+    }
+}
+
+/// Preset applied by `run_gnuplot`
+static PRESET: Mutex<Preset> = Mutex::new(Preset::Standard);
+
+/// Render the plots with the given preset's sizes.
+pub fn set_preset(preset: Preset) {
+    *PRESET.lock().unwrap() = preset;
+}
+
+/// When set, `run_gnuplot` also writes a `THUMBNAIL_SIZE` version of each plot, see
+/// `thumbnail_path`
+static THUMBNAILS: AtomicBool = AtomicBool::new(false);
+
+/// Width and height of thumbnails
+const THUMBNAIL_SIZE: (u32, u32) = (300, 240);
+
+/// Make `run_gnuplot` also write thumbnails of the plots.
+pub fn enable_thumbnails() {
+    THUMBNAILS.store(true, Ordering::Relaxed);
+}
+
+/// Path of the thumbnail of a plot: `<plot>_thumb.png` next to `<plot>.png`
+pub fn thumbnail_path(plot_path: &Path) -> PathBuf {
+    let stem = plot_path.file_stem().unwrap().to_string_lossy();
+    plot_path.with_file_name(format!("{}_thumb.png", stem))
+}
+
+/// Fonts of the plots in order of preference, see `font`
+static FONTS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// The font of `FONTS` that's used, once checked
+static FONT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Render the plots with the first of the given fonts that's installed.
+pub fn set_fonts(fonts: Vec<String>) {
+    *FONTS.lock().unwrap() = fonts;
+    *FONT.lock().unwrap() = None;
+}
+
+/// The font of the plots: the first of `set_fonts` that fontconfig knows, with a warning when
+/// it's not the first one. When none of them is installed the font is empty, which is gnuplot's
+/// default. Without fontconfig, and in dry-run mode, it's the first one.
+pub fn font() -> String {
+    FONT.lock()
+        .unwrap()
+        .get_or_insert_with(|| {
+            let fonts = FONTS.lock().unwrap();
+            let first = fonts.first().cloned().unwrap_or_default();
+            if DRY_RUN.lock().unwrap().is_some() {
+                return first;
+            }
+            let installed = match installed_fonts() {
+                Some(installed) => installed,
+                None => {
+                    log::debug!("Unable to list the installed fonts, using '{}'", first);
+                    return first;
+                }
+            };
+            let font = fonts.iter().find(|font| {
+                installed
+                    .iter()
+                    .any(|family| family.eq_ignore_ascii_case(font))
+            });
+            match font {
+                Some(font) if *font == first => {}
+                Some(font) => log::warn!("Font '{}' isn't installed, using '{}'", first, font),
+                None => log::warn!(
+                    "None of the fonts {} are installed, using gnuplot's default font. \
+                     Use --fonts to select installed ones.",
+                    fonts.join(", ")
+                ),
+            }
+            font.cloned().unwrap_or_default()
+        })
+        .clone()
+}
+
+/// Font families known to fontconfig, from `fc-list`. `None` when it can't be run.
+fn installed_fonts() -> Option<Vec<String>> {
+    let output = Command::new("fc-list")
+        .args([":", "family"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    // Families with several names are listed as `Name,Other name`
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .flat_map(|line| line.split(','))
+            .map(|family| family.trim().to_owned())
+            .collect(),
+    )
+}
+
+/// Values of the theme and preset, the `settings` variable of gnuplot templates, see
+/// `GnuplotProgram`
+#[derive(Serialize)]
+struct Settings {
+    width: u32,
+    height: u32,
+    font: String,
+    font_size: u32,
+    line_width: f64,
+    point_size: f64,
+    minor_grid: bool,
+    /// Options of `set terminal` applying the theme's background
+    background: &'static str,
+    /// Commands applying the theme's text color
+    theme_commands: &'static str,
+    foreground: &'static str,
+    /// Colors of the line styles 1 to 7
+    colors: [&'static str; NUM_LINE_STYLES as usize],
+}
+
+/// The settings of the theme and preset. For thumbnails the preset's sizes are scaled down to
+/// `THUMBNAIL_SIZE`.
+fn settings(thumbnail: bool) -> Settings {
+    let theme = theme();
+    let (background, theme_commands) = theme.background();
+    let mut preset = PRESET.lock().unwrap().settings();
+    if thumbnail {
+        let scale = f64::from(THUMBNAIL_SIZE.0) / f64::from(preset.width);
+        preset = PresetSettings {
+            width: THUMBNAIL_SIZE.0,
+            height: THUMBNAIL_SIZE.1,
+            font_size: ((f64::from(preset.font_size) * scale).round() as u32).max(6),
+            line_width: (preset.line_width * scale).max(1.0),
+            point_size: (preset.point_size * scale).max(0.5),
+            minor_grid: false,
+        };
+    }
+
+    Settings {
+        width: preset.width,
+        height: preset.height,
+        font: font(),
+        font_size: preset.font_size,
+        line_width: preset.line_width,
+        point_size: preset.point_size,
+        minor_grid: preset.minor_grid,
+        background,
+        theme_commands,
+        foreground: theme.foreground(),
+        colors: theme.line_style_colors(),
+    }
+}
+
+/// One line of a plot
+pub struct Series<'a> {
+    /// Augmented CSV with the data of the line
+    pub data: &'a Path,
+    /// Legend title
+    pub label: &'a str,
+    pub style: &'a SeriesStyle,
+    /// Number of leading calls plotted before the first call, see `inputs::Alignment`
+    pub offset: usize,
+    /// 0-based indices of calls to mark with vertical lines in the series' color
+    pub markers: &'a [usize],
+}
+
+/// Title and axis labels of a plot
+pub struct PlotLabels {
+    pub title: Option<String>,
+    pub xlabel: String,
+    pub ylabel: String,
+}
+
+/// Overrides of the labels of plots, by plot name
+static LABEL_OVERRIDES: Mutex<Vec<(String, PlotLabelOverrides)>> = Mutex::new(vec![]);
+
+/// Labels of a plot that replace the ones derived from its name. `None`s keep the derived ones.
+#[derive(Clone, Default)]
+pub struct PlotLabelOverrides {
+    pub title: Option<String>,
+    pub xlabel: Option<String>,
+    pub ylabel: Option<String>,
+}
+
+/// Use the given labels for the plots with the given names, replacing the previous overrides.
+pub fn set_label_overrides(overrides: Vec<(String, PlotLabelOverrides)>) {
+    *LABEL_OVERRIDES.lock().unwrap() = overrides;
+}
+
+/// What the rows of the plotted data are, see `set_row_name`
+static ROW_NAME: Mutex<&str> = Mutex::new("call");
+
+/// Label the x axes with the given name of the rows, see `inputs::PipelineOptions::row_name`.
+pub fn set_row_name(row_name: &'static str) {
+    *ROW_NAME.lock().unwrap() = row_name;
+}
+
+fn row_name() -> &'static str {
+    *ROW_NAME.lock().unwrap()
+}
+
+/// Labels of the plot with the given name and column: no title, "call" (or "round", see
+/// `set_row_name`) as the x label, and the display name of the column's metric (by default the
+/// name with `_`s replaced by spaces) as the y label, unless overridden with
+/// `set_label_overrides`. The y label of a log10 metric is `log10(<y label>)`.
+pub fn plot_labels(plot_name: &str, column_idx: usize) -> PlotLabels {
+    let overrides = LABEL_OVERRIDES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(name, _)| name == plot_name)
+        .map(|(_, overrides)| overrides.clone())
+        .unwrap_or_default();
+
+    PlotLabels {
+        title: overrides.title,
+        xlabel: overrides.xlabel.unwrap_or_else(|| row_name().to_owned()),
+        ylabel: overrides.ylabel.unwrap_or_else(|| {
+            let ylabel = if metrics::is_cumulative_plot(plot_name) {
+                let name = plot_name
+                    .trim_start_matches("cumulative_")
+                    .replace('_', " ");
+                format!("total {}", metrics::display_name(column_idx, &name))
+            } else if let Some(window) = metrics::cumulative_window(plot_name) {
+                let name = plot_name
+                    .trim_start_matches("windowed_cumulative_")
+                    .replace('_', " ");
+                format!(
+                    "total {} per {} {}s",
+                    metrics::display_name(column_idx, &name),
+                    window,
+                    row_name()
+                )
+            } else if metrics::is_percent_of_total_plot(plot_name) {
+                let name = plot_name
+                    .trim_start_matches("percent_of_total_")
+                    .replace('_', " ");
+                format!("{} (% of total)", metrics::name(column_idx, &name))
+            } else {
+                metrics::display_name(column_idx, &plot_name.replace('_', " "))
+            };
+            if metrics::log10(column_idx) {
+                format!("log10({})", ylabel)
+            } else {
+                ylabel
+            }
+        }),
+    }
+}
+
+/// Check a color of an input: `#RRGGBB`, `#AARRGGBB`, or a gnuplot color name like
+/// `dark-green`. Anything else could break out of the quoted string it's inserted in.
+pub fn check_color(color: &str) -> Result<(), String> {
+    let valid = match color.strip_prefix('#') {
+        Some(hex) => {
+            (hex.len() == 6 || hex.len() == 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid color {:?}, expected `#RRGGBB` or a gnuplot color name",
+            color
+        ))
+    }
+}
+
+/// Escape a string for a double quoted gnuplot string.
+fn gnuplot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Variables of `GNUPLOT_TEMPLATE`
+#[derive(Serialize)]
+pub struct PlotProgram {
+    /// No title when `None`
+    pub title: Option<String>,
+    pub xlabel: String,
+    pub ylabel: String,
+    /// gnuplot commands run right before `plot`, e.g. annotations or settings overriding the
+    /// defaults of the template
+    pub extra_commands: String,
+    pub lines: Vec<PlotLine>,
+}
+
+/// One element of the `plot` command of `GNUPLOT_TEMPLATE`
+#[derive(Serialize)]
+pub struct PlotLine {
+    /// Path of the CSV with the data
+    pub data: String,
+    /// `using` specification, e.g. `($0+1):3`
+    pub using: String,
+    /// Style options, e.g. `with lines ls 1 dt 2`
+    pub style: String,
+    /// Legend title
+    pub title: String,
+}
+
+impl PlotProgram {
+    /// A plot without a title or extra commands, with calls (or rounds) on the x axis
+    pub fn new(ylabel: &str, lines: Vec<PlotLine>) -> PlotProgram {
+        PlotProgram {
+            title: None,
+            xlabel: row_name().to_owned(),
+            ylabel: ylabel.to_owned(),
+            extra_commands: String::new(),
+            lines,
+        }
+    }
+
+    /// The gnuplot program of `GNUPLOT_TEMPLATE`
+    pub fn program(&self) -> GnuplotProgram {
+        GnuplotProgram::new(GNUPLOT_TEMPLATE, self)
+    }
+}
+
+/// A gnuplot program: a template, in Tera syntax, with its variables. It's rendered by
+/// `run_gnuplot`, with the theme and preset as the `settings` variable (see `Settings`), once for
+/// the plot and once for its thumbnail. Strings in gnuplot strings should go through the
+/// `gnuplot_escape` filter.
+pub struct GnuplotProgram {
+    template: &'static str,
+    context: tera::Context,
+}
+
+impl GnuplotProgram {
+    /// The program of `template` with the fields of `context` as its variables
+    pub fn new<T: Serialize>(template: &'static str, context: &T) -> GnuplotProgram {
+        GnuplotProgram {
+            template,
+            context: tera::Context::from_serialize(context).unwrap(),
+        }
+    }
+
+    /// Whether the size of the plot is the preset's, which thumbnails scale down
+    fn has_preset_size(&self) -> bool {
+        self.template.contains("settings.width")
+    }
+
+    /// The program with the given settings. Panics when the template is invalid: all templates
+    /// are built in.
+    fn render(&self, settings: &Settings) -> String {
+        let mut tera = tera::Tera::new();
+        tera.register_filter(
+            "gnuplot_escape",
+            |s: &str, _: tera::Kwargs, _: &tera::State| gnuplot_escape(s),
+        );
+        let mut context = self.context.clone();
+        context.insert("settings", settings);
+        tera.render_str(self.template, &context, false)
+            .unwrap_or_else(|err| panic!("Invalid gnuplot template: {:?}", err))
+    }
+}
+
+/// Overrides of the line style `line_styles` picks for a series
+#[derive(Default)]
+pub struct SeriesStyle {
+    /// gnuplot color specification, e.g. `#00A000` or `dark-green`
+    pub color: Option<String>,
+    /// gnuplot dash type index
+    pub dash: Option<u32>,
+}
+
+/// 1-based index of the column of rounds that the per-call values are divided by, see
+/// `set_per_round`
+static PER_ROUND: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Plot the per-call instructions and accessed and dirtied pages divided by the numbers of rounds
+/// of the calls, in the given (1-based) column, or as they are with `None`. Calls without rounds
+/// are left out.
+pub fn set_per_round(rounds_col_idx: Option<usize>) {
+    *PER_ROUND.lock().unwrap() = rounds_col_idx;
+}
+
+/// Render the given plots (names and column indices, as in `PLOTS`) with one line per series,
+/// writing `<file_prefix><plot name>.png` to the working directory. Each plot is reported as
+/// soon as it's written, and `progress` is advanced by one.
+pub fn render_plots(
+    series: &[Series],
+    plots: &[(&str, usize)],
+    file_prefix: &str,
+    progress: &ProgressBar,
+) -> Vec<PathBuf> {
+    let mut plot_paths: Vec<PathBuf> = Vec::with_capacity(plots.len());
+
+    // Programs are generated on this thread and sent to workers running gnuplot, so that the
+    // gnuplot processes run in parallel with each other and with reading the data for the next
+    // plot's annotations. Programs printed in dry-run mode are kept in order with one worker.
+    let dry_run = DRY_RUN.lock().unwrap().is_some();
+    let num_workers = if dry_run {
+        1
+    } else {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    };
+    let (sender, receiver) = mpsc::channel::<(GnuplotProgram, PathBuf)>();
+    let receiver = Mutex::new(receiver);
+
+    std::thread::scope(|scope| {
+        for _ in 0..num_workers.min(plots.len()) {
+            scope.spawn(|| loop {
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok((gnuplot, plot_path)) => {
+                        if run_gnuplot(&gnuplot, &plot_path) && !dry_run {
+                            progress::info(progress, &format!("Wrote {}", plot_path.display()));
+                        }
+                        progress.inc(1);
+                    }
+                    Err(_) => break,
+                }
+            });
+        }
+
+        for (plot_name, column_idx) in plots.iter() {
+            log::debug!("Generating {}", plot_name);
+            progress.set_message(plot_name.to_string());
+
+            let cumulative = metrics::is_cumulative_plot(plot_name);
+            let window = metrics::cumulative_window(plot_name);
+            let percent_of_total = metrics::is_percent_of_total_plot(plot_name);
+            let scale = metrics::scale(*column_idx);
+            let log10 = metrics::log10(*column_idx);
+            let per_round = PER_ROUND.lock().unwrap().filter(|_| {
+                !percent_of_total
+                    && TOTALS
+                        .iter()
+                        .any(|(_, _, source_col_idx)| source_col_idx == column_idx)
+            });
+
+            let mut annotations = if !cumulative
+                && window.is_none()
+                && !percent_of_total
+                && TOTALS
+                    .iter()
+                    .any(|(_, total_col_idx, _)| total_col_idx == column_idx)
+            {
+                crossover_annotations(plot_name, series, *column_idx, progress)
+            } else {
+                String::new()
+            };
+            annotations.push_str(&marker_annotations(series));
+
+            // The y values, in a gnuplot `using` clause. In expressions columns are `$<index>`.
+            let value = if let Some(rounds_col_idx) = per_round {
+                // gnuplot leaves out the points of divisions by 0
+                format!("(${} * {} / real(${}))", column_idx, scale, rounds_col_idx)
+            } else if scale != 1.0 {
+                format!("(${} * {})", column_idx, scale)
+            } else {
+                format!("${}", column_idx)
+            };
+            let y = if cumulative {
+                // `$0` is the record number in the current data file, i.e. the call index
+                annotations.push_str("total = 0\n");
+                format!("(total = ($0 == 0 ? 0 : total) + {})", value)
+            } else if let Some(window) = window {
+                annotations.push_str("total = 0\n");
+                format!(
+                    "(total = (int($0) % {} == 0 ? 0 : total) + {})",
+                    window, value
+                )
+            } else if scale != 1.0 || log10 || per_round.is_some() {
+                value
+            } else {
+                column_idx.to_string()
+            };
+            // gnuplot leaves out the points of values without a logarithm, i.e. 0s
+            let transform = |y: String| {
+                if log10 {
+                    format!("(log10({}))", y)
+                } else {
+                    y
+                }
+            };
+            let lines = if percent_of_total {
+                // The total of each series is computed with `stats` before plotting, as
+                // `series<index>_sum`. Scaling doesn't change the percentages.
+                for (series_idx, series) in series.iter().enumerate() {
+                    annotations.push_str(&format!(
+                        "stats \"{}\" using {} name \"series{}\" nooutput\n",
+                        gnuplot_escape(&series.data.to_string_lossy()),
+                        column_idx,
+                        series_idx
+                    ));
+                }
+                plot_lines(series, |series_idx| {
+                    transform(format!(
+                        "(${} * 100.0 / series{}_sum)",
+                        column_idx, series_idx
+                    ))
+                })
+            } else {
+                plot_lines(series, |_| transform(y.clone()))
+            };
+
+            let labels = plot_labels(plot_name, *column_idx);
+
+            let gnuplot = PlotProgram {
+                title: labels.title,
+                xlabel: labels.xlabel,
+                ylabel: match per_round {
+                    Some(_) => format!("{} per round", labels.ylabel),
+                    None => labels.ylabel,
+                },
+                extra_commands: annotations,
+                lines,
+            }
+            .program();
+
+            let plot_path = plot_path(plot_name, file_prefix);
+            plot_paths.push(plot_path.clone());
+            sender.send((gnuplot, plot_path)).unwrap();
+        }
+
+        // Workers exit when the channel is closed and empty
+        drop(sender);
+    });
+
+    plot_paths
+}
+
+/// When set, `run_gnuplot` prints programs instead of running them, with the data file paths
+/// (the first elements) replaced by placeholders (the second elements)
+static DRY_RUN: Mutex<Option<Vec<(String, String)>>> = Mutex::new(None);
+
+/// Make `run_gnuplot` print the programs to standard output instead of running gnuplot.
+pub fn enable_dry_run() {
+    *DRY_RUN.lock().unwrap() = Some(vec![]);
+}
+
+/// Show `placeholder` instead of `data` in the programs printed in dry-run mode, so that the
+/// output doesn't change with the paths of temporary files.
+pub fn set_dry_run_placeholder(data: &Path, placeholder: &str) {
+    if let Some(placeholders) = DRY_RUN.lock().unwrap().as_mut() {
+        placeholders.push((data.to_string_lossy().into_owned(), placeholder.to_owned()));
+    }
+}
+
+/// Run a gnuplot program with its output going to `output`. In dry-run mode (see
+/// `enable_dry_run`) the program is printed instead. Returns whether the plot was written (or
+/// printed); gnuplot failures are reported here.
+///
+/// Programs are run by gnuplot processes that are kept alive between calls (see `Gnuplot`), so
+/// that startup and font initialization happen once per process rather than once per plot. Calls
+/// from several threads run in parallel, each on its own process.
+pub fn run_gnuplot(program: &GnuplotProgram, output: &Path) -> bool {
+    // Not locked while getting the settings, which check for dry-run mode, see `font`
+    let placeholders = DRY_RUN.lock().unwrap().clone();
+    if let Some(placeholders) = placeholders {
+        let mut gnuplot = program.render(&settings(false));
+        for (data, placeholder) in &placeholders {
+            gnuplot = gnuplot.replace(data.as_str(), placeholder);
+        }
+        println!("# {}", output.display());
+        println!("{}", gnuplot.trim());
+        println!();
+        return true;
+    }
+
+    let gnuplot = |thumbnail| {
+        let gnuplot = program.render(&settings(thumbnail));
+        if old_gnuplot() {
+            compat_terminal(&gnuplot)
+        } else {
+            gnuplot
+        }
+    };
+
+    // Programs with a fixed size can't be made into thumbnails
+    let ok = render(&gnuplot(false), output);
+    if ok && THUMBNAILS.load(Ordering::Relaxed) && program.has_preset_size() {
+        render(&gnuplot(true), &thumbnail_path(output))
+    } else {
+        ok
+    }
+}
+
+/// Oldest gnuplot version that the `set terminal` commands of the templates are known to work
+/// with. With older versions the commands are replaced, see `compat_terminal`.
+const MIN_GNUPLOT_VERSION: (u32, u32) = (5, 0);
+
+/// Whether the installed gnuplot is older than `MIN_GNUPLOT_VERSION`, once checked
+static OLD_GNUPLOT: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Whether programs have to be made compatible with an old gnuplot. The version is checked the
+/// first time, with a warning when it's too old. Versions that can't be determined are assumed to
+/// be recent.
+pub fn old_gnuplot() -> bool {
+    *OLD_GNUPLOT
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| match gnuplot_version() {
+            Some(version) if version < MIN_GNUPLOT_VERSION => {
+                log::warn!(
+                    "gnuplot {}.{} is older than {}.{}, plots are rendered with its default font, \
+                     without rounded lines and theme backgrounds",
+                    version.0,
+                    version.1,
+                    MIN_GNUPLOT_VERSION.0,
+                    MIN_GNUPLOT_VERSION.1
+                );
+                true
+            }
+            version => {
+                log::debug!("gnuplot version: {:?}", version);
+                false
+            }
+        })
+}
+
+/// Major and minor version of the installed gnuplot, from `gnuplot --version`, which prints e.g.
+/// `gnuplot 5.4 patchlevel 2`
+fn gnuplot_version() -> Option<(u32, u32)> {
+    let output = Command::new("gnuplot")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut words = output.split_whitespace();
+    if words.next() != Some("gnuplot") {
+        return None;
+    }
+    let (major, minor) = words.next()?.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Replace the `set terminal` command of a program with a plain `png` terminal of the same size,
+/// as old gnuplot versions don't have all of the font, `rounded`, and `background` options of the
+/// templates.
+fn compat_terminal(gnuplot: &str) -> String {
+    let mut lines: Vec<String> = vec![];
+    // Continued `set terminal` command, without the `\`s
+    let mut terminal: Option<String> = None;
+    for line in gnuplot.lines() {
+        if terminal.is_none() && !line.trim_start().starts_with("set terminal") {
+            lines.push(line.to_owned());
+            continue;
+        }
+        let command = terminal.get_or_insert_with(String::new);
+        let continued = line.trim_end().ends_with('\\');
+        command.push_str(line.trim_end().trim_end_matches('\\'));
+        command.push(' ');
+        if !continued {
+            let size = command
+                .split_whitespace()
+                .skip_while(|word| *word != "size")
+                .nth(1);
+            lines.push(match size {
+                Some(size) => format!("set terminal png size {}", size),
+                None => "set terminal png".to_owned(),
+            });
+            terminal = None;
+        }
+    }
+    lines.join("\n")
+}
+
+/// Run a program on an idle gnuplot process, reporting failures. Plots that exist are only
+/// replaced when they change, see `claim_output`.
+fn render(gnuplot: &str, output: &Path) -> bool {
+    if !output.exists() || *OVERWRITE.lock().unwrap() == Overwrite::Force {
+        // Recorded as written by this process, for later iterations of `--watch`
+        claim_output(output);
+        return render_to(gnuplot, output);
+    }
+
+    // Render next to the plot, so that it can be renamed to it
+    let dir = output
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = output.file_name().unwrap().to_string_lossy();
+    let new = tempfile::Builder::new()
+        .prefix(&format!(".{}.", name))
+        .tempfile_in(dir)
+        .expect("Unable to create temporary plot file");
+    if !render_to(gnuplot, new.path()) {
+        return false;
+    }
+    if std::fs::read(output).ok() == std::fs::read(new.path()).ok() {
+        return true;
+    }
+    claim_output(output);
+    new.persist(output).expect("Unable to write plot");
+    true
+}
+
+fn render_to(gnuplot: &str, output: &Path) -> bool {
+    log::debug!("Running gnuplot > {}", output.display());
+    log::trace!("gnuplot program:\n{}", gnuplot);
+
+    // Take an idle process, or start a new one when all of them are busy
+    let idle = IDLE_GNUPLOTS.lock().unwrap().pop();
+    let mut process = idle.unwrap_or_else(Gnuplot::spawn);
+
+    match process.render(gnuplot, output) {
+        Ok(()) => {
+            IDLE_GNUPLOTS.lock().unwrap().push(process);
+            true
+        }
+        Err(status) => {
+            // gnuplot exits on the first error when reading a program from a pipe. Its error
+            // message has already been printed to stderr. The next plot gets a new process.
+            log::error!(
+                "gnuplot failed while rendering {} ({})",
+                output.display(),
+                status
+            );
+            false
+        }
+    }
+}
+
+/// Stop the gnuplot processes started by `run_gnuplot`, waiting for them to exit.
+pub fn stop_gnuplot() {
+    let processes: Vec<Gnuplot> = std::mem::take(&mut *IDLE_GNUPLOTS.lock().unwrap());
+    for process in processes {
+        process.stop();
+    }
+}
+
+/// gnuplot processes started by `run_gnuplot` that aren't rendering a plot
+static IDLE_GNUPLOTS: Mutex<Vec<Gnuplot>> = Mutex::new(vec![]);
+
+/// A gnuplot process reading programs from its standard input
+struct Gnuplot {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+/// Line printed by gnuplot after each program, to know when the plot has been written
+const GNUPLOT_DONE: &str = "generate_plots: done";
+
+impl Gnuplot {
+    fn spawn() -> Gnuplot {
+        log::debug!("Starting gnuplot");
+
+        let mut child = Command::new("gnuplot")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect("Unable to spawn gnuplot process");
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Gnuplot {
+            child,
+            stdin,
+            stdout,
+        }
+    }
+
+    /// Run a program with the output set to `output`, and wait until the plot is written. The
+    /// settings are reset afterwards so that the next program starts from the defaults. On
+    /// failure the process has exited, and the error is its exit status.
+    fn render(&mut self, gnuplot: &str, output: &Path) -> Result<(), String> {
+        // Single quoted gnuplot strings have no escapes other than `''` for a quote
+        let output = output.to_string_lossy().replace('\'', "''");
+        let program = format!(
+            "set output '{}'\n{}\nunset output\nreset\nset print \"-\"\nprint \"{}\"\n",
+            output, gnuplot, GNUPLOT_DONE
+        );
+
+        // A write error means that gnuplot has exited, which is reported below
+        let _ = self
+            .stdin
+            .write_all(program.as_bytes())
+            .and_then(|()| self.stdin.flush());
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.stdout.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) if line.trim_end() == GNUPLOT_DONE => return Ok(()),
+                Ok(_) => log::debug!("gnuplot: {}", line.trim_end()),
+            }
+        }
+
+        Err(match self.child.wait() {
+            Ok(status) => status.to_string(),
+            Err(err) => err.to_string(),
+        })
+    }
+
+    /// Close the standard input, which makes gnuplot exit, and wait for it.
+    fn stop(self) {
+        let Gnuplot {
+            mut child, stdin, ..
+        } = self;
+        drop(stdin);
+        if let Err(err) = child.wait() {
+            log::warn!("Unable to wait for gnuplot: {}", err);
+        }
+    }
+}
+
+/// Open images with the platform's default viewer. Failures are reported but not fatal, the
+/// plots have been written either way.
+pub fn open_in_viewer(paths: &[PathBuf]) {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    for path in paths {
+        let status = Command::new(opener)
+            .arg(path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(_) => log::warn!("Unable to open {} with {}", path.display(), opener),
+            Err(err) => {
+                log::warn!("Unable to run {}: {}", opener, err);
+                return;
+            }
+        }
+    }
+}
+
+/// A file name friendly version of a label: ASCII alphanumerics are kept, everything else is
+/// replaced with `_`.
+pub fn file_name_slug(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// gnuplot commands drawing a vertical dotted line at each marked call of each series
+fn marker_annotations(series: &[Series]) -> String {
+    let labels: Vec<&str> = series.iter().map(|series| series.label).collect();
+    let line_styles = line_styles(&labels);
+
+    let mut annotations = String::new();
+    for (series, line_style) in series.iter().zip(line_styles) {
+        for call_idx in series.markers {
+            annotations.push_str(&format!(
+                "set arrow from {x}, graph 0 to {x}, graph 1 nohead ls {} lw 1 dt 3 back\n",
+                line_style,
+                x = (*call_idx as i64) + 1 - series.offset as i64,
+            ));
+        }
+    }
+    annotations
+}
+
+/// Maximum number of crossovers labelled in a plot. Curves that keep crossing each other would
+/// otherwise make the plot unreadable.
+const MAX_CROSSOVER_LABELS: usize = 10;
+
+/// Find the calls where the curves of two series cross, print them, and return gnuplot commands
+/// labelling them.
+fn crossover_annotations(
+    plot_name: &str,
+    series: &[Series],
+    column_idx: usize,
+    progress: &ProgressBar,
+) -> String {
+    // Aligned by the offsets, so that the values at an index are plotted at the same call
+    let columns: Vec<Vec<u64>> = series
+        .iter()
+        .map(|series| {
+            let mut values =
+                augment::try_column_values(series.data, column_idx).unwrap_or_else(|err| {
+                    errors::exit(errors::Category::Input, Some(series.data), None, &err)
+                });
+            values.drain(..series.offset.min(values.len()));
+            values
+        })
+        .collect();
+
+    let mut annotations = String::new();
+    let mut num_labels = 0;
+
+    for (idx1, series1) in series.iter().enumerate() {
+        for (idx2, series2) in series.iter().enumerate().skip(idx1 + 1) {
+            let crossovers = stats::crossovers(&columns[idx1], &columns[idx2]);
+            if crossovers.is_empty() {
+                continue;
+            }
+
+            let calls: Vec<String> = crossovers.iter().map(|idx| (idx + 1).to_string()).collect();
+            progress::info(
+                progress,
+                &format!(
+                    "{}: '{}' and '{}' cross at call{} {}",
+                    plot_name,
+                    series1.label,
+                    series2.label,
+                    if calls.len() == 1 { "" } else { "s" },
+                    calls.join(", ")
+                ),
+            );
+
+            for idx in crossovers {
+                if num_labels == MAX_CROSSOVER_LABELS {
+                    break;
+                }
+                num_labels += 1;
+                annotations.push_str(&format!(
+                    "set label \"{}\" at {},{} point pt 6 ps 2 offset 1,1 front\n",
+                    idx + 1,
+                    idx + 1,
+                    columns[idx1][idx] as f64 * metrics::scale(column_idx)
+                ));
+            }
+        }
+    }
+
+    annotations
+}
+
+/// Path of a plot: `file_prefix` followed by the output template (see `set_output_template`), in
+/// the output directory when one is set
+pub fn plot_path(plot_name: &str, file_prefix: &str) -> PathBuf {
+    let template = OUTPUT_TEMPLATE.lock().unwrap();
+    let file_name = template
+        .as_deref()
+        .unwrap_or(DEFAULT_OUTPUT_TEMPLATE)
+        .replace("{ext}", "png")
+        .replace("{plot}", plot_name);
+    let file_name = format!("{}{}", file_prefix, file_name);
+    match &*OUTPUT_DIR.lock().unwrap() {
+        Some(output_dir) => output_dir.join(file_name),
+        None => PathBuf::from(file_name),
+    }
+}
+
+/// What happens to outputs that exist, other than the ones written by this process
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overwrite {
+    /// Exit with an error
+    Refuse,
+    Force,
+    /// Rename them to `<name>.bak`, or `<name>.bak.N` when that exists
+    Backup,
+}
+
+/// Set with `set_overwrite`
+static OVERWRITE: Mutex<Overwrite> = Mutex::new(Overwrite::Refuse);
+
+/// Outputs written by this process, e.g. in earlier iterations of `--watch`, which are
+/// overwritten without checks
+static WRITTEN: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+
+/// Directories whose contents are all generated by the command writing to them, see
+/// `own_output_dir`
+static OWNED_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+
+/// Overwrite outputs in `dir` without checks. For directories that a command generates in full,
+/// e.g. the site of `site build`, which change whenever their sources do.
+pub fn own_output_dir(dir: &Path) {
+    OWNED_DIRS.lock().unwrap().push(dir.to_owned());
+}
+
+/// What `claim_output` does with outputs that exist.
+pub fn set_overwrite(overwrite: Overwrite) {
+    *OVERWRITE.lock().unwrap() = overwrite;
+}
+
+/// Check that an output is about to be written to `path`. When it exists and wasn't written by
+/// this process it's refused (exiting with an error), overwritten, or moved aside, see
+/// `set_overwrite`.
+pub fn claim_output(path: &Path) {
+    let mut written = WRITTEN.lock().unwrap();
+    if written.iter().any(|written| written == path)
+        || OWNED_DIRS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|dir| path.starts_with(dir))
+    {
+        return;
+    }
+    if path.exists() {
+        match *OVERWRITE.lock().unwrap() {
+            Overwrite::Refuse => {
+                errors::exit(
+                    errors::Category::Output,
+                    Some(path),
+                    None,
+                    &format!(
+                        "{} exists and would be overwritten. Use --force to overwrite it, or \
+                         --backup to move it aside.",
+                        path.display()
+                    ),
+                );
+            }
+            Overwrite::Force => {}
+            Overwrite::Backup => {
+                let name = path.file_name().unwrap().to_string_lossy();
+                let backup = std::iter::once(format!("{}.bak", name))
+                    .chain((2..).map(|idx| format!("{}.bak.{}", name, idx)))
+                    .map(|backup| path.with_file_name(backup))
+                    .find(|backup| !backup.exists())
+                    .unwrap();
+                std::fs::rename(path, &backup).unwrap_or_else(|err| {
+                    errors::exit(
+                        errors::Category::Output,
+                        Some(path),
+                        None,
+                        &format!("Unable to move {} aside: {}", path.display(), err),
+                    );
+                });
+                log::info!("Moved {} to {}", path.display(), backup.display());
+            }
+        }
+    }
+    written.push(path.to_owned());
+}
+
+/// Write an output file, see `claim_output`. Outputs that exist with the same contents are left
+/// as they are, so that generating them again from the same inputs doesn't need `--force`.
+pub fn write_output(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if std::fs::read(path).is_ok_and(|old| old == contents) {
+        return Ok(());
+    }
+    claim_output(path);
+    std::fs::write(path, contents)
+}
+
+/// Directory set with `set_output_dir`
+static OUTPUT_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Write plots to `output_dir` instead of the working directory. The directory must exist.
+pub fn set_output_dir(output_dir: &Path) {
+    *OUTPUT_DIR.lock().unwrap() = Some(output_dir.to_owned());
+}
+
+/// Output template used when none is set
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{plot}.{ext}";
+
+/// Output template set with `set_output_template`, with everything but `{plot}` and `{ext}`
+/// already substituted
+static OUTPUT_TEMPLATE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Name plots after `template` instead of `DEFAULT_OUTPUT_TEMPLATE`. Placeholders are `{plot}`
+/// (the plot name, required), `{ext}` (the file extension), `{date}` (today's UTC date, as
+/// `YYYY-MM-DD`), and `{config}` (replaced with `config`). Returns an error for unknown
+/// placeholders and templates without `{plot}`.
+pub fn set_output_template(template: &str, config: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in output template '{}'", template))?;
+        let placeholder = &rest[start + 1..start + end];
+        if !matches!(placeholder, "plot" | "ext" | "date" | "config") {
+            return Err(format!(
+                "unknown placeholder '{{{}}}' in output template '{}'",
+                placeholder, template
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if !template.contains("{plot}") {
+        return Err(format!(
+            "output template '{}' doesn't contain {{plot}}, every plot would be written to the same file",
+            template
+        ));
+    }
+
+    let date = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    let template = template
+        .replace("{date}", &date[..10])
+        .replace("{config}", config);
+
+    *OUTPUT_TEMPLATE.lock().unwrap() = Some(template);
+    Ok(())
+}
+
+/// `plot` elements drawing a line per series, with `y(<index of the series>)` as the y values
+fn plot_lines(series: &[Series], y: impl Fn(usize) -> String) -> Vec<PlotLine> {
+    let labels: Vec<&str> = series.iter().map(|series| series.label).collect();
+    let line_styles = line_styles(&labels);
+
+    series
+        .iter()
+        .zip(line_styles.iter())
+        .enumerate()
+        .map(|(series_idx, (series, line_style))| {
+            let mut style = format!("with linespoints ls {}", line_style);
+            if let Some(color) = &series.style.color {
+                style.push_str(&format!(r#" lc rgb "{}""#, gnuplot_escape(color)));
+            }
+            if let Some(dash) = series.style.dash {
+                style.push_str(&format!(" dt {}", dash));
+            }
+
+            let x = if series.offset == 0 {
+                "($0+1)".to_owned()
+            } else {
+                format!("($0+1-{})", series.offset)
+            };
+
+            PlotLine {
+                data: series.data.to_string_lossy().into_owned(),
+                using: format!("{}:{}", x, y(series_idx)),
+                style,
+                title: series.label.to_owned(),
+            }
+        })
+        .collect()
+}
+
+/// Number of `set style line` definitions in `GNUPLOT_TEMPLATE`
+const NUM_LINE_STYLES: u64 = 7;
+
+/// Pick a line style (1-based, as in `GNUPLOT_TEMPLATE`) for each series based on a hash of its
+/// label, so that a configuration gets the same color in every plot regardless of the order of
+/// the inputs. When two labels hash to the same style, the one that sorts later takes the next
+/// free style.
+pub fn line_styles(labels: &[&str]) -> Vec<u64> {
+    let mut sorted_idxs: Vec<usize> = (0..labels.len()).collect();
+    sorted_idxs.sort_by_key(|idx| labels[*idx]);
+
+    let mut styles = vec![0; labels.len()];
+    let mut used: Vec<u64> = vec![];
+
+    for idx in sorted_idxs {
+        let mut style = fnv1a(labels[idx].as_bytes()) % NUM_LINE_STYLES;
+        // Only probe when there are free styles left, otherwise reuse
+        if (used.len() as u64) < NUM_LINE_STYLES {
+            while used.contains(&style) {
+                style = (style + 1) % NUM_LINE_STYLES;
+            }
+        }
+        used.push(style);
+        styles[idx] = style + 1;
+    }
+
+    styles
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` this is guaranteed to give the same result across Rust
+/// versions, so colors don't change when the tool is rebuilt.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Tera template of the plots, see `PlotProgram::program`
+pub const GNUPLOT_TEMPLATE: &str = r###"
+set terminal png notransparent rounded giant \
+  font "{{ settings.font | gnuplot_escape }},{{ settings.font_size }}" \
+  size {{ settings.width }},{{ settings.height }} {{ settings.background }}
+
+set xtics nomirror
+set ytics nomirror
+
+set style line 80 lt 0 lc rgb "#808080"
+
+set border 3 back ls 80 
+
+set style line 81 lt 0 lc rgb "#808080" lw 0.5
+
+set grid xtics
+set grid ytics
+{% if settings.minor_grid %}set grid mxtics
+set grid mytics{% endif %}
+
+set grid back ls 81
+
+set style line 1 lt 1 lc rgb "{{ settings.colors[0] }}" lw {{ settings.line_width }} pt 7 ps {{ settings.point_size }}
+set style line 2 lt 1 lc rgb "{{ settings.colors[1] }}" lw {{ settings.line_width }} pt 11 ps {{ settings.point_size }}
+set style line 3 lt 1 lc rgb "{{ settings.colors[2] }}" lw {{ settings.line_width }} pt 9 ps {{ settings.point_size }}
+set style line 4 lt 1 lc rgb "{{ settings.colors[3] }}" lw {{ settings.line_width }} pt 8 ps {{ settings.point_size }}
+set style line 5 lt 1 lc rgb "{{ settings.colors[4] }}" lw {{ settings.line_width }} pt 13 ps {{ settings.point_size }}
+set style line 6 lt 1 lc rgb "{{ settings.colors[5] }}" lw {{ settings.line_width }} pt 12 ps {{ settings.point_size }}
+set style line 7 lt 1 lc rgb "{{ settings.colors[6] }}" lw {{ settings.line_width }} pt 5 ps {{ settings.point_size }}
+
+set datafile separator ','
+
+{% if title %}set title "{{ title | gnuplot_escape }}"
+{% endif %}set xlabel "{{ xlabel | gnuplot_escape }}"
+set ylabel "{{ ylabel | gnuplot_escape }}"
+
+set xrange [0:100]
+
+{{ settings.theme_commands }}
+{{ extra_commands }}
+plot {% for line in lines %}"{{ line.data | gnuplot_escape }}" using {{ line.using }} {{ line.style }} title "{{ line.title | gnuplot_escape }}"{% if not loop.last %}, {% endif %}{% endfor %}
+"###;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_color_valid() {
+        for color in ["#A00000", "#80a00000", "dark-green", "red"] {
+            assert_eq!(check_color(color), Ok(()), "{}", color);
+        }
+    }
+
+    #[test]
+    fn check_color_invalid() {
+        for color in [
+            "",
+            "#A0000",
+            "#GG0000",
+            "red\" lw 100",
+            "red\nsystem \"rm -rf ~\"",
+            "light green",
+        ] {
+            assert!(check_color(color).is_err(), "{:?}", color);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Progress bar for processing `total_bytes` of input CSVs. Hidden when `quiet`, or when standard
+/// error is not a terminal.
+pub fn bytes_bar(total_bytes: u64, quiet: bool) -> ProgressBar {
+    bar(
+        total_bytes,
+        quiet,
+        "[{elapsed_precise}] {bar:40} {binary_bytes}/{binary_total_bytes} (ETA {eta}) {msg}",
+    )
+}
+
+/// Progress bar for rendering `num_plots` plots. Hidden when `quiet`, or when standard error is
+/// not a terminal.
+pub fn plots_bar(num_plots: u64, quiet: bool) -> ProgressBar {
+    bar(
+        num_plots,
+        quiet,
+        "[{elapsed_precise}] {bar:40} {pos}/{len} plots (ETA {eta}) {msg}",
+    )
+}
+
+/// `bytes_bar` and `plots_bar` shown together, for when inputs are processed while plots are
+/// rendered
+pub fn bars(total_bytes: u64, num_plots: u64, quiet: bool) -> (ProgressBar, ProgressBar) {
+    let multi = if quiet {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    };
+    (
+        multi.add(bytes_bar(total_bytes, quiet)),
+        multi.add(plots_bar(num_plots, quiet)),
+    )
+}
+
+fn bar(len: u64, quiet: bool, template: &str) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    ProgressBar::new(len).with_style(ProgressStyle::with_template(template).unwrap())
+}
+
+/// Log an info message without garbling the progress bar.
+pub fn info(progress: &ProgressBar, message: &str) {
+    if progress.is_hidden() {
+        log::info!("{}", message);
+    } else {
+        progress.suspend(|| log::info!("{}", message));
+    }
+}
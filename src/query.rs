@@ -0,0 +1,433 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use crate::augment;
+use crate::inputs::Input;
+use crate::table::{localize, print_table};
+
+/// Aggregates supported in queries
+const AGGREGATES: [&str; 6] = ["sum", "min", "max", "mean", "median", "count"];
+
+/// Read queries like `max(instructions)` or `sum(dirtied_host_pages where call > 500)` from
+/// standard input, one per line, and print the result of each for every input.
+///
+/// Columns are the columns of the augmented CSVs with spaces replaced by `_`, plus `call`, the
+/// 1-based row number. Conditions compare a column with a number and can be combined with `and`
+/// and `or`.
+pub fn query(inputs: &[Input]) {
+    let tables: Vec<Table> = inputs.iter().map(Table::load).collect();
+
+    let interactive = std::io::stdin().is_terminal();
+    if interactive {
+        println!("Type a query, `help`, or `quit`");
+    }
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        if interactive {
+            print!("> ");
+            std::io::stdout().flush().unwrap();
+        }
+
+        let line = match lines.next() {
+            Some(line) => line.expect("Unable to read standard input"),
+            None => break,
+        };
+        let line = line.trim();
+
+        match line {
+            "" => continue,
+            "quit" | "exit" => break,
+            "help" => {
+                print_help(&tables);
+                continue;
+            }
+            _ => {}
+        }
+
+        let query = match parse_query(line) {
+            Ok(query) => query,
+            Err(err) => {
+                eprintln!("{}", err);
+                continue;
+            }
+        };
+
+        let mut rows: Vec<Vec<String>> = vec![];
+        for table in &tables {
+            match table.eval(&query) {
+                Ok(value) => rows.push(vec![table.label.clone(), format_value(value)]),
+                Err(err) => {
+                    eprintln!("{}: {}", table.label, err);
+                    rows.clear();
+                    break;
+                }
+            }
+        }
+
+        if !rows.is_empty() {
+            print_table(&["input", line], &rows);
+        }
+    }
+}
+
+fn print_help(tables: &[Table]) {
+    println!("Queries: AGGREGATE(COLUMN) or AGGREGATE(COLUMN where CONDITION)");
+    println!("Aggregates: {}", AGGREGATES.join(", "));
+    println!(
+        "Conditions: COLUMN OP NUMBER, combined with `and` and `or`; OP is one of < <= > >= == !="
+    );
+    if let Some(table) = tables.first() {
+        println!("Columns: call, {}", table.columns.join(", "));
+    }
+}
+
+/// Integral values without a fractional part, everything else with two decimals
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        localize(&format!("{}", value as i64))
+    } else {
+        localize(&format!("{:.2}", value))
+    }
+}
+
+/// Columns of an augmented input, in a form that's easy to evaluate queries on
+struct Table {
+    label: String,
+    /// Normalized column names, see `normalize_column_name`
+    columns: Vec<String>,
+    /// Values of each column. Fields that are not numbers are NaN.
+    values: Vec<Vec<f64>>,
+    num_rows: usize,
+}
+
+impl Table {
+    fn load(input: &Input) -> Table {
+        let augmented = augment::add_cumulative_columns(&input.path);
+        let mut reader = csv::Reader::from_path(augmented.path()).unwrap();
+
+        let columns: Vec<String> = reader
+            .headers()
+            .unwrap()
+            .iter()
+            .map(normalize_column_name)
+            .collect();
+
+        let mut values: Vec<Vec<f64>> = columns.iter().map(|_| vec![]).collect();
+        let mut num_rows = 0;
+
+        for record in reader.records() {
+            let record = record.unwrap();
+            for (column, field) in values.iter_mut().zip(record.iter()) {
+                column.push(field.trim().parse().unwrap_or(f64::NAN));
+            }
+            num_rows += 1;
+        }
+
+        Table {
+            label: input.label.clone(),
+            columns,
+            values,
+            num_rows,
+        }
+    }
+
+    fn check_column(&self, column: &str) -> Result<(), String> {
+        if column == "call" || self.columns.iter().any(|name| name == column) {
+            Ok(())
+        } else {
+            Err(format!("Unknown column '{}'", column))
+        }
+    }
+
+    fn check_condition_columns(&self, condition: &Condition) -> Result<(), String> {
+        match condition {
+            Condition::Compare { column, .. } => self.check_column(column),
+            Condition::And(lhs, rhs) | Condition::Or(lhs, rhs) => {
+                self.check_condition_columns(lhs)?;
+                self.check_condition_columns(rhs)
+            }
+        }
+    }
+
+    fn value(&self, column: &str, row_idx: usize) -> Result<f64, String> {
+        if column == "call" {
+            return Ok((row_idx + 1) as f64);
+        }
+
+        let col_idx = self.columns.iter().position(|name| name == column).unwrap();
+
+        let value = self.values[col_idx][row_idx];
+        if value.is_nan() {
+            return Err(format!("Column '{}' is not numeric", column));
+        }
+        Ok(value)
+    }
+
+    fn eval(&self, query: &Query) -> Result<f64, String> {
+        // Check the columns up front so that typos are reported even when no rows are selected
+        self.check_column(&query.column)?;
+        if let Some(condition) = &query.condition {
+            self.check_condition_columns(condition)?;
+        }
+
+        let mut selected: Vec<f64> = vec![];
+
+        for row_idx in 0..self.num_rows {
+            let include = match &query.condition {
+                Some(condition) => self.eval_condition(condition, row_idx)?,
+                None => true,
+            };
+            if include {
+                selected.push(self.value(&query.column, row_idx)?);
+            }
+        }
+
+        let value = match query.aggregate.as_str() {
+            "count" => selected.len() as f64,
+            "sum" => selected.iter().sum(),
+            "min" => selected.iter().copied().fold(f64::NAN, f64::min),
+            "max" => selected.iter().copied().fold(f64::NAN, f64::max),
+            "mean" => selected.iter().sum::<f64>() / selected.len() as f64,
+            "median" => {
+                selected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let mid = selected.len() / 2;
+                if selected.is_empty() {
+                    f64::NAN
+                } else if selected.len().is_multiple_of(2) {
+                    (selected[mid - 1] + selected[mid]) / 2.0
+                } else {
+                    selected[mid]
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        Ok(value)
+    }
+
+    fn eval_condition(&self, condition: &Condition, row_idx: usize) -> Result<bool, String> {
+        Ok(match condition {
+            Condition::Compare { column, op, value } => {
+                let lhs = self.value(column, row_idx)?;
+                match op.as_str() {
+                    "<" => lhs < *value,
+                    "<=" => lhs <= *value,
+                    ">" => lhs > *value,
+                    ">=" => lhs >= *value,
+                    "==" | "=" => lhs == *value,
+                    "!=" => lhs != *value,
+                    _ => unreachable!(),
+                }
+            }
+            Condition::And(lhs, rhs) => {
+                self.eval_condition(lhs, row_idx)? && self.eval_condition(rhs, row_idx)?
+            }
+            Condition::Or(lhs, rhs) => {
+                self.eval_condition(lhs, row_idx)? || self.eval_condition(rhs, row_idx)?
+            }
+        })
+    }
+}
+
+/// Lower case, with spaces replaced by `_`, so that e.g. "dirtied host pages" can be written as
+/// `dirtied_host_pages`
+fn normalize_column_name(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "_")
+}
+
+struct Query {
+    aggregate: String,
+    column: String,
+    condition: Option<Condition>,
+}
+
+enum Condition {
+    Compare {
+        column: String,
+        op: String,
+        value: f64,
+    },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Op(String),
+    LParen,
+    RParen,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c.is_whitespace() {
+            idx += 1;
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            idx += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            idx += 1;
+        } else if "<>=!".contains(c) {
+            let mut op = c.to_string();
+            if chars.get(idx + 1) == Some(&'=') {
+                op.push('=');
+            }
+            if op == "!" {
+                return Err("Expected '!='".to_owned());
+            }
+            idx += op.len();
+            tokens.push(Token::Op(op));
+        } else if c.is_ascii_digit() || c == '.' || c == '-' {
+            let start = idx;
+            idx += 1;
+            while idx < chars.len()
+                && (chars[idx].is_ascii_alphanumeric() || "._".contains(chars[idx]))
+            {
+                idx += 1;
+            }
+            let number: String = chars[start..idx].iter().filter(|c| **c != '_').collect();
+            let number = number
+                .parse()
+                .map_err(|_| format!("Invalid number '{}'", number))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+            let ident: String = chars[start..idx].iter().collect();
+            tokens.push(Token::Ident(ident.to_lowercase()));
+        } else {
+            return Err(format!("Unexpected character '{}'", c));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_query(s: &str) -> Result<Query, String> {
+    let tokens = tokenize(s)?;
+    let mut parser = Parser { tokens, idx: 0 };
+
+    let aggregate = parser.ident()?;
+    if !AGGREGATES.contains(&aggregate.as_str()) {
+        return Err(format!(
+            "Unknown aggregate '{}', expected one of: {}",
+            aggregate,
+            AGGREGATES.join(", ")
+        ));
+    }
+
+    parser.expect(Token::LParen)?;
+    let column = parser.ident()?;
+
+    let condition = if parser.peek() == Some(&Token::Ident("where".to_owned())) {
+        parser.idx += 1;
+        Some(parser.or_condition()?)
+    } else {
+        None
+    };
+
+    parser.expect(Token::RParen)?;
+
+    if parser.idx != parser.tokens.len() {
+        return Err("Unexpected input after ')'".to_owned());
+    }
+
+    Ok(Query {
+        aggregate,
+        column,
+        condition,
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    idx: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.idx);
+        self.idx += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if *token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {:?}, found {:?}", expected, token)),
+            None => Err(format!("Expected {:?}, found end of query", expected)),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(normalize_column_name(ident)),
+            Some(token) => Err(format!("Expected a name, found {:?}", token)),
+            None => Err("Expected a name, found end of query".to_owned()),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(&Token::Ident(keyword.to_owned())) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or_condition(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and_condition()?;
+        while self.keyword("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and_condition()?));
+        }
+        Ok(condition)
+    }
+
+    fn and_condition(&mut self) -> Result<Condition, String> {
+        let mut condition = self.comparison()?;
+        while self.keyword("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.comparison()?));
+        }
+        Ok(condition)
+    }
+
+    fn comparison(&mut self) -> Result<Condition, String> {
+        if self.peek() == Some(&Token::LParen) {
+            self.idx += 1;
+            let condition = self.or_condition()?;
+            self.expect(Token::RParen)?;
+            return Ok(condition);
+        }
+
+        let column = self.ident()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op.clone(),
+            Some(token) => return Err(format!("Expected a comparison, found {:?}", token)),
+            None => return Err("Expected a comparison, found end of query".to_owned()),
+        };
+        let value = match self.next() {
+            Some(Token::Number(value)) => *value,
+            Some(token) => return Err(format!("Expected a number, found {:?}", token)),
+            None => return Err("Expected a number, found end of query".to_owned()),
+        };
+
+        Ok(Condition::Compare { column, op, value })
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use tempfile::NamedTempFile;
+
+use crate::augment;
+use crate::config::Config;
+use crate::errors;
+use crate::inputs::Input;
+use crate::plot;
+use crate::{PLOTS, TOTALS};
+
+/// An entry of the `[ratio]` table: a per-call column divided by another, plotted as
+/// `<name>.png` with non-alphanumeric characters of the name replaced by `_`
+pub struct Ratio {
+    pub name: String,
+    /// 1-based column indices, in the augmented CSVs
+    pub numerator: usize,
+    pub denominator: usize,
+}
+
+/// Names of the plots that ratios can refer to, with their 1-based column indices: the plots of
+/// the configuration, then `PLOTS`.
+pub fn operands(config: &Config) -> Vec<(&str, usize)> {
+    config
+        .plots
+        .iter()
+        .map(|plot| (plot.name.as_str(), plot.column))
+        .chain(PLOTS.iter().copied())
+        .collect()
+}
+
+/// Parse a ratio like `dirtied_host_pages / accessed_host_pages`, with plot names of `operands`
+/// as the operands.
+pub fn parse_ratio(name: &str, expr: &str, operands: &[(&str, usize)]) -> Result<Ratio, String> {
+    let (numerator, denominator) = match expr.split_once('/') {
+        Some(parts) => parts,
+        None => return Err(format!("expected `PLOT / PLOT`, found `{}`", expr)),
+    };
+    let column = |operand: &str| {
+        let operand = operand.trim();
+        operands
+            .iter()
+            .find(|(plot_name, _)| *plot_name == operand)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| format!("there's no plot `{}`", operand))
+    };
+    Ok(Ratio {
+        name: name.to_owned(),
+        numerator: column(numerator)?,
+        denominator: column(denominator)?,
+    })
+}
+
+/// The ratios of the configuration. Exits with an error when one doesn't parse.
+pub fn ratios(config: &Config) -> Vec<Ratio> {
+    let operands = operands(config);
+    config
+        .ratios
+        .iter()
+        .map(|(name, expr)| match parse_ratio(name, expr, &operands) {
+            Ok(ratio) => ratio,
+            Err(err) => {
+                errors::exit(
+                    errors::Category::Config,
+                    None,
+                    None,
+                    &format!("Invalid ratio '{}': {}", name, err),
+                );
+            }
+        })
+        .collect()
+}
+
+/// Plot each ratio with a line per input. Calls where the denominator is 0 are left out. Returns
+/// the paths of the plots.
+pub fn plot_ratios(inputs: &[Input], ratios: &[Ratio]) -> Vec<PathBuf> {
+    let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+    let line_styles = plot::line_styles(&labels);
+
+    let mut plot_paths = vec![];
+
+    for ratio in ratios {
+        let plot_name = plot::file_name_slug(&ratio.name);
+        log::info!("{}", plot_name);
+
+        let mut data_files: Vec<NamedTempFile> = vec![];
+        let mut lines: Vec<plot::PlotLine> = vec![];
+
+        for (input, line_style) in inputs.iter().zip(&line_styles) {
+            let numerators = column_values(input, ratio.numerator);
+            let denominators = column_values(input, ratio.denominator);
+
+            let mut data_file = NamedTempFile::new().unwrap();
+            for (numerator, denominator) in numerators.iter().zip(&denominators) {
+                if *denominator == 0 {
+                    // gnuplot leaves out points with NaN values
+                    writeln!(data_file, "NaN").unwrap();
+                } else {
+                    writeln!(data_file, "{}", *numerator as f64 / *denominator as f64).unwrap();
+                }
+            }
+            data_file.flush().unwrap();
+            plot::set_dry_run_placeholder(
+                data_file.path(),
+                &format!("<{} of {}>", ratio.name, input.path.display()),
+            );
+
+            lines.push(plot::PlotLine {
+                data: data_file.path().to_string_lossy().into_owned(),
+                using: "($0+1):1".to_owned(),
+                style: format!("with linespoints ls {}", line_style),
+                title: input.label.clone(),
+            });
+
+            data_files.push(data_file);
+        }
+
+        let gnuplot = plot::PlotProgram::new(&ratio.name, lines).program();
+
+        let plot_path = plot::plot_path(&plot_name, "");
+        plot::run_gnuplot(&gnuplot, &plot_path);
+        plot_paths.push(plot_path);
+    }
+
+    plot_paths
+}
+
+/// Values of a (1-based) column of an input. The cumulative columns are computed, as the inputs
+/// aren't augmented yet.
+pub fn column_values(input: &Input, col_idx: usize) -> Vec<u64> {
+    match TOTALS
+        .iter()
+        .find(|(_, total_col_idx, _)| *total_col_idx == col_idx)
+    {
+        Some((_, _, source_col_idx)) => {
+            let mut total = 0;
+            augment::try_column_values(&input.path, *source_col_idx)
+                .unwrap_or_else(|err| {
+                    errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+                })
+                .into_iter()
+                .map(|value| {
+                    total += value;
+                    total
+                })
+                .collect()
+        }
+        // Not a cumulative column, so it's in the input
+        None => augment::try_column_values(&input.path, augment::input_col_idx(col_idx).unwrap())
+            .unwrap_or_else(|err| {
+                errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+            }),
+    }
+}
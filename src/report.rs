@@ -0,0 +1,666 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use base64::Engine;
+use serde::Serialize;
+
+use crate::augment;
+use crate::errors;
+use crate::html;
+use crate::inputs::{self, Input, PipelineOptions};
+use crate::matrix;
+use crate::metrics;
+use crate::plot::{self, Series};
+use crate::table::{self, print_table};
+use crate::{INSTRUCTIONS_COL_IDX, TOTALS};
+
+/// Formats of the report written with `--report-format`
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// An Emacs org-mode document, as `report.org`
+    Org,
+    /// A web page, as `report.html`, with links to download copies of the inputs and their
+    /// augmented versions, which are written to `data/`
+    Html,
+}
+
+impl Format {
+    /// Name of the report file in its directory
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Format::Org => "report.org",
+            Format::Html => "report.html",
+        }
+    }
+}
+
+/// Directory, next to the HTML report, of the copies of the inputs it links to
+const REPORT_DATA_DIR: &str = "data";
+
+/// Index of the suites written by `--all`
+pub const INDEX_FILE: &str = "index.org";
+
+/// Write a report with a table of the totals of each series and links to the plots, in `dir` when
+/// given. `series` must have augmented CSVs as data, and `inputs` are the inputs of the series,
+/// for notes on how they were prepared with `options`. Returns the path of the report, and the paths of the other
+/// files it links to that are written with it.
+pub fn write_report(
+    format: Format,
+    series: &[Series],
+    inputs: &[Input],
+    options: &PipelineOptions,
+    plot_paths: &[PathBuf],
+    dir: Option<&Path>,
+) -> (PathBuf, Vec<PathBuf>) {
+    let summary = summary(series, inputs, options);
+
+    let dir = dir.unwrap_or(Path::new(""));
+    let path = dir.join(format.file_name());
+    let mut data_paths = vec![];
+    let report = match format {
+        Format::Org => org_report(&summary, plot_paths, dir),
+        Format::Html => {
+            data_paths = write_report_data(series, inputs, dir);
+            html_report(&summary, series, &data_paths, plot_paths, dir)
+        }
+    };
+
+    plot::write_output(&path, report.as_bytes()).expect("Unable to write report");
+    log::info!("Wrote {}", path.display());
+    (path, data_paths)
+}
+
+/// Copy the input and the augmented CSV of each series to `REPORT_DATA_DIR` in `dir`, as
+/// `<label>.csv` and `<label>_augmented.csv`. Returns the paths of the copies, two per series.
+fn write_report_data(series: &[Series], inputs: &[Input], dir: &Path) -> Vec<PathBuf> {
+    let data_dir = dir.join(REPORT_DATA_DIR);
+    std::fs::create_dir_all(&data_dir).unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Output,
+            Some(&data_dir),
+            None,
+            &format!("Unable to create {}: {}", data_dir.display(), err),
+        )
+    });
+
+    let mut paths = vec![];
+    for (series, input) in series.iter().zip(inputs) {
+        let slug = plot::file_name_slug(series.label);
+        for (source, path) in [
+            (
+                input.original.as_path(),
+                data_dir.join(format!("{}.csv", slug)),
+            ),
+            (
+                series.data,
+                data_dir.join(format!("{}_augmented.csv", slug)),
+            ),
+        ] {
+            let contents = std::fs::read(source).unwrap_or_else(|err| {
+                errors::exit(
+                    errors::Category::Input,
+                    Some(source),
+                    None,
+                    &format!("Unable to read {}: {}", source.display(), err),
+                )
+            });
+            plot::write_output(&path, &contents).unwrap_or_else(|err| {
+                errors::exit(
+                    errors::Category::Output,
+                    Some(&path),
+                    None,
+                    &format!("Unable to write {}: {}", path.display(), err),
+                )
+            });
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Write the tables and notes of the report, a table of the inputs, and the plots (as data URLs)
+/// to an HTML file at `path`, without references to other files, to be sent around. See
+/// `write_report`.
+pub fn write_bundle(
+    path: &Path,
+    series: &[Series],
+    inputs: &[Input],
+    options: &PipelineOptions,
+    plot_paths: &[PathBuf],
+) {
+    let summary = summary(series, inputs, options);
+
+    let mut body = format!(
+        "<p>Generated by {} {} at {}</p>\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        humantime::format_rfc3339_seconds(SystemTime::now())
+    );
+
+    body.push_str("<h2>Inputs</h2>\n<table>\n");
+    body.push_str(&html::table_row(
+        "th",
+        ["input", "file", "description"].iter().copied(),
+    ));
+    for (series, input) in series.iter().zip(inputs) {
+        let file = input.path.to_string_lossy();
+        body.push_str(&html::table_row(
+            "td",
+            [
+                series.label,
+                &file,
+                input.description.as_deref().unwrap_or(""),
+            ]
+            .iter()
+            .copied(),
+        ));
+    }
+    body.push_str("</table>\n");
+
+    body.push_str("<h2>Totals</h2>\n<table>\n");
+    body.push_str(&html::table_row(
+        "th",
+        summary.headers.iter().map(String::as_str),
+    ));
+    for row in &summary.rows {
+        body.push_str(&html::table_row("td", row.iter().map(String::as_str)));
+    }
+    body.push_str("</table>\n");
+    for note in &summary.notes {
+        body.push_str(&format!("<p>{}</p>\n", html::escape(note)));
+    }
+
+    body.push_str("<h2>Plots</h2>\n");
+    for plot_path in plot_paths {
+        let media_type = match plot_path.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => "image/png",
+            Some("svg") => "image/svg+xml",
+            _ => continue,
+        };
+        let image = match std::fs::read(plot_path) {
+            Ok(image) => image,
+            Err(err) => {
+                log::warn!("Leaving {} out of the bundle: {}", plot_path.display(), err);
+                continue;
+            }
+        };
+        body.push_str(&format!(
+            "<h3>{}</h3>\n<img src=\"data:{};base64,{}\" alt=\"{}\">\n",
+            html::escape(&plot_title(plot_path)),
+            media_type,
+            base64::engine::general_purpose::STANDARD.encode(image),
+            html::escape(&plot_title(plot_path))
+        ));
+    }
+
+    plot::write_output(
+        path,
+        html::page("Canister perf report", "", &body).as_bytes(),
+    )
+    .unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Output,
+            Some(path),
+            None,
+            &format!("Unable to write {}: {}", path.display(), err),
+        )
+    });
+    log::info!("Wrote {}", path.display());
+}
+
+/// The table of totals of reports, with notes on how the inputs were prepared
+struct Summary {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    notes: Vec<String>,
+}
+
+/// The totals of each series, and the numbers of their calls. Series that `run` ran drun for
+/// also get the wall time and peak RSS of drun.
+fn summary(series: &[Series], inputs: &[Input], options: &PipelineOptions) -> Summary {
+    let usage: Vec<Option<matrix::Usage>> = series
+        .iter()
+        .map(|series| matrix::usage(series.label))
+        .collect();
+    let with_usage = usage.iter().any(Option::is_some);
+    let mut headers: Vec<String> = ["input", "calls"]
+        .iter()
+        .map(|header| header.to_string())
+        .chain(
+            TOTALS
+                .iter()
+                .map(|(name, col_idx, _)| metrics::display_name(*col_idx, name)),
+        )
+        .collect();
+    if with_usage {
+        headers.extend(["drun wall time".to_owned(), "drun peak RSS".to_owned()]);
+    }
+
+    let totals: Vec<Vec<u64>> = series
+        .iter()
+        .map(|series| {
+            let col_idxs: Vec<usize> = TOTALS.iter().map(|(_, col_idx, _)| *col_idx).collect();
+            augment::last_values(series.data, &col_idxs)
+        })
+        .collect();
+
+    let rows: Vec<Vec<String>> = series
+        .iter()
+        .zip(&totals)
+        .zip(&usage)
+        .map(|((series, input_totals), usage)| {
+            let num_calls = augment::try_column_values(series.data, INSTRUCTIONS_COL_IDX)
+                .unwrap_or_else(|err| {
+                    errors::exit(errors::Category::Input, Some(series.data), None, &err)
+                })
+                .len();
+            let mut row = vec![
+                series.label.to_owned(),
+                table::localize(&num_calls.to_string()),
+            ];
+            for (total_idx, total) in input_totals.iter().enumerate() {
+                let first = totals.first().map(|first| first[total_idx]);
+                row.push(format_total(TOTALS[total_idx].1, *total, first));
+            }
+            match usage {
+                Some(usage) => {
+                    row.push(usage.format_wall_time());
+                    row.push(usage.format_peak_rss());
+                }
+                None if with_usage => row.extend(["-".to_owned(), "-".to_owned()]),
+                None => {}
+            }
+            row
+        })
+        .collect();
+
+    let mut notes: Vec<String> = options
+        .trim
+        .map(|trim| {
+            let affected = match trim.mode {
+                inputs::TrimMode::Clamp => "values clamped",
+                inputs::TrimMode::Drop => "calls left out",
+            };
+            let counts: Vec<String> = series
+                .iter()
+                .zip(inputs)
+                .map(|(series, input)| format!("{}: {}", series.label, input.trimmed))
+                .collect();
+            vec![format!(
+                "Outliers below percentile {} or above percentile {} trimmed, {}: {}",
+                100.0 - trim.percentile,
+                trim.percentile,
+                affected,
+                counts.join(", ")
+            )]
+        })
+        .unwrap_or_default();
+
+    for (series, input) in series.iter().zip(inputs) {
+        if let Some(description) = &input.description {
+            notes.push(format!("{}: {}", series.label, description));
+        }
+        if !input.setup_calls.is_empty() && options.setup_phase != inputs::SetupPhase::Mark {
+            notes.push(format!(
+                "{}: {} install/upgrade calls left out{}",
+                series.label,
+                table::localize(&input.setup_calls.len().to_string()),
+                if input.setup_csv.is_some() {
+                    ", plotted in the setup_ plots"
+                } else {
+                    ""
+                }
+            ));
+        }
+        for filled in &input.filled {
+            notes.push(format!(
+                "{}: {} missing values of {}, {}",
+                series.label,
+                filled.count,
+                metrics::display_name(filled.col_idx, &filled.header),
+                filled.missing.description()
+            ));
+        }
+    }
+
+    Summary {
+        headers,
+        rows,
+        notes,
+    }
+}
+
+/// How `report diff` shows the plots that changed
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiffMode {
+    /// The plot of the first directory left of the plot of the second
+    SideBySide,
+    /// The two plots on top of each other, half transparent, so that what moved is doubled
+    Blend,
+}
+
+/// Pair up the plots of two output directories by file name, print which of them changed, and
+/// render each pair that changed as `diff_<plot>.png` (see `plot::plot_path`), showing the two
+/// plots as `mode` says. Plots are compared byte by byte, which is enough as gnuplot renders the
+/// same data the same way. Thumbnails and the outputs of earlier diffs are left out.
+pub fn diff(dir_a: &Path, dir_b: &Path, mode: DiffMode) {
+    let plots_a = plot_files(dir_a);
+    let plots_b = plot_files(dir_b);
+    let mut names: Vec<&String> = plots_a.iter().chain(&plots_b).collect();
+    names.sort();
+    names.dedup();
+
+    let mut rows: Vec<Vec<String>> = vec![];
+    let mut num_changed = 0;
+    for name in names {
+        let path_a = dir_a.join(name);
+        let path_b = dir_b.join(name);
+        let status = match (plots_a.contains(name), plots_b.contains(name)) {
+            (true, false) => format!("only in {}", dir_a.display()),
+            (false, true) => format!("only in {}", dir_b.display()),
+            _ => {
+                let image_a = std::fs::read(&path_a).expect("Unable to read plot");
+                let image_b = std::fs::read(&path_b).expect("Unable to read plot");
+                if image_a == image_b {
+                    "unchanged".to_owned()
+                } else {
+                    num_changed += 1;
+                    let plot_name = Path::new(name).file_stem().unwrap().to_string_lossy();
+                    let diff_path = plot::plot_path(&plot_name, "diff_");
+                    let gnuplot = diff_program(
+                        (&path_a, png_size(&image_a)),
+                        (&path_b, png_size(&image_b)),
+                        mode,
+                    );
+                    if plot::run_gnuplot(&gnuplot, &diff_path) {
+                        format!("changed, see {}", diff_path.display())
+                    } else {
+                        "changed".to_owned()
+                    }
+                }
+            }
+        };
+        rows.push(vec![name.clone(), status]);
+    }
+
+    if rows.is_empty() {
+        println!("No plots in {} or {}", dir_a.display(), dir_b.display());
+        return;
+    }
+    print_table(&["plot", "status"], &rows);
+    println!("{} of {} plots changed", num_changed, rows.len());
+}
+
+/// File names of the PNG plots in a directory, except thumbnails and diffs
+fn plot_files(dir: &Path) -> Vec<String> {
+    let entries = std::fs::read_dir(dir).unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Input,
+            Some(dir),
+            None,
+            &format!("Unable to read {}: {}", dir.display(), err),
+        )
+    });
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| {
+            name.ends_with(".png") && !name.ends_with("_thumb.png") && !name.starts_with("diff_")
+        })
+        .collect()
+}
+
+/// Width and height of a PNG image, from its header
+fn png_size(image: &[u8]) -> Option<(u32, u32)> {
+    // The signature, then the length and type of the IHDR chunk, which starts with the size
+    if image.len() < 24 || &image[12..16] != b"IHDR" {
+        return None;
+    }
+    let be_u32 = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    Some((be_u32(&image[16..20]), be_u32(&image[20..24])))
+}
+
+/// Variables of `DIFF_TEMPLATE`
+#[derive(Serialize)]
+struct Diff<'a> {
+    side_by_side: bool,
+    width: u32,
+    height: u32,
+    a: &'a str,
+    b: &'a str,
+}
+
+fn diff_program(
+    (path_a, size_a): (&Path, Option<(u32, u32)>),
+    (path_b, size_b): (&Path, Option<(u32, u32)>),
+    mode: DiffMode,
+) -> plot::GnuplotProgram {
+    // The size of the plots of the default preset when the images aren't valid PNGs, which
+    // gnuplot reports
+    let (width, height) = match (size_a, size_b) {
+        (Some((width_a, height_a)), Some((width_b, height_b))) => {
+            (width_a.max(width_b), height_a.max(height_b))
+        }
+        _ => (1200, 960),
+    };
+    let side_by_side = mode == DiffMode::SideBySide;
+    plot::GnuplotProgram::new(
+        DIFF_TEMPLATE,
+        &Diff {
+            side_by_side,
+            width: if side_by_side { width * 2 } else { width },
+            height,
+            a: &path_a.to_string_lossy(),
+            b: &path_b.to_string_lossy(),
+        },
+    )
+}
+
+const DIFF_TEMPLATE: &str = r###"
+set terminal png truecolor notransparent font "{{ settings.font | gnuplot_escape }},14" \
+  size {{ width }},{{ height }} {{ settings.background }}
+
+unset border
+unset tics
+unset key
+set margins 0, 0, 0, 0
+set size ratio -1
+{% if side_by_side %}
+set multiplot layout 1,2
+plot "{{ a | gnuplot_escape }}" binary filetype=png with rgbimage
+plot "{{ b | gnuplot_escape }}" binary filetype=png with rgbimage
+unset multiplot
+{% else %}
+plot "{{ a | gnuplot_escape }}" binary filetype=png using 1:2:3:(128) with rgbalpha, \
+  "{{ b | gnuplot_escape }}" binary filetype=png using 1:2:3:(128) with rgbalpha
+{% endif %}
+"###;
+
+/// The total of a (1-based) column in a row, with its ratio to the total of the first input when
+/// they differ
+fn format_total(col_idx: usize, total: u64, first: Option<u64>) -> String {
+    let value = metrics::format_value(col_idx, total);
+    match first {
+        Some(first) if first != 0 && first != total => {
+            let ratio = table::localize(&format!("{:.2}", total as f64 / first as f64));
+            format!("{} ({}x)", value, ratio)
+        }
+        _ => value,
+    }
+}
+
+/// Write an index of suites, with links to the reports of the suites that have one and to every
+/// plot, to `path`.
+pub fn write_index(path: &Path, suites: &[(&str, Vec<PathBuf>)]) {
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut index = String::new();
+    index.push_str("#+TITLE: Canister perf suites\n");
+    index.push_str("#+STARTUP: inlineimages\n");
+
+    for (name, plot_paths) in suites {
+        index.push_str(&format!("\n* {}\n", name));
+        let suite_dir = plot_paths.first().and_then(|plot_path| plot_path.parent());
+        for (format, title) in [(Format::Org, "Report"), (Format::Html, "HTML report")].iter() {
+            let report_path = suite_dir.map(|suite_dir| suite_dir.join(format.file_name()));
+            if let Some(report_path) = report_path.filter(|report_path| report_path.exists()) {
+                index.push_str(&format!(
+                    "\n[[file:{}][{}]]\n",
+                    relative_to(&report_path, dir).display(),
+                    title
+                ));
+            }
+        }
+        for plot_path in plot_paths {
+            index.push_str(&format!(
+                "\n** {}\n\n{}\n",
+                plot_title(plot_path),
+                plot_link(plot_path, dir)
+            ));
+        }
+    }
+
+    plot::write_output(path, index.as_bytes()).expect("Unable to write index");
+    log::info!("Wrote {}", path.display());
+}
+
+/// The HTML report, with the copies of the inputs and augmented CSVs of `write_report_data` as
+/// downloads
+fn html_report(
+    summary: &Summary,
+    series: &[Series],
+    data_paths: &[PathBuf],
+    plot_paths: &[PathBuf],
+    dir: &Path,
+) -> String {
+    let download_link = |path: &Path| {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        format!(
+            "<a href=\"{}\" download>{}</a>",
+            url_path(relative_to(path, dir)),
+            html::escape(&file_name)
+        )
+    };
+
+    let mut body = String::from("<h2>Totals</h2>\n<table>\n");
+    body.push_str(&html::table_row(
+        "th",
+        summary.headers.iter().map(String::as_str),
+    ));
+    for row in &summary.rows {
+        body.push_str(&html::table_row("td", row.iter().map(String::as_str)));
+    }
+    body.push_str("</table>\n");
+    for note in &summary.notes {
+        body.push_str(&format!("<p>{}</p>\n", html::escape(note)));
+    }
+
+    // The links are already escaped, so the rows are written without `html::table_row`
+    body.push_str("<h2>Data</h2>\n<table>\n");
+    body.push_str(&html::table_row(
+        "th",
+        ["input", "CSV", "augmented CSV"].iter().copied(),
+    ));
+    for (series, paths) in series.iter().zip(data_paths.chunks(2)) {
+        body.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            html::escape(series.label),
+            download_link(&paths[0]),
+            download_link(&paths[1])
+        ));
+    }
+    body.push_str("</table>\n");
+
+    body.push_str("<h2>Plots</h2>\n");
+    for path in plot_paths {
+        let thumbnail_path = plot::thumbnail_path(path);
+        let image_path = if thumbnail_path.exists() {
+            &thumbnail_path
+        } else {
+            path
+        };
+        body.push_str(&format!(
+            "<h3>{}</h3>\n<a href=\"{}\"><img src=\"{}\" alt=\"{}\"></a>\n",
+            html::escape(&plot_title(path)),
+            url_path(relative_to(path, dir)),
+            url_path(relative_to(image_path, dir)),
+            html::escape(&plot_title(path))
+        ));
+    }
+
+    html::page("Canister perf report", "", &body)
+}
+
+fn org_report(summary: &Summary, plot_paths: &[PathBuf], dir: &Path) -> String {
+    let Summary {
+        headers,
+        rows,
+        notes,
+    } = summary;
+    let mut report = String::new();
+    report.push_str("#+TITLE: Canister perf report\n");
+    report.push_str("#+STARTUP: inlineimages\n\n");
+
+    report.push_str("* Totals\n\n");
+    report.push_str(&org_row(headers.iter().map(|header| header.to_string())));
+    report.push_str(&format!("|{}|\n", vec!["-"; headers.len()].join("+")));
+    for row in rows {
+        report.push_str(&org_row(row.iter().cloned()));
+    }
+    for note in notes {
+        report.push_str(&format!("\n{}\n", note));
+    }
+
+    report.push_str("\n* Plots\n");
+    for path in plot_paths {
+        report.push_str(&format!(
+            "\n** {}\n\n{}\n",
+            plot_title(path),
+            plot_link(path, dir)
+        ));
+    }
+
+    report
+}
+
+/// An org link to a plot from a document in `dir`. With a thumbnail the link shows the
+/// thumbnail, linking to the plot.
+fn plot_link(path: &Path, dir: &Path) -> String {
+    let thumbnail_path = plot::thumbnail_path(path);
+    if thumbnail_path.exists() {
+        format!(
+            "[[file:{}][file:{}]]",
+            relative_to(path, dir).display(),
+            relative_to(&thumbnail_path, dir).display()
+        )
+    } else {
+        format!("[[file:{}]]", relative_to(path, dir).display())
+    }
+}
+
+/// A relative URL of a path, with its components escaped
+fn url_path(path: &Path) -> String {
+    let components: Vec<String> = path
+        .to_string_lossy()
+        .split('/')
+        .map(html::url_escape)
+        .collect();
+    components.join("/")
+}
+
+/// `path` relative to `dir`, when it's in `dir`
+fn relative_to<'a>(path: &'a Path, dir: &Path) -> &'a Path {
+    path.strip_prefix(dir).unwrap_or(path)
+}
+
+/// A row of an org table. `|`s in cells are escaped, as they would end the cell.
+fn org_row(cells: impl Iterator<Item = String>) -> String {
+    let cells: Vec<String> = cells.map(|cell| cell.replace('|', "\\vert{}")).collect();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+/// Title of a plot in reports: its file name without the extension, with `_`s as spaces
+fn plot_title(path: &Path) -> String {
+    path.file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().replace('_', " "))
+}
@@ -0,0 +1,182 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use tempfile::NamedTempFile;
+
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::plot;
+use crate::INSTRUCTIONS_COL_IDX;
+
+/// Plot the rolling p95 (solid) and p99 (dashed) of per-call instructions of each input over
+/// the last `window` calls. The first `window - 1` calls use the calls available so far.
+/// Returns the path of the plot.
+pub fn plot_rolling_percentiles(inputs: &[Input], window: usize) -> PathBuf {
+    let window = window.max(1);
+    let plot_name = "rolling_percentiles_instructions";
+    log::info!("{}", plot_name);
+
+    let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+    let line_styles = plot::line_styles(&labels);
+
+    let mut data_files: Vec<NamedTempFile> = vec![];
+    let mut lines: Vec<plot::PlotLine> = vec![];
+
+    for (input, line_style) in inputs.iter().zip(line_styles) {
+        let values =
+            augment::try_column_values(&input.path, INSTRUCTIONS_COL_IDX).unwrap_or_else(|err| {
+                errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+            });
+
+        let mut data_file = NamedTempFile::new().unwrap();
+        for end in 1..=values.len() {
+            let mut window_values = values[end.saturating_sub(window)..end].to_vec();
+            window_values.sort_unstable();
+            writeln!(
+                data_file,
+                "{},{}",
+                percentile(&window_values, 0.95),
+                percentile(&window_values, 0.99)
+            )
+            .unwrap();
+        }
+        data_file.flush().unwrap();
+        plot::set_dry_run_placeholder(
+            data_file.path(),
+            &format!("<rolling percentiles of {}>", input.path.display()),
+        );
+
+        let path = data_file.path().to_string_lossy();
+        lines.push(plot::PlotLine {
+            data: path.to_string(),
+            using: "($0+1):1".to_owned(),
+            style: format!("with lines ls {} lw 2", line_style),
+            title: format!("{} p95", input.label),
+        });
+        lines.push(plot::PlotLine {
+            data: path.to_string(),
+            using: "($0+1):2".to_owned(),
+            style: format!("with lines ls {} lw 2 dt 2", line_style),
+            title: format!("{} p99", input.label),
+        });
+
+        data_files.push(data_file);
+    }
+
+    let gnuplot = plot::PlotProgram::new(
+        &format!("instructions (rolling p95/p99 over {} calls)", window),
+        lines,
+    )
+    .program();
+
+    let plot_path = plot::plot_path(plot_name, "");
+    plot::run_gnuplot(&gnuplot, &plot_path);
+    plot_path
+}
+
+/// How `plot_smoothed` smooths per-call values
+#[derive(Clone, Copy)]
+pub enum Smoothing {
+    /// Mean of the last this many calls
+    Window(usize),
+    /// Exponential moving average with this weight (in (0, 1]) of the newest call. Follows level
+    /// shifts faster than a window of similar smoothness.
+    Ema(f64),
+}
+
+impl Smoothing {
+    fn description(self) -> String {
+        match self {
+            Smoothing::Window(window) => format!("mean over {} calls", window),
+            Smoothing::Ema(alpha) => format!("EMA, alpha {}", alpha),
+        }
+    }
+
+    /// Smoothed values of `values`. The first `window - 1` calls of a window use the calls
+    /// available so far, and the EMA starts at the first value.
+    fn apply(self, values: &[u64]) -> Vec<f64> {
+        match self {
+            Smoothing::Window(window) => {
+                let window = window.max(1);
+                let mut sum = 0;
+                (0..values.len())
+                    .map(|idx| {
+                        sum += values[idx];
+                        if idx >= window {
+                            sum -= values[idx - window];
+                        }
+                        sum as f64 / (idx + 1).min(window) as f64
+                    })
+                    .collect()
+            }
+            Smoothing::Ema(alpha) => {
+                let mut ema = None;
+                values
+                    .iter()
+                    .map(|value| {
+                        let value = *value as f64;
+                        let next = ema.map_or(value, |ema| alpha * value + (1.0 - alpha) * ema);
+                        ema = Some(next);
+                        next
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Plot per-call instructions of each input smoothed with `smoothing`. Returns the path of the
+/// plot.
+pub fn plot_smoothed(inputs: &[Input], smoothing: Smoothing) -> PathBuf {
+    let plot_name = "smoothed_instructions";
+    log::info!("{}", plot_name);
+
+    let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+    let line_styles = plot::line_styles(&labels);
+
+    let mut data_files: Vec<NamedTempFile> = vec![];
+    let mut lines: Vec<plot::PlotLine> = vec![];
+
+    for (input, line_style) in inputs.iter().zip(line_styles) {
+        let values =
+            augment::try_column_values(&input.path, INSTRUCTIONS_COL_IDX).unwrap_or_else(|err| {
+                errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+            });
+
+        let mut data_file = NamedTempFile::new().unwrap();
+        for value in smoothing.apply(&values) {
+            writeln!(data_file, "{}", value).unwrap();
+        }
+        data_file.flush().unwrap();
+        plot::set_dry_run_placeholder(
+            data_file.path(),
+            &format!("<smoothed {}>", input.path.display()),
+        );
+
+        lines.push(plot::PlotLine {
+            data: data_file.path().to_string_lossy().into_owned(),
+            using: "($0+1):1".to_owned(),
+            style: format!("with lines ls {} lw 2", line_style),
+            title: input.label.clone(),
+        });
+
+        data_files.push(data_file);
+    }
+
+    let gnuplot = plot::PlotProgram::new(
+        &format!("instructions ({})", smoothing.description()),
+        lines,
+    )
+    .program();
+
+    let plot_path = plot::plot_path(plot_name, "");
+    plot::run_gnuplot(&gnuplot, &plot_path);
+    plot_path
+}
+
+/// Nearest-rank percentile of sorted values
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
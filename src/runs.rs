@@ -0,0 +1,432 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::augment;
+use crate::errors;
+use crate::plot::{self, Series};
+use crate::table::{localize, print_table};
+use crate::{DIRTIED_TOTAL_COL_IDX, INSTRUCTIONS_TOTAL_COL_IDX};
+
+/// Directory (relative to the working directory) that archived runs are stored in. Every run is a
+/// subdirectory named after its tag, with the augmented CSVs in `data/`, the generated images in
+/// `plots/`, and a `metadata.csv` with one record per input file.
+pub const RUNS_DIR: &str = "runs";
+
+const METADATA_FILE: &str = "metadata.csv";
+
+/// Marker file in a run directory that marks the run as a baseline
+const BASELINE_FILE: &str = "BASELINE";
+
+/// One input file of an archived run, as recorded in the run's `metadata.csv`.
+pub struct RunInput {
+    pub label: String,
+    /// Path of the CSV the run was generated from, as it was given to the tool
+    pub input: String,
+    /// Path of the augmented CSV inside the run directory
+    pub data: PathBuf,
+    pub total_instructions: u64,
+    pub total_dirtied_host_pages: u64,
+}
+
+pub struct Run {
+    pub tag: String,
+    pub timestamp: SystemTime,
+    pub baseline: bool,
+    pub inputs: Vec<RunInput>,
+}
+
+/// Copy augmented inputs and generated plots into a new run directory named `tag`. `inputs` are
+/// the paths of the inputs as they were given. The augmented CSVs are named after the position and
+/// label of their input, as inputs may have the same file name.
+pub fn archive(tag: &str, inputs: &[&Path], series: &[Series], plots: &[PathBuf]) {
+    let run_dir = run_dir(tag);
+    if run_dir.exists() {
+        errors::exit(
+            errors::Category::Runs,
+            Some(&run_dir),
+            None,
+            &format!("Run '{}' already exists in {}", tag, run_dir.display()),
+        );
+    }
+
+    let data_dir = run_dir.join("data");
+    let plots_dir = run_dir.join("plots");
+    std::fs::create_dir_all(&data_dir).expect("Unable to create run data directory");
+    std::fs::create_dir_all(&plots_dir).expect("Unable to create run plots directory");
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let mut metadata = csv::Writer::from_path(run_dir.join(METADATA_FILE)).unwrap();
+    metadata
+        .write_record([
+            "label",
+            "input",
+            "data",
+            "timestamp",
+            "total instructions",
+            "total dirtied host pages",
+        ])
+        .unwrap();
+
+    for (input_idx, (input, series)) in inputs.iter().zip(series.iter()).enumerate() {
+        let file_name = format!(
+            "{}_{}.csv",
+            input_idx + 1,
+            plot::file_name_slug(series.label)
+        );
+        std::fs::copy(series.data, data_dir.join(&file_name))
+            .expect("Unable to copy augmented CSV to run directory");
+
+        let (total_instructions, total_dirtied_host_pages) = final_totals(series.data);
+
+        metadata
+            .write_record([
+                series.label.to_owned(),
+                input.to_string_lossy().into_owned(),
+                Path::new("data")
+                    .join(file_name)
+                    .to_string_lossy()
+                    .into_owned(),
+                timestamp.to_string(),
+                total_instructions.to_string(),
+                total_dirtied_host_pages.to_string(),
+            ])
+            .unwrap();
+    }
+
+    metadata.flush().unwrap();
+
+    for plot in plots {
+        std::fs::copy(plot, plots_dir.join(plot.file_name().unwrap()))
+            .expect("Unable to copy plot to run directory");
+    }
+
+    log::info!("Archived run '{}' in {}", tag, run_dir.display());
+}
+
+/// Distinct labels of the inputs of the runs, in the order they first appear
+pub fn labels<'a>(runs: impl IntoIterator<Item = &'a Run>) -> Vec<&'a str> {
+    let mut labels: Vec<&str> = vec![];
+    for run in runs {
+        for input in &run.inputs {
+            if !labels.contains(&input.label.as_str()) {
+                labels.push(&input.label);
+            }
+        }
+    }
+    labels
+}
+
+/// The inputs of a run with the label. Inputs with the same label in a run are repetitions of the
+/// same configuration.
+pub fn repetitions<'a>(run: &'a Run, label: &str) -> Vec<&'a RunInput> {
+    run.inputs
+        .iter()
+        .filter(|input| input.label == label)
+        .collect()
+}
+
+/// Mean over the repetitions of the final values of the given columns
+pub fn mean_totals(inputs: &[&RunInput], col_idxs: &[usize]) -> Vec<f64> {
+    let mut sums = vec![0.0; col_idxs.len()];
+    for input in inputs {
+        for (sum, total) in sums
+            .iter_mut()
+            .zip(augment::last_values(&input.data, col_idxs))
+        {
+            *sum += total as f64;
+        }
+    }
+    sums.iter().map(|sum| sum / inputs.len() as f64).collect()
+}
+
+/// Print a table of all archived runs, oldest first.
+pub fn list() {
+    let runs = load_all();
+
+    if runs.is_empty() {
+        println!("No archived runs in {}", RUNS_DIR);
+        return;
+    }
+
+    let mut rows: Vec<Vec<String>> = vec![];
+    for run in &runs {
+        for (input_idx, input) in run.inputs.iter().enumerate() {
+            let (tag, timestamp, baseline) = if input_idx == 0 {
+                (
+                    run.tag.clone(),
+                    humantime::format_rfc3339_seconds(run.timestamp).to_string(),
+                    if run.baseline { "yes" } else { "" }.to_owned(),
+                )
+            } else {
+                (String::new(), String::new(), String::new())
+            };
+
+            rows.push(vec![
+                tag,
+                timestamp,
+                baseline,
+                format!("{} ({})", input.input, input.label),
+                localize(&input.total_instructions.to_string()),
+                localize(&input.total_dirtied_host_pages.to_string()),
+            ]);
+        }
+    }
+
+    print_table(
+        &[
+            "tag",
+            "timestamp",
+            "baseline",
+            "input",
+            "total instructions",
+            "total dirtied host pages",
+        ],
+        &rows,
+    );
+}
+
+/// Load every run in the archive, sorted by timestamp. Exits with an error message when a run
+/// can't be loaded.
+pub fn load_all() -> Vec<Run> {
+    try_load_all().unwrap_or_else(|err| errors::exit(errors::Category::Runs, None, None, &err))
+}
+
+/// Like `load_all`, but returns an error message when a run can't be loaded.
+pub fn try_load_all() -> Result<Vec<Run>, String> {
+    let entries = match std::fs::read_dir(RUNS_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),
+    };
+
+    let mut runs = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|err| format!("Unable to read {}: {}", RUNS_DIR, err))?;
+        if entry.path().join(METADATA_FILE).exists() {
+            runs.push(try_load(&entry.file_name().to_string_lossy())?);
+        }
+    }
+
+    runs.sort_by(|r1, r2| r1.timestamp.cmp(&r2.timestamp).then(r1.tag.cmp(&r2.tag)));
+    Ok(runs)
+}
+
+/// Load the run with the given tag. Exits with an error message when the run does not exist or
+/// can't be loaded.
+pub fn load(tag: &str) -> Run {
+    try_load(tag).unwrap_or_else(|err| errors::exit(errors::Category::Runs, None, None, &err))
+}
+
+/// Like `load`, but returns an error message when the run does not exist or can't be loaded.
+pub fn try_load(tag: &str) -> Result<Run, String> {
+    let run_dir = run_dir(tag);
+    let metadata_path = run_dir.join(METADATA_FILE);
+
+    let mut reader = csv::Reader::from_path(&metadata_path)
+        .map_err(|_| format!("Run '{}' not found in {}", tag, RUNS_DIR))?;
+
+    let mut timestamp = UNIX_EPOCH;
+    let mut inputs = vec![];
+
+    let invalid = |err: &dyn std::fmt::Display| {
+        format!("Invalid metadata {}: {}", metadata_path.display(), err)
+    };
+    for record in reader.records() {
+        let record = record.map_err(|err| invalid(&err))?;
+        if record.len() < 6 {
+            return Err(invalid(&format!(
+                "expected 6 fields, found {}",
+                record.len()
+            )));
+        }
+        let seconds = record[3].parse().map_err(|err| invalid(&err))?;
+        timestamp = UNIX_EPOCH + Duration::from_secs(seconds);
+        inputs.push(RunInput {
+            label: record[0].to_owned(),
+            input: record[1].to_owned(),
+            data: run_dir.join(&record[2]),
+            total_instructions: record[4].parse().map_err(|err| invalid(&err))?,
+            total_dirtied_host_pages: record[5].parse().map_err(|err| invalid(&err))?,
+        });
+    }
+
+    Ok(Run {
+        tag: tag.to_owned(),
+        timestamp,
+        baseline: run_dir.join(BASELINE_FILE).exists(),
+        inputs,
+    })
+}
+
+/// Add or remove the baseline mark of a run.
+pub fn set_baseline(tag: &str, baseline: bool) {
+    // Check that the run exists
+    load(tag);
+
+    let marker = run_dir(tag).join(BASELINE_FILE);
+    if baseline {
+        std::fs::write(&marker, "").expect("Unable to write baseline marker");
+        log::info!("Marked run '{}' as a baseline", tag);
+    } else if marker.exists() {
+        std::fs::remove_file(&marker).expect("Unable to remove baseline marker");
+        log::info!("Run '{}' is no longer a baseline", tag);
+    }
+}
+
+/// Delete runs that are not among the `keep_last` most recent non-baseline runs, or are older
+/// than `max_age`. Baselines are never deleted.
+pub fn gc(keep_last: Option<usize>, max_age: Option<Duration>, dry_run: bool) {
+    if keep_last.is_none() && max_age.is_none() {
+        errors::exit(
+            errors::Category::Usage,
+            None,
+            None,
+            "runs gc: at least one of --keep-last and --max-age is required",
+        );
+    }
+
+    let now = SystemTime::now();
+
+    let mut runs: Vec<Run> = load_all().into_iter().filter(|run| !run.baseline).collect();
+
+    // Newest first, so that the ones to keep come first
+    runs.reverse();
+
+    for (run_idx, run) in runs.iter().enumerate() {
+        let too_many = keep_last.is_some_and(|keep_last| run_idx >= keep_last);
+        let too_old = max_age
+            .is_some_and(|max_age| now.duration_since(run.timestamp).unwrap_or_default() > max_age);
+
+        if !too_many && !too_old {
+            continue;
+        }
+
+        if dry_run {
+            println!("Would delete run '{}'", run.tag);
+        } else {
+            std::fs::remove_dir_all(run_dir(&run.tag)).expect("Unable to delete run directory");
+            log::info!("Deleted run '{}'", run.tag);
+        }
+    }
+}
+
+/// Write the run directory of `tag` to `file` as a zstd-compressed tarball. Paths in the tarball
+/// are relative to the archive directory, i.e. they all start with the tag.
+pub fn export(tag: &str, file: &Path) {
+    // Check that the run exists
+    load(tag);
+
+    let out = std::fs::File::create(file).expect("Unable to create export file");
+    let encoder = zstd::Encoder::new(out, 0).unwrap().auto_finish();
+    let mut builder = tar::Builder::new(encoder);
+    builder
+        .append_dir_all(tag, run_dir(tag))
+        .expect("Unable to write run to tarball");
+    builder.finish().expect("Unable to write run to tarball");
+
+    log::info!("Exported run '{}' to {}", tag, file.display());
+}
+
+/// Unpack a tarball generated by `export` into the archive directory.
+pub fn import(file: &Path) {
+    let tag = match exported_tag(file) {
+        Some(tag) => tag,
+        None => {
+            errors::exit(
+                errors::Category::Usage,
+                Some(file),
+                None,
+                &format!("{} is not an exported run", file.display()),
+            );
+        }
+    };
+
+    if run_dir(&tag).exists() {
+        errors::exit(
+            errors::Category::Runs,
+            None,
+            None,
+            &format!("Run '{}' already exists in {}", tag, RUNS_DIR),
+        );
+    }
+
+    std::fs::create_dir_all(RUNS_DIR).expect("Unable to create runs directory");
+
+    let mut archive = tar::Archive::new(open_tarball(file));
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        entry
+            .unpack_in(RUNS_DIR)
+            .expect("Unable to unpack run from tarball");
+    }
+
+    log::info!("Imported run '{}' into {}", tag, RUNS_DIR);
+}
+
+/// Tag of an exported run: the common first component of all paths in the tarball. `None` when
+/// the paths don't share a first component or the tarball has no run metadata.
+fn exported_tag(file: &Path) -> Option<String> {
+    let mut archive = tar::Archive::new(open_tarball(file));
+
+    let mut tag: Option<String> = None;
+    let mut has_metadata = false;
+
+    for entry in archive.entries().ok()? {
+        let entry = entry.ok()?;
+        let path = entry.path().ok()?.into_owned();
+
+        let mut components = path.components();
+        let first = match components.next()? {
+            std::path::Component::Normal(first) => first.to_string_lossy().into_owned(),
+            _ => return None,
+        };
+
+        if components.as_path() == Path::new(METADATA_FILE) {
+            has_metadata = true;
+        }
+
+        match &tag {
+            Some(tag) if *tag != first => return None,
+            Some(_) => {}
+            None => tag = Some(first),
+        }
+    }
+
+    if has_metadata {
+        tag
+    } else {
+        None
+    }
+}
+
+fn open_tarball(file: &Path) -> zstd::Decoder<'static, std::io::BufReader<std::fs::File>> {
+    let file = std::fs::File::open(file).expect("Unable to open tarball");
+    zstd::Decoder::new(file).expect("Unable to read tarball")
+}
+
+/// Paths of the plots archived with a run, sorted
+pub fn plot_paths(tag: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(run_dir(tag).join("plots")) {
+        Ok(entries) => entries.map(|entry| entry.unwrap().path()).collect(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+    paths
+}
+
+fn run_dir(tag: &str) -> PathBuf {
+    Path::new(RUNS_DIR).join(tag)
+}
+
+/// Read the "total instructions" and "total dirtied host pages" columns of the last record of an
+/// augmented CSV.
+fn final_totals(augmented_csv: &Path) -> (u64, u64) {
+    let totals = augment::last_values(
+        augmented_csv,
+        &[INSTRUCTIONS_TOTAL_COL_IDX, DIRTIED_TOTAL_COL_IDX],
+    );
+    (totals[0], totals[1])
+}
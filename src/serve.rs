@@ -0,0 +1,653 @@
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::augment;
+use crate::config::Config;
+use crate::errors;
+use crate::inputs::{Input, DRUN_HEADERS};
+use crate::plot;
+use crate::ratios;
+use crate::rolling;
+use crate::runs;
+
+/// Address that `serve` listens on by default
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8000";
+
+/// How often the inputs are checked for new rows
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// An input of the dashboard
+struct DashboardInput {
+    label: String,
+    path: PathBuf,
+    /// CSS color of its lines
+    color: String,
+}
+
+/// What the dashboard shows, from the inputs and the configuration, see `dashboard`
+pub struct Dashboard {
+    inputs: Vec<DashboardInput>,
+    /// Headers of the columns of the plots of the configuration and `PLOTS`, with the titles of
+    /// their charts
+    charts: Vec<(String, String)>,
+    /// Names of the plots and their columns that `/runs/<tag>/metrics/<name>` accepts
+    metrics: Vec<(String, usize)>,
+}
+
+/// The dashboard of `inputs`, with their colors, and charts of the columns of the plots of
+/// `config` and `PLOTS`, titled like the plots. Colors of the inputs' styles are used when they're
+/// RGB colors, which browsers understand too, and the colors of the theme otherwise.
+pub fn dashboard(inputs: &[Input], config: &Config) -> Dashboard {
+    let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+    let colors = plot::theme().line_style_colors();
+    let inputs = inputs
+        .iter()
+        .zip(plot::line_styles(&labels))
+        .map(|(input, line_style)| DashboardInput {
+            label: input.label.clone(),
+            path: input.path.clone(),
+            color: match &input.style.color {
+                Some(color) if color.starts_with('#') => color.clone(),
+                _ => colors[(line_style - 1) as usize % colors.len()].to_owned(),
+            },
+        })
+        .collect();
+
+    let operands = ratios::operands(config);
+    let mut charts: Vec<(String, String)> = vec![];
+    for (name, col_idx) in &operands {
+        // Only the columns of the inputs are streamed, not the cumulative ones
+        let header = match augment::input_col_idx(*col_idx) {
+            Some(input_col_idx) if input_col_idx <= DRUN_HEADERS.len() => {
+                DRUN_HEADERS[input_col_idx - 1]
+            }
+            _ => continue,
+        };
+        if charts
+            .iter()
+            .all(|(chart_header, _)| chart_header != header)
+        {
+            let labels = plot::plot_labels(name, *col_idx);
+            charts.push((header.to_owned(), labels.title.unwrap_or(labels.ylabel)));
+        }
+    }
+
+    Dashboard {
+        inputs,
+        charts,
+        metrics: operands
+            .into_iter()
+            .map(|(name, col_idx)| (name.to_owned(), col_idx))
+            .collect(),
+    }
+}
+
+/// Serve `dashboard` on `addr` until the process is stopped. The dashboard has a chart of each
+/// plot, or of each numeric column of inputs without the columns of the plots, with a line per
+/// input, that is extended as rows are appended to the inputs: the rows are streamed to the
+/// browser with server-sent events from `/events`. The inputs don't have to exist yet, and inputs
+/// that are truncated (e.g. by a new run of drun) start over.
+///
+/// When the file at `config_path` changes, the dashboard is replaced with the one `reload`
+/// returns, if any, and the browsers start over with it.
+///
+/// The archived runs are served as JSON too, see `runs_json` and `metric_json`.
+pub fn serve(
+    dashboard: Dashboard,
+    addr: &str,
+    config_path: &Path,
+    reload: impl Fn() -> Option<Dashboard> + Send + 'static,
+) {
+    let listener = TcpListener::bind(addr).unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Usage,
+            None,
+            None,
+            &format!("Unable to listen on {}: {}", addr, err),
+        )
+    });
+
+    log::info!(
+        "Serving the dashboard of {} inputs at http://{}/",
+        dashboard.inputs.len(),
+        listener.local_addr().unwrap()
+    );
+    let dashboard = Arc::new(Mutex::new(Arc::new(dashboard)));
+
+    let config_path = config_path.to_owned();
+    let reloaded = dashboard.clone();
+    std::thread::spawn(move || {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut config_modified = modified(&config_path);
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let now_modified = modified(&config_path);
+            if now_modified != config_modified {
+                config_modified = now_modified;
+                if let Some(dashboard) = reload() {
+                    *reloaded.lock().unwrap() = Arc::new(dashboard);
+                }
+            }
+        }
+    });
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Unable to accept a connection: {}", err);
+                continue;
+            }
+        };
+        let dashboard = dashboard.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = handle(stream, &dashboard) {
+                log::debug!("Connection closed: {}", err);
+            }
+        });
+    }
+}
+
+/// Answer a request: the dashboard at `/`, the rows of the inputs at `/events`, and the archived
+/// runs at `/runs` and `/runs/<tag>/metrics/<name>`
+fn handle(stream: TcpStream, dashboard: &Mutex<Arc<Dashboard>>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers don't matter
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    // Query strings aren't used
+    let path = path.split('?').next().unwrap_or_default();
+    log::debug!("{}", request_line.trim());
+    let mut stream = stream;
+    let segments: Vec<String> = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    match segments[..] {
+        ["runs"] => {
+            let (status, json) = runs_json();
+            return send_json(stream, status, &json);
+        }
+        ["runs", tag, "metrics", name] => {
+            // Not holding the lock while the archived runs are read
+            let latest = dashboard.lock().unwrap().clone();
+            let (status, json) = metric_json(tag, name, &latest.metrics);
+            return send_json(stream, status, &json);
+        }
+        _ => {}
+    }
+    match path {
+        "/" | "/index.html" => {
+            let page = DASHBOARD.replace("$TITLE", "Canister perf dashboard");
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                page.len(),
+                page
+            )
+        }
+        "/events" => stream_rows(stream, dashboard),
+        _ => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ),
+    }
+}
+
+/// Send the inputs, then their rows as they're appended, as server-sent events: an `inputs` event
+/// with the labels and colors of the inputs and the charts, then a `header` event with the header
+/// of each input and `rows` events with its rows, with numbers as numbers and other values as
+/// nulls. An input that gets shorter is sent again from the start, after a `reset` event, and
+/// when the dashboard is replaced everything is sent again from the `inputs` event. Returns when
+/// the browser closes the connection.
+fn stream_rows(mut stream: TcpStream, dashboard: &Mutex<Arc<Dashboard>>) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n"
+    )?;
+
+    let mut current: Option<Arc<Dashboard>> = None;
+    let mut tails: Vec<Tail> = vec![];
+    loop {
+        let latest = dashboard.lock().unwrap().clone();
+        if !current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &latest))
+        {
+            let inputs = &latest.inputs;
+            let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+            let colors: Vec<&str> = inputs.iter().map(|input| input.color.as_str()).collect();
+            send(
+                &mut stream,
+                "inputs",
+                &json!({ "labels": labels, "colors": colors, "charts": latest.charts }),
+            )?;
+            tails = inputs.iter().map(|_| Tail::default()).collect();
+            current = Some(latest);
+        }
+        let inputs = &current.as_ref().unwrap().inputs;
+
+        for (input_idx, (input, tail)) in inputs.iter().zip(&mut tails).enumerate() {
+            let lines = match tail.read(&input.path) {
+                Some(lines) => lines,
+                None => {
+                    send(&mut stream, "reset", &json!({ "input": input_idx }))?;
+                    *tail = Tail::default();
+                    continue;
+                }
+            };
+            let mut records = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(lines.as_slice())
+                .into_records();
+            if !tail.has_header {
+                match records.next() {
+                    Some(Ok(header)) => {
+                        let header: Vec<&str> = header.iter().map(str::trim).collect();
+                        send(
+                            &mut stream,
+                            "header",
+                            &json!({ "input": input_idx, "header": header }),
+                        )?;
+                        tail.has_header = true;
+                    }
+                    _ => continue,
+                }
+            }
+            let rows: Vec<Vec<Option<f64>>> = records
+                .filter_map(Result::ok)
+                .map(|record| {
+                    record
+                        .iter()
+                        .map(|value| value.trim().parse().ok())
+                        .collect()
+                })
+                .collect();
+            if !rows.is_empty() {
+                send(
+                    &mut stream,
+                    "rows",
+                    &json!({ "input": input_idx, "rows": rows }),
+                )?;
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The archived runs, oldest first, with their inputs and final totals. Returns the HTTP status
+/// with the JSON, which is an `error` when the runs can't be read.
+fn runs_json() -> (&'static str, serde_json::Value) {
+    let runs = match runs::try_load_all() {
+        Ok(runs) => runs,
+        Err(error) => return ("500 Internal Server Error", json!({ "error": error })),
+    };
+    let runs: Vec<serde_json::Value> = runs
+        .iter()
+        .map(|run| {
+            let inputs: Vec<serde_json::Value> = run
+                .inputs
+                .iter()
+                .map(|input| {
+                    json!({
+                        "label": input.label,
+                        "input": input.input,
+                        "total_instructions": input.total_instructions,
+                        "total_dirtied_host_pages": input.total_dirtied_host_pages,
+                    })
+                })
+                .collect();
+            json!({
+                "tag": run.tag,
+                "timestamp": humantime::format_rfc3339_seconds(run.timestamp).to_string(),
+                "baseline": run.baseline,
+                "inputs": inputs,
+            })
+        })
+        .collect();
+    ("200 OK", json!({ "runs": runs }))
+}
+
+/// The values of a metric (a plot name of `metrics`) in each input of a run, from the augmented
+/// CSVs, with their count, sum, minimum, maximum, mean, and median, 90th, and 99th percentiles.
+/// Returns the HTTP status with the JSON, which is an `error` for unknown runs and metrics, and for
+/// runs that can't be read.
+fn metric_json(
+    tag: &str,
+    name: &str,
+    metrics: &[(String, usize)],
+) -> (&'static str, serde_json::Value) {
+    let runs = match runs::try_load_all() {
+        Ok(runs) => runs,
+        Err(error) => return ("500 Internal Server Error", json!({ "error": error })),
+    };
+    let run = match runs.into_iter().find(|run| run.tag == tag) {
+        Some(run) => run,
+        None => {
+            let error = format!("Run '{}' not found in {}", tag, runs::RUNS_DIR);
+            return ("404 Not Found", json!({ "error": error }));
+        }
+    };
+    let col_idx = match metrics.iter().find(|(metric, _)| metric == name) {
+        Some((_, col_idx)) => *col_idx,
+        None => {
+            let names: Vec<&str> = metrics.iter().map(|(metric, _)| metric.as_str()).collect();
+            let error = format!(
+                "There's no metric '{}', the metrics are {}",
+                name,
+                names.join(", ")
+            );
+            return ("404 Not Found", json!({ "error": error }));
+        }
+    };
+
+    let mut inputs: Vec<serde_json::Value> = vec![];
+    for input in &run.inputs {
+        match augment::num_columns(&input.data) {
+            Ok(num_columns) if num_columns < col_idx => {
+                let error = format!("{} of run '{}' has no column {}", input.label, tag, col_idx);
+                return ("404 Not Found", json!({ "error": error }));
+            }
+            Ok(_) => {}
+            Err(error) => return ("500 Internal Server Error", json!({ "error": error })),
+        }
+        let values = match augment::try_column_values(&input.data, col_idx) {
+            Ok(values) => values,
+            Err(error) => return ("500 Internal Server Error", json!({ "error": error })),
+        };
+        let mut sorted = values.clone();
+        sorted.sort_unstable();
+        let stats = if sorted.is_empty() {
+            json!(null)
+        } else {
+            let sum: u64 = values.iter().sum();
+            json!({
+                "count": values.len(),
+                "sum": sum,
+                "min": sorted[0],
+                "max": sorted[sorted.len() - 1],
+                "mean": sum as f64 / values.len() as f64,
+                "median": rolling::percentile(&sorted, 0.5),
+                "p90": rolling::percentile(&sorted, 0.9),
+                "p99": rolling::percentile(&sorted, 0.99),
+            })
+        };
+        inputs.push(json!({
+            "label": input.label,
+            "values": values,
+            "stats": stats,
+        }));
+    }
+    (
+        "200 OK",
+        json!({ "run": run.tag, "metric": name, "column": col_idx, "inputs": inputs }),
+    )
+}
+
+fn send_json(mut stream: TcpStream, status: &str, json: &serde_json::Value) -> std::io::Result<()> {
+    let body = json.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nAccess-Control-Allow-Origin: *\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Decode the `%XX` escapes of a path segment
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = vec![];
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex = bytes
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn send(stream: &mut TcpStream, event: &str, data: &serde_json::Value) -> std::io::Result<()> {
+    write!(stream, "event: {}\ndata: {}\n\n", event, data)?;
+    stream.flush()
+}
+
+/// How much of an input has been sent
+#[derive(Default)]
+struct Tail {
+    /// Length of the lines that have been sent, in bytes
+    offset: u64,
+    has_header: bool,
+}
+
+impl Tail {
+    /// The complete lines appended to the input since the last call. Lines that are still being
+    /// written are left for the next call. Returns `None` when the input got shorter. Inputs that
+    /// don't exist are treated as empty.
+    fn read(&mut self, path: &Path) -> Option<Vec<u8>> {
+        let mut file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(_) => return Some(vec![]),
+        };
+        let len = file.metadata().map_or(0, |metadata| metadata.len());
+        if len < self.offset {
+            return None;
+        }
+        let mut appended = vec![];
+        if file.seek(SeekFrom::Start(self.offset)).is_err()
+            || file.read_to_end(&mut appended).is_err()
+        {
+            return Some(vec![]);
+        }
+        let complete = appended
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline_idx| newline_idx + 1);
+        appended.truncate(complete);
+        self.offset += complete as u64;
+        Some(appended)
+    }
+}
+
+/// The dashboard, with `$TITLE` replaced. The charts are drawn on canvases, so that the page
+/// doesn't need anything but the events.
+const DASHBOARD: &str = r###"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>$TITLE</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+#charts { display: flex; flex-wrap: wrap; gap: 1em; }
+.chart h2 { font-size: 1em; margin: 0 0 0.3em 0; }
+.legend span { margin-right: 1em; }
+</style>
+</head>
+<body>
+<h1>$TITLE</h1>
+<p>
+<label><input type="checkbox" id="cumulative"> Cumulative</label>
+&nbsp; <span id="status">Connecting</span>
+</p>
+<p class="legend" id="legend"></p>
+<div id="charts"></div>
+<script>
+"use strict";
+let labels = [], colors = [], charts = [], headers = [], rows = [];
+let pending = false;
+
+const status = text => { document.getElementById("status").textContent = text; };
+
+const events = new EventSource("events");
+events.onopen = () => status("Live");
+events.onerror = () => status("Disconnected, reconnecting");
+events.addEventListener("inputs", event => {
+  const data = JSON.parse(event.data);
+  labels = data.labels;
+  colors = data.colors;
+  charts = data.charts;
+  headers = labels.map(() => null);
+  rows = labels.map(() => []);
+  const legend = document.getElementById("legend");
+  legend.innerHTML = "";
+  labels.forEach((label, idx) => {
+    const span = document.createElement("span");
+    span.style.color = colors[idx];
+    span.textContent = "■ " + label;
+    legend.appendChild(span);
+  });
+  document.getElementById("charts").innerHTML = "";
+  redraw();
+});
+events.addEventListener("header", event => {
+  const data = JSON.parse(event.data);
+  headers[data.input] = data.header;
+  redraw();
+});
+events.addEventListener("rows", event => {
+  const data = JSON.parse(event.data);
+  for (const row of data.rows) rows[data.input].push(row);
+  redraw();
+});
+events.addEventListener("reset", event => {
+  const data = JSON.parse(event.data);
+  headers[data.input] = null;
+  rows[data.input] = [];
+  redraw();
+});
+document.getElementById("cumulative").onchange = () => redraw();
+
+function redraw() {
+  if (pending) return;
+  pending = true;
+  requestAnimationFrame(() => { pending = false; draw(); });
+}
+
+// Headers and titles of the charts: the columns of the plots that the inputs have, or the numeric
+// columns of the inputs by header, except the first (the message number), when they have none of
+// them
+function columns() {
+  const names = [];
+  headers.forEach((header, input) => {
+    if (!header) return;
+    header.forEach((name, col) => {
+      const numeric = rows[input].some(row => row[col] !== null && row[col] !== undefined);
+      if (col > 0 && numeric && !names.includes(name)) names.push(name);
+    });
+  });
+  const plotted = charts.filter(([name]) => names.includes(name));
+  return plotted.length > 0 ? plotted : names.map(name => [name, name]);
+}
+
+function draw() {
+  const cumulative = document.getElementById("cumulative").checked;
+  const charts = document.getElementById("charts");
+  for (const [name, chartTitle] of columns()) {
+    const id = "chart-" + name;
+    let canvas = document.getElementById(id);
+    if (!canvas) {
+      const div = document.createElement("div");
+      div.className = "chart";
+      const title = document.createElement("h2");
+      title.textContent = chartTitle;
+      canvas = document.createElement("canvas");
+      canvas.id = id;
+      canvas.width = 600;
+      canvas.height = 300;
+      div.appendChild(title);
+      div.appendChild(canvas);
+      charts.appendChild(div);
+    }
+    const series = headers.map((header, input) => {
+      const col = header ? header.indexOf(name) : -1;
+      if (col < 0) return [];
+      let total = 0;
+      return rows[input].map(row => {
+        const value = row[col] === null || row[col] === undefined ? NaN : row[col];
+        if (!cumulative) return value;
+        if (!isNaN(value)) total += value;
+        return total;
+      });
+    });
+    plot(canvas, series);
+  }
+}
+
+function plot(canvas, series) {
+  const ctx = canvas.getContext("2d");
+  const margin = { left: 70, right: 10, top: 10, bottom: 25 };
+  const width = canvas.width - margin.left - margin.right;
+  const height = canvas.height - margin.top - margin.bottom;
+  ctx.clearRect(0, 0, canvas.width, canvas.height);
+
+  // Not spread into `Math.max`, which has a limit on the number of arguments
+  const calls = series.reduce((calls, values) => Math.max(calls, values.length), 1);
+  let max = 1, min = 0;
+  for (const values of series) {
+    for (const value of values) {
+      if (value > max) max = value;
+      if (value < min) min = value;
+    }
+  }
+  const x = call => margin.left + (call / Math.max(1, calls - 1)) * width;
+  const y = value => margin.top + height - ((value - min) / (max - min)) * height;
+
+  ctx.strokeStyle = "#888";
+  ctx.fillStyle = "#444";
+  ctx.font = "11px sans-serif";
+  ctx.beginPath();
+  ctx.moveTo(margin.left, margin.top);
+  ctx.lineTo(margin.left, margin.top + height);
+  ctx.lineTo(margin.left + width, margin.top + height);
+  ctx.stroke();
+  ctx.textAlign = "right";
+  ctx.fillText(max.toLocaleString(), margin.left - 4, margin.top + 8);
+  ctx.fillText(min.toLocaleString(), margin.left - 4, margin.top + height);
+  ctx.textAlign = "center";
+  ctx.fillText("call " + calls.toLocaleString(), margin.left + width, canvas.height - 8);
+
+  series.forEach((values, input) => {
+    ctx.strokeStyle = colors[input];
+    ctx.lineWidth = 1.5;
+    ctx.beginPath();
+    let drawing = false;
+    values.forEach((value, call) => {
+      if (isNaN(value)) { drawing = false; return; }
+      if (drawing) ctx.lineTo(x(call), y(value)); else ctx.moveTo(x(call), y(value));
+      drawing = true;
+    });
+    ctx.stroke();
+  });
+}
+</script>
+</body>
+</html>
+"###;
@@ -0,0 +1,466 @@
+use std::io::Write;
+use std::path::Path;
+
+use tempfile::NamedTempFile;
+
+use crate::augment;
+use crate::errors;
+use crate::html;
+use crate::metrics;
+use crate::plot;
+use crate::runs::{self, Run};
+use crate::stats;
+use crate::TOTALS;
+
+/// Directory that `site build` writes the site to by default
+pub const DEFAULT_SITE_DIR: &str = "site";
+
+/// Atom feed of the regressions, in the directory of the site
+const FEED_FILE: &str = "feed.xml";
+
+/// Write a static website of the archived runs to `dir`: `index.html` with the runs (newest first)
+/// and the metrics, a page per cumulative metric in `metrics/` with a plot of its final totals
+/// across the runs, and a page per run in `runs/<tag>/` with its totals and plots. Links are
+/// relative, so the site can be published as it is, e.g. with GitHub Pages.
+///
+/// The site also has an Atom feed of regressions, `feed.xml`, with an entry for each total of an
+/// input label that grew by more than `threshold` (a fraction, e.g. 0.05) from the previous run
+/// with the label.
+pub fn build(dir: &Path, threshold: f64) {
+    let runs = runs::load_all();
+    if runs.is_empty() {
+        errors::exit(
+            errors::Category::Runs,
+            None,
+            None,
+            &format!("No archived runs in {}", runs::RUNS_DIR),
+        );
+    }
+
+    // Final totals of each input of each run, in the order of `TOTALS`
+    let total_col_idxs: Vec<usize> = TOTALS.iter().map(|(_, col_idx, _)| *col_idx).collect();
+    let totals: Vec<Vec<Vec<u64>>> = runs
+        .iter()
+        .map(|run| {
+            run.inputs
+                .iter()
+                .map(|input| augment::last_values(&input.data, &total_col_idxs))
+                .collect()
+        })
+        .collect();
+
+    for dir in [dir.to_owned(), dir.join("metrics"), dir.join("runs")] {
+        std::fs::create_dir_all(&dir).unwrap_or_else(|err| {
+            errors::exit(
+                errors::Category::Output,
+                Some(&dir),
+                None,
+                &format!("Unable to create {}: {}", dir.display(), err),
+            )
+        });
+    }
+    // The site is regenerated from the runs, so its files are replaced by the new versions
+    plot::own_output_dir(dir);
+
+    // Otherwise GitHub Pages leaves out files starting with `_`
+    write(&dir.join(".nojekyll"), "");
+
+    // Inputs with the same label in a run are repetitions, and their totals are averaged
+    let labels = runs::labels(&runs);
+    let label_means: Vec<Vec<Option<Vec<f64>>>> = runs
+        .iter()
+        .map(|run| {
+            labels
+                .iter()
+                .map(|label| {
+                    let inputs = runs::repetitions(run, label);
+                    if inputs.is_empty() {
+                        None
+                    } else {
+                        Some(runs::mean_totals(&inputs, &total_col_idxs))
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut regressions: Vec<Regression> = vec![];
+    write(&dir.join("index.html"), &index_page(&runs));
+    for (total_idx, (name, col_idx, _)) in TOTALS.iter().enumerate() {
+        let label_totals = label_totals(&label_means, total_idx);
+        write_metric_page(
+            &dir.join("metrics"),
+            name,
+            *col_idx,
+            &runs,
+            &labels,
+            &label_totals,
+        );
+        regressions.extend(find_regressions(
+            *col_idx,
+            name,
+            &labels,
+            &label_totals,
+            threshold,
+        ));
+    }
+    for (run, totals) in runs.iter().zip(&totals) {
+        write_run_page(&dir.join("runs").join(&run.tag), run, totals);
+    }
+    write(&dir.join(FEED_FILE), &feed(&runs, &regressions));
+
+    log::info!(
+        "Wrote the site of {} runs to {}, with {} regressions in {}",
+        runs.len(),
+        dir.display(),
+        regressions.len(),
+        FEED_FILE
+    );
+}
+
+/// The total of each label in each run, `None` when the run doesn't have the label, from the mean
+/// totals of each label in each run, see `runs::mean_totals`
+fn label_totals(label_means: &[Vec<Option<Vec<f64>>>], total_idx: usize) -> Vec<Vec<Option<u64>>> {
+    label_means
+        .iter()
+        .map(|means| {
+            means
+                .iter()
+                .map(|means| means.as_ref().map(|means| means[total_idx].round() as u64))
+                .collect()
+        })
+        .collect()
+}
+
+/// A total of an input label that grew from one run to the next run with the label
+struct Regression {
+    /// Name of the metric, see `metrics::display_name`
+    metric: String,
+    col_idx: usize,
+    label: String,
+    /// Indices of the runs in `runs::load_all`
+    previous_run_idx: usize,
+    run_idx: usize,
+    previous_total: u64,
+    total: u64,
+}
+
+/// The regressions of a cumulative metric (an entry of `TOTALS`) with the totals of
+/// `label_totals`: totals that are more than `threshold` (a fraction) larger than the total of the
+/// same label in the previous run with the label
+fn find_regressions(
+    col_idx: usize,
+    total_name: &str,
+    labels: &[&str],
+    label_totals: &[Vec<Option<u64>>],
+    threshold: f64,
+) -> Vec<Regression> {
+    let mut regressions = vec![];
+    for (label_idx, label) in labels.iter().enumerate() {
+        let mut previous: Option<(usize, u64)> = None;
+        for (run_idx, totals) in label_totals.iter().enumerate() {
+            let total = match totals[label_idx] {
+                Some(total) => total,
+                None => continue,
+            };
+            if let Some((previous_run_idx, previous_total)) = previous {
+                if stats::signed_relative_difference(previous_total as f64, total as f64)
+                    > threshold
+                {
+                    regressions.push(Regression {
+                        metric: metrics::display_name(col_idx, total_name),
+                        col_idx,
+                        label: label.to_string(),
+                        previous_run_idx,
+                        run_idx,
+                        previous_total,
+                        total,
+                    });
+                }
+            }
+            previous = Some((run_idx, total));
+        }
+    }
+    regressions
+}
+
+/// An Atom feed with an entry per regression, newest first. Entries link to the pages of the runs,
+/// relative to the feed, and their IDs only depend on the regression, so feed readers show each
+/// regression once however many times the site is rebuilt.
+fn feed(runs: &[Run], regressions: &[Regression]) -> String {
+    let mut regressions: Vec<&Regression> = regressions.iter().collect();
+    regressions.sort_by_key(|regression| std::cmp::Reverse(regression.run_idx));
+
+    let updated = humantime::format_rfc3339_seconds(runs[runs.len() - 1].timestamp);
+    let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    feed.push_str("<title>Canister perf regressions</title>\n");
+    feed.push_str("<id>urn:generate-plots:regressions</id>\n");
+    feed.push_str(&format!("<updated>{}</updated>\n", updated));
+    feed.push_str(&format!(
+        "<link rel=\"alternate\" href=\"index.html\"/>\n<author><name>{}</name></author>\n",
+        env!("CARGO_PKG_NAME")
+    ));
+
+    for regression in regressions {
+        let previous_run = &runs[regression.previous_run_idx];
+        let run = &runs[regression.run_idx];
+        let delta = stats::signed_relative_difference(
+            regression.previous_total as f64,
+            regression.total as f64,
+        );
+        let title = format!(
+            "{} of {}: {:+.1}% in run {}",
+            regression.metric,
+            regression.label,
+            delta * 100.0,
+            run.tag
+        );
+        let summary = format!(
+            "{} of {} went from {} in run {} to {} in run {}",
+            regression.metric,
+            regression.label,
+            metrics::format_value(regression.col_idx, regression.previous_total),
+            previous_run.tag,
+            metrics::format_value(regression.col_idx, regression.total),
+            run.tag
+        );
+        feed.push_str("<entry>\n");
+        feed.push_str(&format!("<title>{}</title>\n", html::escape(&title)));
+        feed.push_str(&format!(
+            "<id>urn:generate-plots:regression:{}:{}:{}</id>\n",
+            html::url_escape(&run.tag),
+            html::url_escape(&regression.metric),
+            html::url_escape(&regression.label)
+        ));
+        feed.push_str(&format!(
+            "<updated>{}</updated>\n",
+            humantime::format_rfc3339_seconds(run.timestamp)
+        ));
+        feed.push_str(&format!(
+            "<link rel=\"alternate\" href=\"runs/{}/index.html\"/>\n",
+            html::url_escape(&run.tag)
+        ));
+        feed.push_str(&format!("<summary>{}</summary>\n", html::escape(&summary)));
+        feed.push_str("</entry>\n");
+    }
+
+    feed.push_str("</feed>\n");
+    feed
+}
+
+/// Links to the run pages, newest first, and to the metric pages
+fn index_page(runs: &[Run]) -> String {
+    let mut body = String::new();
+    body.push_str("<h2>Metrics</h2>\n<ul>\n");
+    for (name, col_idx, _) in TOTALS.iter() {
+        body.push_str(&format!(
+            "<li><a href=\"metrics/{}.html\">{}</a></li>\n",
+            plot::file_name_slug(name),
+            html::escape(&metrics::display_name(*col_idx, name))
+        ));
+    }
+    body.push_str("</ul>\n");
+    body.push_str(&format!(
+        "<p><a href=\"{}\">Feed of regressions</a></p>\n",
+        FEED_FILE
+    ));
+
+    body.push_str("<h2>Runs</h2>\n<table>\n");
+    body.push_str(&html::table_row(
+        "th",
+        ["run", "date", "baseline", "inputs"].iter().copied(),
+    ));
+    for run in runs.iter().rev() {
+        let labels: Vec<&str> = run
+            .inputs
+            .iter()
+            .map(|input| input.label.as_str())
+            .collect();
+        body.push_str(&format!(
+            "<tr><td><a href=\"runs/{}/index.html\">{}</a></td><td>{}</td><td>{}</td>\
+             <td>{}</td></tr>\n",
+            html::url_escape(&run.tag),
+            html::escape(&run.tag),
+            humantime::format_rfc3339_seconds(run.timestamp),
+            if run.baseline { "yes" } else { "" },
+            html::escape(&labels.join(", "))
+        ));
+    }
+    body.push_str("</table>\n");
+
+    page("Canister perf runs", "", &body)
+}
+
+/// A page with a plot of the final totals of a cumulative metric (an entry of `TOTALS`) in each
+/// run, oldest first, with a line per input label, and a table of the totals. `totals` has the
+/// total of each label in each run, see `label_totals`.
+fn write_metric_page(
+    dir: &Path,
+    total_name: &str,
+    col_idx: usize,
+    runs: &[Run],
+    labels: &[&str],
+    totals: &[Vec<Option<u64>>],
+) {
+    let slug = plot::file_name_slug(total_name);
+    let name = metrics::display_name(col_idx, total_name);
+
+    let line_styles = plot::line_styles(labels);
+    let mut data_files: Vec<NamedTempFile> = vec![];
+    let mut lines: Vec<plot::PlotLine> = vec![];
+    for (label_idx, (label, line_style)) in labels.iter().zip(&line_styles).enumerate() {
+        // The run number, the tag, and the total. Every line has all the runs, for the tics.
+        let mut data_file = NamedTempFile::new().unwrap();
+        for (run_idx, (run, totals)) in runs.iter().zip(totals).enumerate() {
+            let total = match totals[label_idx] {
+                Some(total) => total.to_string(),
+                None => "NaN".to_owned(),
+            };
+            writeln!(data_file, "{},\"{}\",{}", run_idx + 1, run.tag, total).unwrap();
+        }
+        data_file.flush().unwrap();
+        plot::set_dry_run_placeholder(
+            data_file.path(),
+            &format!("<{} of {} in the runs>", name, label),
+        );
+
+        lines.push(plot::PlotLine {
+            data: data_file.path().to_string_lossy().into_owned(),
+            using: "1:3:xtic(2)".to_owned(),
+            style: format!("with linespoints ls {}", line_style),
+            title: label.to_string(),
+        });
+        data_files.push(data_file);
+    }
+
+    let mut program = plot::PlotProgram::new(&name, lines);
+    program.xlabel = "run".to_owned();
+    program.extra_commands = HISTORY_COMMANDS.to_owned();
+    plot::run_gnuplot(&program.program(), &dir.join(format!("{}.png", slug)));
+
+    let mut body = format!(
+        "<img src=\"{}.png\" alt=\"{}\">\n",
+        slug,
+        html::escape(&name)
+    );
+    body.push_str("<table>\n");
+    body.push_str(&html::table_row(
+        "th",
+        std::iter::once("run").chain(labels.iter().copied()),
+    ));
+    for (run, totals) in runs.iter().zip(totals).rev() {
+        body.push_str(&format!(
+            "<tr><td><a href=\"../runs/{}/index.html\">{}</a></td>",
+            html::url_escape(&run.tag),
+            html::escape(&run.tag)
+        ));
+        for total in totals {
+            body.push_str(&format!(
+                "<td>{}</td>",
+                total.map_or(String::new(), |total| metrics::format_value(col_idx, total))
+            ));
+        }
+        body.push_str("</tr>\n");
+    }
+    body.push_str("</table>\n");
+
+    write(
+        &dir.join(format!("{}.html", slug)),
+        &page(&name, "../", &body),
+    );
+}
+
+/// Settings overriding the defaults of the plot template for the plots of the metric pages
+const HISTORY_COMMANDS: &str = r###"
+set xrange [*:*]
+set xtics rotate by -30
+"###;
+
+/// A page with the totals of the inputs of a run and its plots, which are copied next to it
+fn write_run_page(dir: &Path, run: &Run, totals: &[Vec<u64>]) {
+    std::fs::create_dir_all(dir).expect("Unable to create run page directory");
+
+    let mut body = format!(
+        "<p>{}{}</p>\n<table>\n",
+        humantime::format_rfc3339_seconds(run.timestamp),
+        if run.baseline { ", baseline" } else { "" }
+    );
+    let total_names: Vec<String> = TOTALS
+        .iter()
+        .map(|(name, col_idx, _)| metrics::display_name(*col_idx, name))
+        .collect();
+    body.push_str(&html::table_row(
+        "th",
+        ["input", "file"]
+            .iter()
+            .copied()
+            .chain(total_names.iter().map(String::as_str)),
+    ));
+    for (input, totals) in run.inputs.iter().zip(totals) {
+        let values: Vec<String> = TOTALS
+            .iter()
+            .zip(totals)
+            .map(|((_, col_idx, _), total)| metrics::format_value(*col_idx, *total))
+            .collect();
+        body.push_str(&html::table_row(
+            "td",
+            [input.label.as_str(), input.input.as_str()]
+                .iter()
+                .copied()
+                .chain(values.iter().map(String::as_str)),
+        ));
+    }
+    body.push_str("</table>\n");
+
+    for plot_path in runs::plot_paths(&run.tag) {
+        let file_name = plot_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        let contents = std::fs::read(&plot_path).expect("Unable to read plot of run");
+        plot::write_output(&dir.join(&file_name), &contents).expect("Unable to copy plot");
+        body.push_str(&format!(
+            "<h2>{}</h2>\n<img src=\"{}\" alt=\"{}\">\n",
+            html::escape(&plot_title(&plot_path)),
+            html::url_escape(&file_name),
+            html::escape(&file_name)
+        ));
+    }
+
+    write(
+        &dir.join("index.html"),
+        &page(&format!("Run {}", run.tag), "../../", &body),
+    );
+}
+
+/// Title of a plot: its file name without the extension, with `_`s replaced by spaces
+fn plot_title(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .replace('_', " ")
+}
+
+/// A page of the site. `root` is the relative path of the directory of the site from the page,
+/// e.g. `../`, for the link to the index.
+fn page(title: &str, root: &str, body: &str) -> String {
+    let nav = if root.is_empty() {
+        String::new()
+    } else {
+        format!("<p><a href=\"{}index.html\">All runs</a></p>\n", root)
+    };
+    html::page(title, &nav, body)
+}
+
+fn write(path: &Path, contents: &str) {
+    plot::write_output(path, contents.as_bytes()).unwrap_or_else(|err| {
+        errors::exit(
+            errors::Category::Output,
+            Some(path),
+            None,
+            &format!("Unable to write {}: {}", path.display(), err),
+        )
+    });
+}
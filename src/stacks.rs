@@ -0,0 +1,151 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use tempfile::NamedTempFile;
+
+use crate::config::Config;
+use crate::errors;
+use crate::inputs::Input;
+use crate::metrics;
+use crate::plot;
+use crate::ratios;
+
+/// An entry of the `[stack]` table: columns that are components of a cost, e.g. mutator and GC
+/// instructions, plotted per input as a stacked area chart named `<name>_<label>.png` with
+/// non-alphanumeric characters replaced by `_`
+pub struct Stack {
+    pub name: String,
+    /// Plot names and 1-based column indices in the augmented CSVs, bottom first
+    pub components: Vec<(String, usize)>,
+}
+
+/// Parse a stack of the plot names of `operands`, see `ratios::operands`.
+pub fn parse_stack(
+    name: &str,
+    components: &[String],
+    operands: &[(&str, usize)],
+) -> Result<Stack, String> {
+    if components.is_empty() {
+        return Err("expected a list of plots, found an empty one".to_owned());
+    }
+    let components = components
+        .iter()
+        .map(|component| {
+            let component = component.trim();
+            operands
+                .iter()
+                .find(|(plot_name, _)| *plot_name == component)
+                .map(|(_, column)| (component.to_owned(), *column))
+                .ok_or_else(|| format!("there's no plot `{}`", component))
+        })
+        .collect::<Result<Vec<(String, usize)>, String>>()?;
+    Ok(Stack {
+        name: name.to_owned(),
+        components,
+    })
+}
+
+/// The stacks of the configuration. Exits with an error when one doesn't parse.
+pub fn stacks(config: &Config) -> Vec<Stack> {
+    let operands = ratios::operands(config);
+    config
+        .stacks
+        .iter()
+        .map(
+            |(name, components)| match parse_stack(name, components, &operands) {
+                Ok(stack) => stack,
+                Err(err) => {
+                    errors::exit(
+                        errors::Category::Config,
+                        None,
+                        None,
+                        &format!("Invalid stack '{}': {}", name, err),
+                    );
+                }
+            },
+        )
+        .collect()
+}
+
+/// Plot each stack for each input, with the components as areas stacked on top of each other so
+/// that the top of the stack is their sum. Returns the paths of the plots.
+pub fn plot_stacks(inputs: &[Input], stacks: &[Stack]) -> Vec<PathBuf> {
+    let mut plot_paths = vec![];
+
+    for stack in stacks {
+        let component_names: Vec<String> = stack
+            .components
+            .iter()
+            .map(|(plot_name, col_idx)| metrics::display_name(*col_idx, plot_name))
+            .collect();
+        let component_names: Vec<&str> = component_names.iter().map(String::as_str).collect();
+        let line_styles = plot::line_styles(&component_names);
+
+        for input in inputs {
+            let plot_name = format!(
+                "{}_{}",
+                plot::file_name_slug(&stack.name),
+                plot::file_name_slug(&input.label)
+            );
+            log::info!("{}", plot_name);
+
+            let values: Vec<Vec<u64>> = stack
+                .components
+                .iter()
+                .map(|(_, col_idx)| ratios::column_values(input, *col_idx))
+                .collect();
+            let num_calls = values.iter().map(Vec::len).min().unwrap_or(0);
+
+            // The running sums of the components, from the bottom of the stack, on each line
+            let mut data_file = NamedTempFile::new().unwrap();
+            for call_idx in 0..num_calls {
+                let mut sum = 0;
+                let sums: Vec<String> = values
+                    .iter()
+                    .map(|values| {
+                        sum += values[call_idx];
+                        sum.to_string()
+                    })
+                    .collect();
+                writeln!(data_file, "{}", sums.join(" ")).unwrap();
+            }
+            data_file.flush().unwrap();
+            plot::set_dry_run_placeholder(
+                data_file.path(),
+                &format!("<{} of {}>", stack.name, input.path.display()),
+            );
+
+            // The top of the stack is drawn first, so that the areas below are drawn over it
+            let lines: Vec<plot::PlotLine> = component_names
+                .iter()
+                .zip(&line_styles)
+                .enumerate()
+                .rev()
+                .map(
+                    |(component_idx, (component_name, line_style))| plot::PlotLine {
+                        data: data_file.path().to_string_lossy().into_owned(),
+                        using: format!("($0+1):{}", component_idx + 1),
+                        style: format!("with filledcurves x1 ls {}", line_style),
+                        title: component_name.to_string(),
+                    },
+                )
+                .collect();
+
+            let mut program = plot::PlotProgram::new(&stack.name, lines);
+            program.title = Some(input.label.clone());
+            program.extra_commands = STACK_COMMANDS.to_owned();
+
+            let plot_path = plot::plot_path(&plot_name, "");
+            plot::run_gnuplot(&program.program(), &plot_path);
+            plot_paths.push(plot_path);
+        }
+    }
+
+    plot_paths
+}
+
+/// Settings overriding the defaults of the plot template for stacked area charts
+const STACK_COMMANDS: &str = r###"
+set style fill solid 0.6 noborder
+set yrange [0:*]
+"###;
@@ -0,0 +1,208 @@
+pub fn geometric_mean(values: &[f64]) -> f64 {
+    let log_sum: f64 = values.iter().map(|value| value.ln()).sum();
+    (log_sum / values.len() as f64).exp()
+}
+
+/// Two-sided p-value of the Mann-Whitney U test of the samples, using the normal approximation
+/// with tie and continuity corrections. `None` when either sample is empty or all values are
+/// equal.
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    let n1 = a.len() as f64;
+    let n2 = b.len() as f64;
+    let n = n1 + n2;
+
+    // Values tagged with the sample they come from, sorted by value
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|value| (*value, true))
+        .chain(b.iter().map(|value| (*value, false)))
+        .collect();
+    all.sort_by(|(v1, _), (v2, _)| v1.partial_cmp(v2).unwrap());
+
+    // Sum of ranks of `a`, with tied values getting the average of their ranks
+    let mut rank_sum_a = 0.0;
+    let mut tie_correction = 0.0;
+    let mut group_start = 0;
+    while group_start < all.len() {
+        let mut group_end = group_start;
+        while group_end < all.len() && all[group_end].0 == all[group_start].0 {
+            group_end += 1;
+        }
+
+        let group_len = (group_end - group_start) as f64;
+        // Ranks are 1-based
+        let avg_rank = (group_start + group_end + 1) as f64 / 2.0;
+        for (_, from_a) in &all[group_start..group_end] {
+            if *from_a {
+                rank_sum_a += avg_rank;
+            }
+        }
+        tie_correction += group_len * group_len * group_len - group_len;
+
+        group_start = group_end;
+    }
+
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_correction / (n * (n - 1.0)));
+
+    if variance <= 0.0 {
+        return None;
+    }
+
+    let diff = (u - mean).abs();
+    let z = (diff - 0.5).max(0.0) / variance.sqrt();
+
+    Some(erfc(z / std::f64::consts::SQRT_2).min(1.0))
+}
+
+/// Complementary error function, with fractional error less than 1.2e-7 (Numerical Recipes
+/// `erfcc`).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// Pearson correlation coefficient of two equally long samples. `None` when either sample is
+/// constant or the samples have less than two values.
+pub fn pearson_correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len().min(ys.len());
+    if n < 2 {
+        return None;
+    }
+
+    let mean_x = xs[..n].iter().sum::<f64>() / n as f64;
+    let mean_y = ys[..n].iter().sum::<f64>() / n as f64;
+
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x) * (x - mean_x);
+        var_y += (y - mean_y) * (y - mean_y);
+    }
+
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+
+    Some(cov / (var_x.sqrt() * var_y.sqrt()))
+}
+
+/// 1-based ranks of the values, with tied values getting the average of their ranks.
+pub fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut idxs: Vec<usize> = (0..values.len()).collect();
+    idxs.sort_by(|i1, i2| values[*i1].partial_cmp(&values[*i2]).unwrap());
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut group_start = 0;
+    while group_start < idxs.len() {
+        let mut group_end = group_start;
+        while group_end < idxs.len() && values[idxs[group_end]] == values[idxs[group_start]] {
+            group_end += 1;
+        }
+
+        let avg_rank = (group_start + group_end + 1) as f64 / 2.0;
+        for idx in &idxs[group_start..group_end] {
+            ranks[*idx] = avg_rank;
+        }
+
+        group_start = group_end;
+    }
+
+    ranks
+}
+
+/// Running sums of the values
+pub fn cumulative(values: &[f64]) -> Vec<f64> {
+    let mut total = 0.0;
+    values
+        .iter()
+        .map(|value| {
+            total += value;
+            total
+        })
+        .collect()
+}
+
+/// Area under the curve through `(i, ys[i])` using the trapezoidal rule.
+pub fn trapezoid_area(ys: &[f64]) -> f64 {
+    ys.windows(2).map(|w| (w[0] + w[1]) / 2.0).sum()
+}
+
+/// Indices at which the sign of `a[i] - b[i]` changes compared to the last index where it was
+/// non-zero, i.e. where the curves through the values cross. Touching without crossing is not a
+/// crossover.
+pub fn crossovers(a: &[u64], b: &[u64]) -> Vec<usize> {
+    let mut crossovers = vec![];
+    let mut last_ordering = std::cmp::Ordering::Equal;
+
+    for (idx, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+        let ordering = a.cmp(b);
+        if ordering == std::cmp::Ordering::Equal {
+            continue;
+        }
+        if last_ordering != std::cmp::Ordering::Equal && ordering != last_ordering {
+            crossovers.push(idx);
+        }
+        last_ordering = ordering;
+    }
+
+    crossovers
+}
+
+/// `(b - a) / a`, infinite when only `a` is zero
+pub fn signed_relative_difference(a: f64, b: f64) -> f64 {
+    if a == 0.0 {
+        if b == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY * b.signum()
+        }
+    } else {
+        (b - a) / a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mann_whitney_u_p_values() {
+        // scipy.stats.mannwhitneyu(..., method="asymptotic") gives 0.0808556
+        let p = mann_whitney_u(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]).unwrap();
+        assert!((p - 0.0808556).abs() < 1e-6, "{}", p);
+        assert_eq!(mann_whitney_u(&[4.0, 5.0, 6.0], &[1.0, 2.0, 3.0]), Some(p));
+
+        let p = mann_whitney_u(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0]).unwrap();
+        assert!((p - 1.0).abs() < 1e-6, "{}", p);
+    }
+
+    #[test]
+    fn mann_whitney_u_degenerate_samples() {
+        assert_eq!(mann_whitney_u(&[], &[1.0]), None);
+        assert_eq!(mann_whitney_u(&[1.0], &[]), None);
+        assert_eq!(mann_whitney_u(&[2.0, 2.0], &[2.0, 2.0, 2.0]), None);
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+
+/// Print rows as a left-aligned table with columns separated by two spaces.
+pub fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    print!("{}", format_table(headers, rows));
+}
+
+/// Format rows as a left-aligned table with columns separated by two spaces.
+pub fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+
+    let headers: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    push_row(&mut table, &widths, &headers);
+
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    push_row(&mut table, &widths, &rule);
+
+    for row in rows {
+        push_row(&mut table, &widths, row);
+    }
+
+    table
+}
+
+fn push_row(table: &mut String, widths: &[usize], cells: &[String]) {
+    let line = cells
+        .iter()
+        .zip(widths.iter())
+        .map(|(cell, width)| format!("{:width$}", cell, width = width))
+        .collect::<Vec<_>>()
+        .join("  ");
+    table.push_str(line.trim_end());
+    table.push('\n');
+}
+
+/// Separators of numbers in tables and reports
+#[derive(Clone, Copy)]
+struct Separators {
+    /// Between groups of thousands, e.g. `,` in "93,482,934,823"
+    group: char,
+    decimal: char,
+}
+
+/// Set with `set_raw_numbers`. `None` for numbers as Rust formats them.
+static SEPARATORS: Mutex<Option<Separators>> = Mutex::new(None);
+
+/// Whether `localize` leaves numbers as they are, or uses the separators of the locale of
+/// `LC_ALL`, `LC_NUMERIC`, or `LANG`.
+pub fn set_raw_numbers(raw: bool) {
+    *SEPARATORS.lock().unwrap() = if raw { None } else { Some(locale_separators()) };
+}
+
+/// Separators of the locale, by its language. Languages other than the ones that group with `.`
+/// or spaces, and the C locale, get `,` and `.`.
+fn locale_separators() -> Separators {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_default();
+    let language = locale.split(['_', '.', '@']).next().unwrap_or("");
+    let (group, decimal) = match language {
+        "da" | "de" | "el" | "es" | "id" | "it" | "nl" | "pt" | "tr" => ('.', ','),
+        "cs" | "fi" | "fr" | "hu" | "nb" | "no" | "pl" | "ru" | "sk" | "sv" | "uk" => (' ', ','),
+        _ => (',', '.'),
+    };
+    Separators { group, decimal }
+}
+
+/// A number formatted by Rust (an optional sign, digits, and optionally a `.` and decimals or a
+/// suffix like `%`) with the separators of `set_raw_numbers`, e.g. "93482934823" as
+/// "93,482,934,823".
+pub fn localize(number: &str) -> String {
+    let separators = match *SEPARATORS.lock().unwrap() {
+        Some(separators) => separators,
+        None => return number.to_owned(),
+    };
+
+    let start = number
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(number.len());
+    let end = number[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(number.len(), |len| start + len);
+    let digits = &number[start..end];
+
+    let mut localized = number[..start].to_owned();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            localized.push(separators.group);
+        }
+        localized.push(digit);
+    }
+    match number[end..].strip_prefix('.') {
+        Some(decimals) => {
+            localized.push(separators.decimal);
+            localized.push_str(decimals);
+        }
+        None => localized.push_str(&number[end..]),
+    }
+    localized
+}
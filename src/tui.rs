@@ -0,0 +1,253 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, GraphType, List, ListState, Paragraph};
+use ratatui::Frame;
+
+use crate::augment;
+use crate::errors;
+use crate::inputs::Input;
+use crate::plot;
+use crate::PLOTS;
+
+/// Colors of the line styles in `GNUPLOT_TEMPLATE`, so that series have the same colors as in
+/// the generated images
+const LINE_STYLE_COLORS: [Color; 7] = [
+    Color::Rgb(0xA0, 0x00, 0x00),
+    Color::Rgb(0x00, 0xA0, 0x00),
+    Color::Rgb(0x50, 0x60, 0xD0),
+    Color::Rgb(0x00, 0x00, 0xA0),
+    Color::Rgb(0xD0, 0xD0, 0x00),
+    Color::Rgb(0x00, 0xD0, 0xD0),
+    Color::Rgb(0xB2, 0x00, 0xB2),
+];
+
+struct App<'a> {
+    inputs: &'a [Input],
+    colors: Vec<Color>,
+    /// `points[input_idx][plot_idx]` are the `(call, value)` points of the input's series in the
+    /// plot. Calls are 1-based, as in the images.
+    points: Vec<Vec<Vec<(f64, f64)>>>,
+    plot_idx: usize,
+    visible: Vec<bool>,
+    /// 0-based index of the call under the cursor
+    cursor: usize,
+    num_calls: usize,
+}
+
+/// Show the plots of `PLOTS` as braille line charts. Up/down selects the plot, left/right moves
+/// the cursor, number keys toggle series, `q` quits.
+pub fn tui(inputs: &[Input]) {
+    let points: Vec<Vec<Vec<(f64, f64)>>> = inputs
+        .iter()
+        .map(|input| {
+            let augmented = augment::add_cumulative_columns(&input.path);
+            PLOTS
+                .iter()
+                .map(|(_, col_idx)| {
+                    augment::try_column_values(augmented.path(), *col_idx)
+                        .unwrap_or_else(|err| {
+                            errors::exit(errors::Category::Input, Some(&input.path), None, &err)
+                        })
+                        .into_iter()
+                        .enumerate()
+                        .map(|(idx, value)| ((idx + 1) as f64, value as f64))
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+
+    let labels: Vec<&str> = inputs.iter().map(|input| input.label.as_str()).collect();
+    let colors = plot::line_styles(&labels)
+        .into_iter()
+        .map(|line_style| LINE_STYLE_COLORS[(line_style - 1) as usize])
+        .collect();
+
+    let num_calls = points
+        .iter()
+        .map(|input_points| input_points[0].len())
+        .max()
+        .unwrap_or(0);
+
+    let mut app = App {
+        inputs,
+        colors,
+        points,
+        plot_idx: 0,
+        visible: vec![true; inputs.len()],
+        cursor: 0,
+        num_calls,
+    };
+
+    let mut terminal = ratatui::init();
+
+    loop {
+        terminal
+            .draw(|frame| draw(frame, &app))
+            .expect("Unable to draw to terminal");
+
+        let key = match event::read().expect("Unable to read terminal event") {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        let last_call = app.num_calls.saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Up | KeyCode::Char('k') => app.plot_idx = app.plot_idx.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.plot_idx = (app.plot_idx + 1).min(PLOTS.len() - 1)
+            }
+            KeyCode::Left | KeyCode::Char('h') => app.cursor = app.cursor.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => app.cursor = (app.cursor + 1).min(last_call),
+            KeyCode::PageUp => app.cursor = app.cursor.saturating_sub(10),
+            KeyCode::PageDown => app.cursor = (app.cursor + 10).min(last_call),
+            KeyCode::Home => app.cursor = 0,
+            KeyCode::End => app.cursor = last_call,
+            KeyCode::Char(c) => {
+                if let Some(idx) = c.to_digit(10).and_then(|d| (d as usize).checked_sub(1)) {
+                    if let Some(visible) = app.visible.get_mut(idx) {
+                        *visible = !*visible;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ratatui::restore();
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [sidebar, main] =
+        Layout::horizontal([Constraint::Length(34), Constraint::Min(20)]).areas(frame.area());
+    let [plots_area, inputs_area, help_area] = Layout::vertical([
+        Constraint::Length(PLOTS.len() as u16 + 2),
+        Constraint::Min(3),
+        Constraint::Length(6),
+    ])
+    .areas(sidebar);
+    let [chart_area, values_area] = Layout::vertical([
+        Constraint::Min(5),
+        Constraint::Length(app.inputs.len() as u16 + 3),
+    ])
+    .areas(main);
+
+    let plot_names: Vec<&str> = PLOTS.iter().map(|(name, _)| *name).collect();
+    let mut list_state = ListState::default().with_selected(Some(app.plot_idx));
+    frame.render_stateful_widget(
+        List::new(plot_names)
+            .block(Block::bordered().title("Plots"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        plots_area,
+        &mut list_state,
+    );
+
+    let input_lines: Vec<Line> = app
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(idx, input)| {
+            Line::from(vec![
+                Span::raw(format!(
+                    "[{}] {} ",
+                    if app.visible[idx] { "x" } else { " " },
+                    idx + 1
+                )),
+                Span::styled(input.label.clone(), Style::default().fg(app.colors[idx])),
+            ])
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(input_lines).block(Block::bordered().title("Inputs")),
+        inputs_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::raw("up/down: select plot"),
+            Line::raw("left/right, pgup/pgdn: cursor"),
+            Line::raw("1-9: toggle input"),
+            Line::raw("q: quit"),
+        ])
+        .block(Block::bordered().title("Keys")),
+        help_area,
+    );
+
+    let mut max_value: f64 = 0.0;
+    for (input_idx, input_points) in app.points.iter().enumerate() {
+        if app.visible[input_idx] {
+            for (_, value) in &input_points[app.plot_idx] {
+                max_value = max_value.max(*value);
+            }
+        }
+    }
+    let max_value = if max_value == 0.0 { 1.0 } else { max_value };
+
+    let cursor_x = (app.cursor + 1) as f64;
+    let cursor_points = [(cursor_x, 0.0), (cursor_x, max_value)];
+
+    let mut datasets: Vec<Dataset> = app
+        .points
+        .iter()
+        .enumerate()
+        .filter(|(input_idx, _)| app.visible[*input_idx])
+        .map(|(input_idx, input_points)| {
+            Dataset::default()
+                .name(app.inputs[input_idx].label.clone())
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(app.colors[input_idx]))
+                .data(&input_points[app.plot_idx])
+        })
+        .collect();
+    datasets.push(
+        Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::DarkGray))
+            .data(&cursor_points),
+    );
+
+    let num_calls = app.num_calls.max(1) as f64;
+    let chart = Chart::new(datasets)
+        .block(Block::bordered().title(PLOTS[app.plot_idx].0.replace('_', " ")))
+        .x_axis(
+            Axis::default()
+                .title("call")
+                .bounds([1.0, num_calls])
+                .labels(["1".to_owned(), format!("{}", num_calls)]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([0.0, max_value])
+                .labels(["0".to_owned(), format!("{:.0}", max_value)]),
+        );
+    frame.render_widget(chart, chart_area);
+
+    let value_lines: Vec<Line> = app
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(input_idx, input)| {
+            let value = app.points[input_idx][app.plot_idx]
+                .get(app.cursor)
+                .map_or("-".to_owned(), |(_, value)| format!("{:.0}", value));
+            Line::from(vec![
+                Span::styled(
+                    format!("{}: ", input.label),
+                    Style::default().fg(app.colors[input_idx]),
+                ),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(value_lines)
+            .block(Block::bordered().title(format!("Call {}", app.cursor + 1))),
+        values_area,
+    );
+}
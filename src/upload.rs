@@ -0,0 +1,142 @@
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use crate::errors;
+
+/// Object storage that `--upload` uploads to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Service {
+    /// Amazon S3, with the `aws` command line tool
+    S3,
+    /// Google Cloud Storage, with the `gsutil` command line tool
+    Gcs,
+}
+
+/// Where the outputs are uploaded to, parsed from `s3://BUCKET/PREFIX` or `gs://BUCKET/PREFIX`
+#[derive(Clone)]
+pub struct Destination {
+    service: Service,
+    bucket: String,
+    /// Without slashes at the ends, empty for the root of the bucket
+    prefix: String,
+}
+
+/// Parse a `--upload` destination.
+pub fn parse_destination(url: &str) -> Result<Destination, String> {
+    let (service, rest) = if let Some(rest) = url.strip_prefix("s3://") {
+        (Service::S3, rest)
+    } else if let Some(rest) = url.strip_prefix("gs://") {
+        (Service::Gcs, rest)
+    } else {
+        return Err(format!(
+            "expected `s3://BUCKET/PREFIX` or `gs://BUCKET/PREFIX`, found `{}`",
+            url
+        ));
+    };
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        return Err(format!("`{}` has no bucket", url));
+    }
+    Ok(Destination {
+        service,
+        bucket: bucket.to_owned(),
+        prefix: prefix.trim_matches('/').to_owned(),
+    })
+}
+
+impl Destination {
+    /// Key of an output: its path relative to `dir` (or its file name when it's outside `dir`),
+    /// after the prefix
+    fn key(&self, path: &Path, dir: &Path) -> String {
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        let relative: PathBuf = if relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            relative.to_owned()
+        } else {
+            PathBuf::from(path.file_name().unwrap_or_default())
+        };
+        let components: Vec<String> = relative
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let relative = components.join("/");
+        if self.prefix.is_empty() {
+            relative
+        } else {
+            format!("{}/{}", self.prefix, relative)
+        }
+    }
+
+    /// The `s3://` or `gs://` URL of a key, for the upload tools
+    fn object_url(&self, key: &str) -> String {
+        match self.service {
+            Service::S3 => format!("s3://{}/{}", self.bucket, key),
+            Service::Gcs => format!("gs://{}/{}", self.bucket, key),
+        }
+    }
+
+    /// The HTTPS URL of a key
+    fn https_url(&self, key: &str) -> String {
+        match self.service {
+            Service::S3 => format!("https://{}.s3.amazonaws.com/{}", self.bucket, key),
+            Service::Gcs => format!("https://storage.googleapis.com/{}/{}", self.bucket, key),
+        }
+    }
+}
+
+/// Upload outputs to `destination`, keyed by their paths relative to `dir`, and print their
+/// URLs. Outputs that don't exist (e.g. plots that failed) are left out. The uploads use the
+/// credentials and configuration of the `aws` or `gsutil` command line tools, which have to be
+/// installed. Exits with an error when an upload fails.
+pub fn upload(destination: &Destination, paths: &[PathBuf], dir: &Path) {
+    let (program, args): (&str, &[&str]) = match destination.service {
+        Service::S3 => ("aws", &["s3", "cp", "--only-show-errors"]),
+        Service::Gcs => ("gsutil", &["-q", "cp"]),
+    };
+
+    let mut uploaded: Vec<&PathBuf> = vec![];
+    for path in paths {
+        if !path.exists() || uploaded.contains(&path) {
+            continue;
+        }
+        let key = destination.key(path, dir);
+        log::debug!(
+            "Uploading {} to {}",
+            path.display(),
+            destination.object_url(&key)
+        );
+        let status = Command::new(program)
+            .args(args)
+            .arg(path)
+            .arg(destination.object_url(&key))
+            .status()
+            .unwrap_or_else(|err| {
+                errors::exit(
+                    errors::Category::Output,
+                    None,
+                    None,
+                    &format!("Unable to run {} to upload the outputs: {}", program, err),
+                )
+            });
+        if !status.success() {
+            errors::exit(
+                errors::Category::Output,
+                Some(path),
+                None,
+                &format!(
+                    "Unable to upload {} to {} ({} {})",
+                    path.display(),
+                    destination.object_url(&key),
+                    program,
+                    status
+                ),
+            );
+        }
+        println!("{}", destination.https_url(&key));
+        uploaded.push(path);
+    }
+    log::info!("Uploaded {} outputs", uploaded.len());
+}